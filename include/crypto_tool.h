@@ -0,0 +1,172 @@
+/* CryptoTool C API */
+
+#ifndef CRYPTO_TOOL_H
+#define CRYPTO_TOOL_H
+
+/* このファイルはcbindgenで生成しています。直接編集しないでください。 */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// # 処理結果
+typedef enum CtStatus {
+  // 成功
+  CT_STATUS_OK = 0,
+  // 引数がNULL、または誤っている
+  CT_STATUS_INVALID_ARGUMENT = 1,
+  // 鍵が32byteではない
+  CT_STATUS_INVALID_KEY = 2,
+  // ヘッダーの途中でデータが終わっている
+  CT_STATUS_TRUNCATED_HEADER = 3,
+  // 書き出し先のバッファが足りない
+  CT_STATUS_BUFFER_TOO_SMALL = 4,
+  // ファイルが見つからない
+  CT_STATUS_NOT_FOUND = 5,
+  // ファイルにアクセスする権限がない
+  CT_STATUS_PERMISSION_DENIED = 6,
+  // その他の読み込み・書き込みのエラー
+  CT_STATUS_IO_ERROR = 7,
+  // このCPUでは使えない
+  CT_STATUS_UNSUPPORTED = 8,
+  // ライブラリの内部で異常終了した
+  CT_STATUS_PANIC = 9,
+  // 認証タグが一致しない (鍵が違うか、暗号文が改ざん・切り詰められている)
+  CT_STATUS_AUTHENTICATION_FAILED = 10,
+  // 対応していない形式のバージョン・暗号のファイル
+  CT_STATUS_UNSUPPORTED_FORMAT = 11,
+} CtStatus;
+
+// # 鍵のハンドル
+// `ct_key_generate`などで作成して、`ct_key_free`で解放します。
+typedef struct CtKey CtKey;
+
+// # 進捗の通知
+// 処理したサイズ(byte)と全体のサイズ(byte)、`user_data`を受け取ります。
+// 呼び出したスレッドとは別のスレッドから呼ばれることがありますが、同時に呼ばれることはありません。
+typedef void (*CtProgressCallback)(uint64_t processed, uint64_t total, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// # ライブラリのバージョン
+const char *ct_version(void);
+
+// # 最後のエラーのメッセージ
+// 同じスレッドで最後に失敗した関数のメッセージを返します。成功した場合はNULLを返します。
+// 文字列は同じスレッドで次にC APIの関数を呼ぶまで有効です。
+const char *ct_last_error_message(void);
+
+// # メッセージの言語の設定
+// `"ja"`か`"en"`を指定します。
+//
+// # Safety
+// `lang`はNULL終端の文字列を指している必要があります。
+enum CtStatus ct_set_language(const char *lang);
+
+// # 鍵の生成
+// OSの乱数で新しい鍵を生成して`out`に返します。
+//
+// # Safety
+// `out`は書き込める`CtKey *`を指している必要があります。
+enum CtStatus ct_key_generate(struct CtKey **out);
+
+// # バイト列から鍵の作成
+// 32byteのバイト列から鍵を作成して`out`に返します。
+//
+// # Safety
+// `bytes`は`len`byte読み込める領域を、`out`は書き込める`CtKey *`を指している必要があります。
+enum CtStatus ct_key_from_bytes(const uint8_t *bytes,
+                                size_t len,
+                                struct CtKey **out);
+
+// # 鍵ファイルの読み込み
+// 32byteの鍵ファイルを読み込んで`out`に返します。
+//
+// # Safety
+// `path`はNULL終端のUTF-8の文字列を、`out`は書き込める`CtKey *`を指している必要があります。
+enum CtStatus ct_key_from_file(const char *path,
+                               struct CtKey **out);
+
+// # 鍵の解放
+// メモリ上の鍵を0で上書きしてから解放します。NULLの場合は何もしません。
+//
+// # Safety
+// `key`は`ct_key_*`で作成してまだ解放していない鍵か、NULLである必要があります。
+void ct_key_free(struct CtKey *key);
+
+// # 暗号化したサイズ
+// `len`byteの平文を`ct_encrypt_buffer`で暗号化したときのサイズ(byte)を返します。
+size_t ct_encrypted_size(size_t len);
+
+// # 復号したサイズ
+// `len`byteの暗号文を復号したときのサイズ(byte)の最大値を返します。
+// 実際のサイズはヘッダーの形式と暗号によって小さくなります。
+size_t ct_decrypted_size(size_t len);
+
+// # ファイルの暗号化
+// `input_path`のファイルを暗号化して`output_path`に書き込みます。
+// 大きなファイルはCPUのコア数のスレッドで処理します。`progress`はNULLでも構いません。
+// 失敗した場合は`output_path`にファイルを残さず、既存のファイルも変更しません。
+//
+// # Safety
+// `key`は有効な鍵を、パスはNULL終端のUTF-8の文字列を指している必要があります。
+// `user_data`は`progress`に渡すだけで、別のスレッドから使える必要があります。
+enum CtStatus ct_encrypt_file(const struct CtKey *key,
+                              const char *input_path,
+                              const char *output_path,
+                              CtProgressCallback progress,
+                              void *user_data);
+
+// # ファイルの復号
+// `input_path`のファイルを復号して`output_path`に書き込みます。
+// 大きなファイルはCPUのコア数のスレッドで処理します。`progress`はNULLでも構いません。
+// 失敗した場合は`output_path`にファイルを残さず、既存のファイルも変更しません。
+//
+// # Safety
+// `key`は有効な鍵を、パスはNULL終端のUTF-8の文字列を指している必要があります。
+// `user_data`は`progress`に渡すだけで、別のスレッドから使える必要があります。
+enum CtStatus ct_decrypt_file(const struct CtKey *key,
+                              const char *input_path,
+                              const char *output_path,
+                              CtProgressCallback progress,
+                              void *user_data);
+
+// # バッファの暗号化
+// `input`の平文を暗号化して`output`に書き込み、書き込んだサイズを`output_len`に返します。
+// `output_capacity`が`ct_encrypted_size(input_len)`より小さい場合は`CT_STATUS_BUFFER_TOO_SMALL`を返し、
+// 必要なサイズを`output_len`に返します。
+//
+// # Safety
+// `input`は`input_len`byte読み込める領域を、`output`は`output_capacity`byte書き込める領域を、
+// `output_len`は書き込める`size_t`を指している必要があります。
+enum CtStatus ct_encrypt_buffer(const struct CtKey *key,
+                                const uint8_t *input,
+                                size_t input_len,
+                                uint8_t *output,
+                                size_t output_capacity,
+                                size_t *output_len);
+
+// # バッファの復号
+// `input`の暗号文を復号して`output`に書き込み、書き込んだサイズを`output_len`に返します。
+// `output_capacity`が復号したサイズより小さい場合は`CT_STATUS_BUFFER_TOO_SMALL`を返し、
+// 必要なサイズを`output_len`に返します。`ct_decrypted_size(input_len)`あれば足ります。
+//
+// # Safety
+// `input`は`input_len`byte読み込める領域を、`output`は`output_capacity`byte書き込める領域を、
+// `output_len`は書き込める`size_t`を指している必要があります。
+enum CtStatus ct_decrypt_buffer(const struct CtKey *key,
+                                const uint8_t *input,
+                                size_t input_len,
+                                uint8_t *output,
+                                size_t output_capacity,
+                                size_t *output_len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CRYPTO_TOOL_H */
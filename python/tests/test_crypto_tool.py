@@ -0,0 +1,150 @@
+"""Pythonのバインディングのテスト
+
+CLIで暗号化・復号したファイルと、Pythonで暗号化・復号したファイルが同じ形式であることを確認します。
+
+    maturin develop --features python
+    cargo build
+    python -m unittest discover python/tests
+
+CLIの実行ファイルは環境変数`CRYPTO_TOOL_BIN`で指定できます。(省略時は`target/debug/crypto_tool`)
+"""
+
+import io
+import os
+import subprocess
+import tempfile
+import unittest
+from pathlib import Path
+
+import crypto_tool
+
+ROOT = Path(__file__).resolve().parents[2]
+CLI = Path(os.environ.get("CRYPTO_TOOL_BIN", ROOT / "target" / "debug" / "crypto_tool"))
+
+
+def plain_text(size):
+    return bytes((i * 31) % 251 for i in range(size))
+
+
+class CryptoToolTest(unittest.TestCase):
+    def setUp(self):
+        self.tempdir = tempfile.TemporaryDirectory()
+        self.dir = Path(self.tempdir.name)
+        self.key_path = self.dir / "key.bin"
+        self.key_path.write_bytes(bytes(range(32)))
+        self.key = crypto_tool.Key.from_file(self.key_path)
+
+    def tearDown(self):
+        self.tempdir.cleanup()
+
+    def cli(self, mode, input_path, output_path):
+        subprocess.run(
+            [str(CLI), mode, "-k", str(self.key_path), "-o", str(output_path), str(input_path)],
+            check=True,
+            stdout=subprocess.DEVNULL,
+            stderr=subprocess.DEVNULL,
+        )
+
+    def test_decrypt_file_encrypted_by_cli(self):
+        data = plain_text(1024 * 1024 + 3)
+        (self.dir / "data.bin").write_bytes(data)
+        self.cli("encrypt", self.dir / "data.bin", self.dir / "data.bin.c20")
+
+        with open(self.dir / "data.bin.c20", "rb") as src, open(self.dir / "out.bin", "wb") as dst:
+            size = crypto_tool.decrypt(self.key, src, dst)
+
+        self.assertEqual(size, len(data))
+        self.assertEqual((self.dir / "out.bin").read_bytes(), data)
+
+    def test_cli_decrypts_file_encrypted_by_python(self):
+        data = plain_text(300_001)
+        with open(self.dir / "data.bin.c20", "wb") as dst:
+            size = crypto_tool.encrypt(self.key, io.BytesIO(data), dst)
+        self.assertEqual(size, len(data))
+        header = crypto_tool.Header.parse((self.dir / "data.bin.c20").read_bytes())
+        self.assertEqual((self.dir / "data.bin.c20").stat().st_size, len(data) + header.size)
+
+        self.cli("decrypt", self.dir / "data.bin.c20", self.dir / "out.bin")
+        self.assertEqual((self.dir / "out.bin").read_bytes(), data)
+
+    def test_read_header_of_cli_file(self):
+        (self.dir / "data.bin").write_bytes(b"hello")
+        self.cli("encrypt", self.dir / "data.bin", self.dir / "data.bin.c20")
+        encrypted = (self.dir / "data.bin.c20").read_bytes()
+
+        with open(self.dir / "data.bin.c20", "rb") as src:
+            header = crypto_tool.read_header(src)
+            self.assertEqual(src.tell(), header.size)
+        self.assertEqual(header.cipher, "chacha20")
+        # ソルトとナンスは固定長の部分(16byte)の後ろに続く
+        self.assertEqual(header.salt, encrypted[16 : 16 + len(header.salt)])
+        nonce_start = 16 + len(header.salt)
+        self.assertEqual(header.nonce, encrypted[nonce_start : nonce_start + len(header.nonce)])
+        self.assertEqual(crypto_tool.Header.parse(encrypted).nonce, header.nonce)
+
+    def test_bytes_round_trip(self):
+        data = plain_text(10_000)
+        encrypted = crypto_tool.encrypt_bytes(self.key, data)
+        header = crypto_tool.Header.parse(encrypted)
+        self.assertEqual(len(encrypted), len(data) + header.size)
+        self.assertNotEqual(encrypted[header.size :], data)
+        self.assertEqual(crypto_tool.decrypt_bytes(self.key, encrypted), data)
+
+        output = io.BytesIO()
+        crypto_tool.decrypt(self.key, io.BytesIO(encrypted), output)
+        self.assertEqual(output.getvalue(), data)
+
+    def test_aead_ciphers_round_trip_with_cli(self):
+        data = plain_text(200_003)
+        for cipher in ("chacha20poly1305", "xchacha20poly1305", "aes256gcm"):
+            with self.subTest(cipher=cipher):
+                encrypted = crypto_tool.encrypt_bytes(self.key, data, cipher=cipher)
+                self.assertEqual(crypto_tool.Header.parse(encrypted).cipher, cipher)
+                (self.dir / "data.bin.c20").write_bytes(encrypted)
+                self.cli("decrypt", self.dir / "data.bin.c20", self.dir / "out.bin")
+                self.assertEqual((self.dir / "out.bin").read_bytes(), data)
+
+                # 改ざんした暗号文は復号できない
+                tampered = bytearray(encrypted)
+                tampered[-1] ^= 1
+                with self.assertRaises(crypto_tool.CryptoError):
+                    crypto_tool.decrypt_bytes(self.key, bytes(tampered))
+
+        with self.assertRaises(ValueError):
+            crypto_tool.encrypt_bytes(self.key, data, cipher="rot13")
+
+    def test_empty_input(self):
+        output = io.BytesIO()
+        self.assertEqual(crypto_tool.encrypt(self.key, io.BytesIO(), output), 0)
+        self.assertEqual(len(output.getvalue()), crypto_tool.Header.parse(output.getvalue()).size)
+        self.assertEqual(crypto_tool.decrypt_bytes(self.key, output.getvalue()), b"")
+
+    def test_keys(self):
+        self.assertEqual(crypto_tool.Key(bytes(range(32))).fingerprint, self.key.fingerprint)
+        self.assertNotEqual(crypto_tool.Key.generate().fingerprint, self.key.fingerprint)
+        with self.assertRaises(ValueError):
+            crypto_tool.Key(b"short")
+
+        (self.dir / "bad.bin").write_bytes(b"x" * 31)
+        with self.assertRaises(ValueError):
+            crypto_tool.Key.from_file(self.dir / "bad.bin")
+        with self.assertRaises(FileNotFoundError):
+            crypto_tool.Key.from_file(self.dir / "missing.bin")
+
+    def test_truncated_header(self):
+        with self.assertRaises(crypto_tool.CryptoError):
+            crypto_tool.decrypt_bytes(self.key, b"short")
+        with self.assertRaises(crypto_tool.CryptoError):
+            crypto_tool.read_header(io.BytesIO(b"short"))
+
+    def test_reader_error_is_propagated(self):
+        class BrokenReader:
+            def read(self, size):
+                raise KeyError("broken")
+
+        with self.assertRaises(KeyError):
+            crypto_tool.encrypt(self.key, BrokenReader(), io.BytesIO())
+
+
+if __name__ == "__main__":
+    unittest.main()
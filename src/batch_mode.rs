@@ -0,0 +1,556 @@
+//! # 一括暗号化・復号モード
+//! 複数のファイルやディレクトリをまとめて暗号化・復号するモジュール
+//! 途中のファイルで失敗しても残りのファイルの処理を続けて、最後に結果の一覧を表示します。
+//! `--json`が指定された場合は一覧の代わりにファイルごとの結果をJSONで表示します。
+//! 失敗したファイルがあっても、ファイルごとの結果のほかにコマンドの失敗の結果は表示しません。
+
+use super::cli_arg_accepter::{CliArg, Mode};
+use super::crypto;
+use super::crypto_mode::{self, CryptoMode, ProcessSetting, Processed};
+use super::message::{self, Message};
+use super::report::{self, Report};
+use log::debug;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// 処理対象のファイル
+struct BatchTarget {
+    /// 入力ファイルのパス
+    input_file_path: PathBuf,
+    /// 入力として指定されたディレクトリからの相対パス
+    relative_path: PathBuf,
+    /// ディレクトリの中から見つけたファイルか
+    found_in_dir: bool,
+}
+
+/// ファイルごとの処理結果
+enum BatchStatus {
+    Success,
+    Skipped,
+    Failed(io::Error),
+}
+
+/// ファイルごとの処理結果の一覧の行
+struct BatchResult {
+    input_file_path: PathBuf,
+    output_file_path: Option<PathBuf>,
+    status: BatchStatus,
+    /// 処理したサイズ(byte)
+    bytes: u64,
+    /// 暗号 処理しなかった場合はNone
+    cipher: Option<crypto::Cipher>,
+    /// 処理にかかった時間
+    elapsed: std::time::Duration,
+}
+
+/// すべてのファイルに共通の処理の設定
+struct BatchSetting<'a> {
+    /// 書き出し先のディレクトリ
+    output_dir: Option<&'a Path>,
+    key: &'a crypto::Key,
+    crypto_mode: CryptoMode,
+    process_setting: ProcessSetting,
+}
+
+/// 処理中のファイルのプログレスバーの表示形式
+const FILE_PROGRESS_TEMPLATE: &str = "{msg:30!} [{wide_bar}] {bytes}/{total_bytes} ({eta})";
+
+/// # 一括処理するかの判定
+/// 入力が複数ある場合、ディレクトリが含まれる場合、`--recursive`が指定された場合に一括処理します。
+pub fn is_batch(cli_arg: &CliArg) -> bool {
+    match cli_arg.mode {
+        Mode::Encrypt | Mode::Decrypt => {
+            cli_arg.recursive
+                || cli_arg.input_file_paths.len() > 1
+                || cli_arg
+                    .input_file_paths
+                    .iter()
+                    .any(|path| Path::new(path).is_dir())
+        }
+        _ => false,
+    }
+}
+
+/// # 一括暗号化・復号モード
+/// 1つでも失敗したファイルがあればErrを返します。
+pub fn batch_mode(cli_arg: CliArg) -> io::Result<()> {
+    let crypto_mode = match cli_arg.mode {
+        Mode::Decrypt => CryptoMode::Decrypt,
+        _ => CryptoMode::Encrypt,
+    };
+
+    // 標準入力は一括処理できない
+    if cli_arg.input_file_paths.iter().any(|path| path == "-") {
+        debug!("一括処理では標準入力を使えません。");
+        eprintln!("{}", message::text(Message::BatchStdin));
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            message::text(Message::BatchStdin),
+        ));
+    }
+
+    // 複数のファイルを同時に処理するので、1ファイルは指定がなければ1スレッドで処理する
+    let process_setting = ProcessSetting::from_cli_arg(&cli_arg, 1)?;
+    process_setting.check_deterministic()?;
+
+    // ファイルパターンを用意する
+    let include_patterns = compile_patterns(&cli_arg.include_patterns)?;
+    let exclude_patterns = compile_patterns(&cli_arg.exclude_patterns)?;
+
+    // 鍵データを読み込む
+    let key = crypto_mode::read_key(cli_arg.key_file_path)?;
+
+    // 処理対象のファイルを集める
+    // 書き出したファイルを再び処理しないように、処理を始める前にすべて集めておく
+    let mut targets = Vec::new();
+    for input_file_path in &cli_arg.input_file_paths {
+        collect_targets(Path::new(input_file_path), cli_arg.recursive, &mut targets)?;
+    }
+    let targets = targets.into_iter().filter(|target| {
+        let included = include_patterns.is_empty()
+            || include_patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(&target.relative_path));
+        let excluded = exclude_patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(&target.relative_path));
+        included && !excluded
+    });
+
+    let targets = targets.collect::<Vec<_>>();
+    let output_dir = cli_arg.output_file_path.map(PathBuf::from);
+    check_duplicate_outputs(&targets, output_dir.as_deref())?;
+
+    // 同時に処理するファイル数を決める
+    let jobs = cli_arg
+        .jobs
+        .unwrap_or_else(crypto_mode::default_threads)
+        .clamp(1, targets.len().max(1));
+    debug!("jobs: {}", jobs);
+
+    // ファイルごとに暗号化・復号する
+    let setting = BatchSetting {
+        output_dir: output_dir.as_deref(),
+        key: &key,
+        crypto_mode,
+        process_setting,
+    };
+    let results = crypto_targets(&targets, &setting, jobs);
+
+    if cli_arg.json {
+        for result in &results {
+            report::print_json(&to_report(result, &setting));
+        }
+    } else {
+        print_summary(&results);
+    }
+    if let CryptoMode::Encrypt = setting.crypto_mode {
+        setting.process_setting.warn_key_usage(setting.key);
+    }
+
+    let failed_count = results
+        .iter()
+        .filter(|result| matches!(result.status, BatchStatus::Failed(_)))
+        .count();
+    if failed_count > 0 {
+        return Err(io::Error::other(FilesFailed(failed_count)));
+    }
+    Ok(())
+}
+
+/// # ファイルの処理に失敗したエラー
+/// 失敗したファイルの数を持ちます。ファイルごとの結果は表示済みです。
+#[derive(Debug)]
+struct FilesFailed(usize);
+
+impl std::fmt::Display for FilesFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", message::format(Message::BatchFailed, &[&self.0]))
+    }
+}
+
+impl std::error::Error for FilesFailed {}
+
+/// # ファイルごとの結果を表示済みのエラーか
+/// 一括処理で一部のファイルの処理に失敗した場合は`true`を返します。
+pub fn is_reported(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<FilesFailed>())
+}
+
+/// # ファイルパターンの用意
+/// `--include`、`--exclude`で指定されたglobパターンを解析します。
+fn compile_patterns(patterns: &[String]) -> io::Result<Vec<glob::Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern).map_err(|e| {
+                debug!("ファイルパターンが誤っています。");
+                debug!("{:?}", e);
+                eprintln!("{}", message::format(Message::InvalidPattern, &[pattern]));
+                io::Error::new(io::ErrorKind::InvalidInput, e.to_string())
+            })
+        })
+        .collect()
+}
+
+/// # 処理対象のファイルを集める
+/// ファイルが指定された場合はそのファイルを、ディレクトリが指定された場合はその中のファイルを追加します。
+/// `recursive`が`false`の場合はサブディレクトリの中は見ません。
+/// ディレクトリの中のディレクトリへのシンボリックリンクはたどりません。
+fn collect_targets(
+    input_path: &Path,
+    recursive: bool,
+    targets: &mut Vec<BatchTarget>,
+) -> io::Result<()> {
+    if !input_path.is_dir() {
+        targets.push(BatchTarget {
+            input_file_path: input_path.to_path_buf(),
+            relative_path: PathBuf::from(input_path.file_name().unwrap_or_default()),
+            found_in_dir: false,
+        });
+        return Ok(());
+    }
+
+    // ディレクトリはディレクトリ名から下の階層をそのまま書き出し先に再現する
+    let base_dir = input_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut dirs = vec![input_path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("ディレクトリを読み込めませんでした。");
+                debug!("{:?}", e);
+                eprintln!(
+                    "{}",
+                    message::format(Message::ReadDirFailed, &[&dir.display()])
+                );
+                return Err(e);
+            }
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                // 親のディレクトリへのリンクがあると終わらなくなるので、リンクの先は処理しない
+                let is_symlink = path
+                    .symlink_metadata()
+                    .is_ok_and(|metadata| metadata.file_type().is_symlink());
+                if is_symlink {
+                    debug!("ディレクトリへのシンボリックリンク{:?}を飛ばします。", path);
+                } else if recursive {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            let relative_path = path.strip_prefix(base_dir).unwrap_or(&path).to_path_buf();
+            targets.push(BatchTarget {
+                input_file_path: path,
+                relative_path,
+                found_in_dir: true,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// # ファイルの並列処理
+/// `jobs`個のスレッドでファイルを暗号化・復号します。
+/// スレッドごとに処理中のファイルのプログレスバーを表示して、一番下に全体のプログレスバーを表示します。
+/// 処理結果は`targets`と同じ順番で返します。スレッドが異常終了したファイルは失敗した結果にします。
+fn crypto_targets(
+    targets: &[BatchTarget],
+    setting: &BatchSetting,
+    jobs: usize,
+) -> Vec<BatchResult> {
+    let multi_progress = indicatif::MultiProgress::new();
+    // MultiProgressはスレッド間で共有できないので、プログレスバーは先に作っておく
+    let file_progress_bars = (0..jobs)
+        .map(|_| {
+            let progress_bar = multi_progress.add(indicatif::ProgressBar::new(0));
+            progress_bar.set_style(
+                indicatif::ProgressStyle::default_bar().template(FILE_PROGRESS_TEMPLATE),
+            );
+            progress_bar
+        })
+        .collect::<Vec<_>>();
+    let overall_progress_bar =
+        multi_progress.add(indicatif::ProgressBar::new(targets.len() as u64));
+    overall_progress_bar.set_style(
+        indicatif::ProgressStyle::default_bar().template(message::text(Message::OverallProgress)),
+    );
+
+    let next_index = AtomicUsize::new(0);
+    let results = Mutex::new((0..targets.len()).map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        let workers = file_progress_bars
+            .into_iter()
+            .map(|progress_bar| {
+                let next_index = &next_index;
+                let results = &results;
+                let overall_progress_bar = &overall_progress_bar;
+                scope.spawn(move || {
+                    // 処理していないファイルがなくなるまで1つずつ取り出して処理する
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::SeqCst);
+                        let target = match targets.get(index) {
+                            Some(target) => target,
+                            None => break,
+                        };
+                        progress_bar.reset();
+                        progress_bar.set_message(target.input_file_path.display().to_string());
+                        let result = crypto_target(target, setting, progress_bar.clone());
+                        overall_progress_bar.inc(1);
+                        results
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())[index] = Some(result);
+                    }
+                    progress_bar.finish_and_clear();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // すべてのスレッドが終わったら全体のプログレスバーを終了させる
+        let overall_progress_bar = &overall_progress_bar;
+        scope.spawn(move || {
+            for worker in workers {
+                let _ = worker.join();
+            }
+            overall_progress_bar.finish();
+        });
+
+        // プログレスバーの描画 全てのプログレスバーが終了するまで戻らない
+        let _ = multi_progress.join();
+    });
+
+    // 異常終了したスレッドが処理していたファイルは結果がないので、失敗として扱う
+    results
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .into_iter()
+        .zip(targets)
+        .map(|(result, target)| {
+            result.unwrap_or_else(|| {
+                debug!(
+                    "{:?}を処理していたスレッドが異常終了しました。",
+                    target.input_file_path
+                );
+                BatchResult {
+                    input_file_path: target.input_file_path.clone(),
+                    output_file_path: None,
+                    status: BatchStatus::Failed(crypto::Error::ThreadPanicked.into()),
+                    bytes: 0,
+                    cipher: None,
+                    elapsed: std::time::Duration::ZERO,
+                }
+            })
+        })
+        .collect()
+}
+
+/// # 書き出し先の重複の確認
+/// 別のディレクトリの同じ名前のファイルを`-o`で同じディレクトリに書き出す場合など、
+/// 書き出し先が同じになるファイルがあれば、上書きしないように処理を始める前にエラーにします。
+fn check_duplicate_outputs(targets: &[BatchTarget], output_dir: Option<&Path>) -> io::Result<()> {
+    let mut output_base_paths = HashMap::new();
+    for target in targets {
+        if let Some(other) = output_base_paths.insert(
+            output_base_path(target, output_dir),
+            &target.input_file_path,
+        ) {
+            let text = message::format(
+                Message::DuplicateOutput,
+                &[&other.display(), &target.input_file_path.display()],
+            );
+            debug!("書き出し先が同じになるファイルがあります。");
+            eprintln!("{}", text);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, text));
+        }
+    }
+    Ok(())
+}
+
+/// # 拡張子を変える前の書き出し先のパス
+/// 書き出し先のディレクトリがなければ、入力ファイルと同じ場所に書き出します。
+fn output_base_path(target: &BatchTarget, output_dir: Option<&Path>) -> PathBuf {
+    match output_dir {
+        Some(output_dir) => output_dir.join(&target.relative_path),
+        None => target.input_file_path.clone(),
+    }
+}
+
+/// # 1ファイルの暗号化・復号
+/// 書き出し先のディレクトリが指定されている場合は、入力のディレクトリ構成をそのまま再現します。
+fn crypto_target(
+    target: &BatchTarget,
+    setting: &BatchSetting,
+    progress_bar: indicatif::ProgressBar,
+) -> BatchResult {
+    // ディレクトリの中のファイルを復号する場合は.c20以外のファイルを、
+    // 暗号化する場合は暗号化済みの.c20のファイルを飛ばす
+    if let (true, CryptoMode::Decrypt, CryptoMode::Encrypt)
+    | (true, CryptoMode::Encrypt, CryptoMode::Decrypt) = (
+        target.found_in_dir,
+        setting.crypto_mode,
+        crypto_mode::detect_crypto_mode(&target.input_file_path),
+    ) {
+        return BatchResult {
+            input_file_path: target.input_file_path.clone(),
+            output_file_path: None,
+            status: BatchStatus::Skipped,
+            bytes: 0,
+            cipher: None,
+            elapsed: std::time::Duration::ZERO,
+        };
+    }
+    let start_time = std::time::Instant::now();
+    let (output_file_path, status, processed) = crypto_target_file(target, setting, progress_bar);
+    BatchResult {
+        input_file_path: target.input_file_path.clone(),
+        output_file_path,
+        status,
+        bytes: processed.map_or(0, |processed| processed.bytes),
+        cipher: processed.map(|processed| processed.cipher),
+        elapsed: start_time.elapsed(),
+    }
+}
+
+/// # 書き出し先を決めて暗号化・復号する
+/// 書き出し先のパス、処理結果、処理したサイズ(byte)と暗号を返します。
+fn crypto_target_file(
+    target: &BatchTarget,
+    setting: &BatchSetting,
+    progress_bar: indicatif::ProgressBar,
+) -> (Option<PathBuf>, BatchStatus, Option<Processed>) {
+    let output_base_path = output_base_path(target, setting.output_dir);
+    let output_file_path =
+        match crypto_mode::prepare_output_file_name(output_base_path, setting.crypto_mode) {
+            Ok(path) => path,
+            Err(e) => return (None, BatchStatus::Failed(e), None),
+        };
+    if let Some(parent) = output_file_path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            debug!("書き出し先のディレクトリを作成出来ませんでした。");
+            debug!("{:?}", e);
+            eprintln!("{}", message::text(Message::OutputDirCreateFailed));
+            return (Some(output_file_path), BatchStatus::Failed(e), None);
+        }
+    }
+    match crypto_mode::crypto_file(
+        &target.input_file_path,
+        &output_file_path,
+        setting.key,
+        setting.crypto_mode,
+        Some(progress_bar),
+        &setting.process_setting,
+    ) {
+        Ok(processed) => (
+            Some(output_file_path),
+            BatchStatus::Success,
+            Some(processed),
+        ),
+        Err(e) => (Some(output_file_path), BatchStatus::Failed(e), None),
+    }
+}
+
+/// # JSONで表示する処理結果に変換する
+fn to_report(result: &BatchResult, setting: &BatchSetting) -> Report {
+    let report = Report::new(
+        setting.crypto_mode,
+        Some(&result.input_file_path),
+        result.output_file_path.as_deref(),
+        result.bytes,
+        result.elapsed,
+        result.cipher,
+        setting.key,
+    );
+    match &result.status {
+        BatchStatus::Success => report,
+        BatchStatus::Skipped => report.skipped(),
+        BatchStatus::Failed(e) => report.failed(e),
+    }
+}
+
+/// # 処理結果の一覧を表示する
+fn print_summary(results: &[BatchResult]) {
+    let rows = results
+        .iter()
+        .map(|result| {
+            let (status, message) = match &result.status {
+                BatchStatus::Success => ("OK", String::new()),
+                BatchStatus::Skipped => ("SKIP", String::new()),
+                BatchStatus::Failed(e) => ("NG", e.to_string()),
+            };
+            let output_file_path = result
+                .output_file_path
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| "-".to_string());
+            [
+                status.to_string(),
+                result.input_file_path.display().to_string(),
+                output_file_path,
+                message,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = [
+        message::text(Message::SummaryResult).to_string(),
+        message::text(Message::SummaryInput).to_string(),
+        message::text(Message::SummaryOutput).to_string(),
+        message::text(Message::SummaryError).to_string(),
+    ];
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(message::display_width(cell));
+        }
+    }
+    let print_row = |row: &[String; 4]| {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| {
+                format!(
+                    "{}{}",
+                    cell,
+                    " ".repeat(width - message::display_width(cell))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        println!("{}", line.trim_end());
+    };
+
+    print_row(&header);
+    println!(
+        "{}",
+        widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-")
+    );
+    for row in &rows {
+        print_row(row);
+    }
+
+    let count = |f: fn(&BatchStatus) -> bool| results.iter().filter(|r| f(&r.status)).count();
+    println!(
+        "{}",
+        message::format(
+            Message::SummaryCount,
+            &[
+                &count(|status| matches!(status, BatchStatus::Success)),
+                &count(|status| matches!(status, BatchStatus::Skipped)),
+                &count(|status| matches!(status, BatchStatus::Failed(_))),
+            ]
+        )
+    );
+}
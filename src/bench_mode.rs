@@ -0,0 +1,303 @@
+//! # ベンチマークモード
+//! メモリ上のバッファを暗号化して、暗号とバックエンドごとの処理速度を表示するモジュール
+//! ファイルの読み書きを含まないので、暗号の実装だけの速度を比較できます。
+//! バックエンドはChaCha20の実装なので、認証付き暗号はバックエンドを切り替えずに1回だけ計測します。
+//! AES-256-GCMはAES-NIを使えるCPUでは`aesni`と表示します。
+//! 設定の選択や、性能の劣化の確認に使います。
+
+use super::cli_arg_accepter::CliArg;
+use super::crypto::{self, Backend, ChaCha20Cipher, Cipher, Key, MAX_NONCE_SIZE};
+use super::message::{self, Message};
+use super::report;
+use log::debug;
+use serde::Serialize;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// 省略時に計測するバッファのサイズ(byte)
+pub const DEFAULT_SIZES: [usize; 4] = [1024, 64 * 1024, 1024 * 1024, 16 * 1024 * 1024];
+/// 省略時の1つの組み合わせを計測する時間(秒)
+pub const DEFAULT_SECONDS: f64 = 0.5;
+
+/// # ベンチマークの設定
+pub struct BenchSetting {
+    /// 計測するバッファのサイズ(byte)
+    pub sizes: Vec<usize>,
+    /// 1つの組み合わせを計測する時間
+    pub duration: Duration,
+}
+
+impl BenchSetting {
+    /// # CLI引数からベンチマークの設定を作成
+    /// 指定されていない値は省略時の値を使います。
+    /// 計測する時間が有限の正の数ではない場合はエラーを返します。
+    pub fn from_cli_arg(cli_arg: &CliArg) -> io::Result<Self> {
+        let sizes = if cli_arg.bench_sizes.is_empty() {
+            DEFAULT_SIZES.to_vec()
+        } else {
+            cli_arg.bench_sizes.clone()
+        };
+        let seconds = cli_arg.bench_seconds.unwrap_or(DEFAULT_SECONDS);
+        if !seconds.is_finite() || seconds <= 0.0 {
+            debug!("計測する時間の指定が誤っています。");
+            let text = message::format(Message::InvalidSeconds, &[&seconds]);
+            eprintln!("{}", text);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, text));
+        }
+        Ok(BenchSetting {
+            sizes,
+            duration: Duration::from_secs_f64(seconds),
+        })
+    }
+}
+
+/// # 計測する暗号
+/// 暗号を追加する場合はここに追加します。
+struct BenchCipher {
+    cipher: Cipher,
+    /// バックエンドごとに計測するか
+    uses_backend: bool,
+    /// バッファ1つ分を暗号化する関数
+    run: fn(Cipher, Backend, &mut Vec<u8>),
+}
+
+const BENCH_CIPHERS: [BenchCipher; 5] = [
+    BenchCipher {
+        cipher: Cipher::ChaCha20,
+        uses_backend: true,
+        run: run_chacha20,
+    },
+    BenchCipher {
+        cipher: Cipher::ChaCha20Poly1305,
+        uses_backend: false,
+        run: run_aead,
+    },
+    BenchCipher {
+        cipher: Cipher::XChaCha20Poly1305,
+        uses_backend: false,
+        run: run_aead,
+    },
+    BenchCipher {
+        cipher: Cipher::Aes256Gcm,
+        uses_backend: false,
+        run: run_aead,
+    },
+    BenchCipher {
+        cipher: Cipher::Aes256GcmSiv,
+        uses_backend: false,
+        run: run_aead,
+    },
+];
+
+/// # 計測結果
+#[derive(Serialize)]
+struct BenchResult {
+    operation: &'static str,
+    status: &'static str,
+    cipher: &'static str,
+    backend: &'static str,
+    size: usize,
+    /// 1秒あたりに処理したサイズ(MB、1MB = 10^6 byte)
+    megabytes_per_second: f64,
+    /// 1byteあたりのサイクル数 タイムスタンプカウンタが使えない場合はNone
+    cycles_per_byte: Option<f64>,
+}
+
+/// # ベンチマークモード
+/// `json`が`true`の場合は計測結果を1つずつJSONで表示します。
+pub fn bench_mode(setting: BenchSetting, json: bool) {
+    let backends = bench_backends();
+
+    let widths = column_widths();
+    if !json {
+        print_header(&widths);
+    }
+    for cipher in BENCH_CIPHERS.iter() {
+        let cipher_backends = match cipher.uses_backend {
+            true => backends.iter().copied().map(Some).collect(),
+            false => vec![None],
+        };
+        for &backend in cipher_backends.iter() {
+            for &size in setting.sizes.iter() {
+                let result = bench(cipher, backend, size, setting.duration);
+                if json {
+                    report::print_json(&result);
+                } else {
+                    print_result(&result, &widths);
+                }
+            }
+        }
+    }
+}
+
+/// # 計測するバックエンド
+/// `--backend`で指定された場合はそのバックエンドだけを計測します。
+/// 自動選択の場合は自動で選ばれるバックエンドとポータブル実装を計測します。
+fn bench_backends() -> Vec<Backend> {
+    match crypto::selected_backend() {
+        Backend::Auto => {
+            let detected = crypto::detect_backend();
+            if detected == Backend::Soft {
+                vec![Backend::Soft]
+            } else {
+                vec![detected, Backend::Soft]
+            }
+        }
+        backend => vec![backend],
+    }
+}
+
+/// # 1つの組み合わせの計測
+/// `duration`が経過するまで同じバッファの暗号化を繰り返します。
+/// `backend`がNoneの場合は選択されているバックエンドで計測し、バックエンドは暗号の実装を表示します。
+fn bench(
+    cipher: &BenchCipher,
+    backend: Option<Backend>,
+    size: usize,
+    duration: Duration,
+) -> BenchResult {
+    let mut buffer = vec![0; size];
+    let run_backend = backend.unwrap_or_else(crypto::selected_backend);
+
+    // 1回目はキャッシュなどの影響を受けるので計測しない
+    (cipher.run)(cipher.cipher, run_backend, &mut buffer);
+
+    let mut bytes = 0u64;
+    let start_cycles = read_cycle_counter();
+    let start = Instant::now();
+    loop {
+        (cipher.run)(cipher.cipher, run_backend, &mut buffer);
+        bytes += size as u64;
+        if start.elapsed() >= duration {
+            break;
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let end_cycles = read_cycle_counter();
+
+    BenchResult {
+        operation: "bench",
+        status: "success",
+        cipher: cipher.cipher.name(),
+        backend: backend.map_or_else(|| cipher.cipher.backend(), Backend::name),
+        size,
+        megabytes_per_second: bytes as f64 / 1_000_000.0 / elapsed,
+        cycles_per_byte: start_cycles
+            .zip(end_cycles)
+            .map(|(start, end)| end.wrapping_sub(start) as f64 / bytes as f64),
+    }
+}
+
+/// # ChaCha20の計測
+/// 鍵とナンスの準備も含めて計測します。
+// 認証タグを追加する暗号と関数の型をそろえるので`Vec`を受け取る
+#[allow(clippy::ptr_arg)]
+fn run_chacha20(_cipher: Cipher, backend: Backend, buffer: &mut Vec<u8>) {
+    let mut cipher = ChaCha20Cipher::with_backend(backend, &[0; 32], &[0; 12]);
+    cipher.apply_keystream(buffer);
+}
+
+/// # 認証付き暗号の計測
+/// バッファ全体を1つのチャンクとして暗号化します。鍵とナンスの準備も含めて計測します。
+/// 次の計測で同じサイズを暗号化できるように、追加された認証タグは取り除きます。
+fn run_aead(cipher: Cipher, _backend: Backend, buffer: &mut Vec<u8>) {
+    let suite = cipher.suite();
+    let size = buffer.len();
+    let chunk_cipher = suite.chunk_cipher(
+        &Key::new([0; 32]),
+        &[0; MAX_NONCE_SIZE][..suite.nonce_size()],
+        size as u32,
+        &[],
+    );
+    // 平文のサイズの上限はないので失敗しない
+    let _ = chunk_cipher.seal(0, true, buffer);
+    buffer.truncate(size);
+}
+
+/// # サイクル数の取得
+/// タイムスタンプカウンタの値を返します。x86_64以外ではNoneを返します。
+/// タイムスタンプカウンタは一定の周波数で進むので、CPUのクロックが変わる場合は目安になります。
+fn read_cycle_counter() -> Option<u64> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: x86_64のCPUは必ずRDTSC命令を持っている
+        Some(unsafe { core::arch::x86_64::_rdtsc() })
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        None
+    }
+}
+
+/// # 表の見出し
+const HEADER: [Message; 5] = [
+    Message::BenchHeaderCipher,
+    Message::BenchHeaderBackend,
+    Message::BenchHeaderSize,
+    Message::BenchHeaderSpeed,
+    Message::BenchHeaderCyclesPerByte,
+];
+
+/// # 表の列の幅
+/// 計測結果が収まる幅と、見出しの表示幅の大きい方にします。
+fn column_widths() -> [usize; 5] {
+    let mut widths = [18, 8, 8, 10, 12];
+    for (width, header) in widths.iter_mut().zip(HEADER) {
+        *width = (*width).max(message::display_width(message::text(header)));
+    }
+    widths
+}
+
+/// # 表の見出しの表示
+/// 暗号と実装は左寄せ、数値の列は右寄せにします。
+fn print_header(widths: &[usize; 5]) {
+    let line = HEADER
+        .iter()
+        .zip(widths)
+        .enumerate()
+        .map(|(column, (&header, &width))| {
+            let text = message::text(header);
+            let padding = " ".repeat(width - message::display_width(text));
+            if column < 2 {
+                format!("{}{}", text, padding)
+            } else {
+                format!("{}{}", padding, text)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!("{}", line.trim_end());
+}
+
+/// # 計測結果の表示
+fn print_result(result: &BenchResult, widths: &[usize; 5]) {
+    let cycles_per_byte = match result.cycles_per_byte {
+        Some(cycles_per_byte) => format!("{:.2}", cycles_per_byte),
+        None => "-".to_string(),
+    };
+    println!(
+        "{:<w0$} {:<w1$} {:>w2$} {:>w3$.2} {:>w4$}",
+        result.cipher,
+        result.backend,
+        format_size(result.size),
+        result.megabytes_per_second,
+        cycles_per_byte,
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+        w3 = widths[3],
+        w4 = widths[4],
+    );
+}
+
+/// # サイズの表示
+/// 割り切れる場合は`K`、`M`、`G`の単位を付けて表示します。
+fn format_size(size: usize) -> String {
+    const UNITS: [(usize, &str); 3] = [(1024 * 1024 * 1024, "G"), (1024 * 1024, "M"), (1024, "K")];
+    for (unit, suffix) in UNITS.iter() {
+        if size >= *unit && size.is_multiple_of(*unit) {
+            return format!("{}{}", size / unit, suffix);
+        }
+    }
+    size.to_string()
+}
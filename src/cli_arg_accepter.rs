@@ -1,16 +1,216 @@
 //! CLI引数を受け取るモジュール
 
 // Cli ArgumentParser
+use super::crypto::{Backend, Cipher};
+use super::message::{self, Lang, Message};
 use clap::*;
 use log::debug;
 
 pub enum Mode {
+    /// `-i`で渡されたファイルの拡張子から暗号化・復号を判定するモード
     CliCrypto,
+    /// `encrypt`サブコマンド
+    Encrypt,
+    /// `decrypt`サブコマンド
+    Decrypt,
+    /// `diagnostics`サブコマンド
+    Diagnostics,
+    /// `bench`サブコマンド
+    Bench,
+    /// `selftest`サブコマンド
+    Selftest,
     Gui,
 }
 
+/// # 復号する範囲
+/// 平文の`offset`byteの位置から`length`byte
+#[derive(Clone, Copy)]
+pub struct DecryptRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// # 受け取ったCLI引数
+/// ファイルパスに`-`が指定された場合は標準入力・標準出力を表します。
+pub struct CliArg {
+    pub input_file_paths: Vec<String>,
+    pub key_file_path: Option<String>,
+    /// 複数のファイルを処理する場合は書き出し先のディレクトリ
+    pub output_file_path: Option<String>,
+    pub recursive: bool,
+    pub include_patterns: Vec<String>,
+    pub exclude_patterns: Vec<String>,
+    /// 同時に処理するファイル数
+    pub jobs: Option<usize>,
+    /// 1ファイルの処理に使うスレッド数
+    pub threads: Option<usize>,
+    /// パイプラインのバッファのサイズ(byte)
+    pub buffer_size: Option<usize>,
+    /// パイプラインのバッファの数
+    pub pipeline_depth: Option<usize>,
+    /// 復号する範囲 (`decrypt`サブコマンドだけ)
+    pub range: Option<DecryptRange>,
+    /// 暗号化に使う暗号 (`encrypt`サブコマンドだけ)
+    pub cipher: Cipher,
+    /// 同じ平文を同じ暗号文に暗号化する (`encrypt`サブコマンドだけ)
+    pub deterministic: bool,
+    /// 使ったナンスを記録する台帳のファイルパス (`encrypt`サブコマンドだけ)
+    pub ledger_path: Option<String>,
+    /// ChaCha20の実装
+    pub backend: Backend,
+    /// 処理結果をJSONで表示する
+    pub json: bool,
+    /// ベンチマークで計測するバッファのサイズ(byte)
+    pub bench_sizes: Vec<usize>,
+    /// ベンチマークで1つの組み合わせを計測する時間(秒)
+    pub bench_seconds: Option<f64>,
+    pub mode: Mode,
+}
+
+/// # サブコマンドの定義
+/// `encrypt`と`decrypt`は同じ引数を受け取ります。
+fn crypto_sub_command<'a, 'b>(name: &str, about: &'b str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(
+            Arg::with_name("input")
+                .index(1)
+                .required(true)
+                .multiple(true)
+                .value_name("FILE")
+                .help(message::text(Message::HelpInput)),
+        )
+        .arg(
+            Arg::with_name("key_file")
+                .short("k")
+                .long("key_file")
+                .takes_value(true)
+                .value_name("FILE")
+                .help(message::text(Message::HelpKeyFile)),
+        )
+        .arg(
+            Arg::with_name("output_file")
+                .short("o")
+                .long("output_file")
+                .takes_value(true)
+                .value_name("FILE")
+                .help(message::text(Message::HelpOutput)),
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .short("r")
+                .long("recursive")
+                .help(message::text(Message::HelpRecursive)),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("GLOB")
+                .help(message::text(Message::HelpInclude)),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("GLOB")
+                .help(message::text(Message::HelpExclude)),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .help(message::text(Message::HelpJobs)),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .value_name("N")
+                .help(message::text(Message::HelpThreads)),
+        )
+        .arg(
+            Arg::with_name("buffer_size")
+                .long("buffer-size")
+                .takes_value(true)
+                .value_name("SIZE")
+                .validator(|size| parse_size(&size).map(|_| ()))
+                .help(message::text(Message::HelpBufferSize)),
+        )
+        .arg(
+            Arg::with_name("pipeline_depth")
+                .long("pipeline-depth")
+                .takes_value(true)
+                .value_name("N")
+                .help(message::text(Message::HelpPipelineDepth)),
+        )
+}
+
+/// # サイズの解析
+/// `K`、`M`、`G`の単位を付けたサイズ(byte)を解析します。
+fn parse_size(size: &str) -> std::result::Result<usize, String> {
+    match parse_bytes(size) {
+        Some(number) if number > 0 => Ok(number as usize),
+        _ => Err(message::format(Message::InvalidSize, &[&size.trim()])),
+    }
+}
+
+/// # 単位付きのバイト数の解析
+/// `K`、`M`、`G`の単位を付けた0以上のバイト数を解析します。
+fn parse_bytes(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, unit) = match size.char_indices().last() {
+        Some((index, 'K')) | Some((index, 'k')) => (&size[..index], 1024),
+        Some((index, 'M')) | Some((index, 'm')) => (&size[..index], 1024 * 1024),
+        Some((index, 'G')) | Some((index, 'g')) => (&size[..index], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// # 範囲の解析
+/// `offset:len`の形式で指定された範囲を解析します。
+fn parse_range(range: &str) -> std::result::Result<DecryptRange, String> {
+    let parsed = range.split_once(':').and_then(|(offset, length)| {
+        Some(DecryptRange {
+            offset: parse_bytes(offset)?,
+            length: parse_bytes(length)?,
+        })
+    });
+    parsed.ok_or_else(|| message::format(Message::InvalidRange, &[&range]))
+}
+
+/// 複数指定できる引数の値を取得する
+fn values_of(matches: &ArgMatches, name: &str) -> Vec<String> {
+    matches.values_of_lossy(name).unwrap_or_default()
+}
+
+/// # 言語の指定を探す
+/// ヘルプを表示する前に言語を決める必要があるので、clapで解析する前に`--lang`を探します。
+fn find_lang_arg() -> Option<Lang> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--lang" {
+            return args.next().and_then(|lang| lang.parse().ok());
+        }
+        if let Some(lang) = arg.strip_prefix("--lang=") {
+            return lang.parse().ok();
+        }
+    }
+    None
+}
+
 /// # CLI引数を受け取る関数
-pub fn accept_cli_arg() -> (Option<String>, Option<String>, Mode) {
+pub fn accept_cli_arg() -> CliArg {
+    // 表示する言語を決める CLIとGUIで同じ言語を使う
+    message::set_lang(find_lang_arg().unwrap_or_else(message::detect_lang));
+
     let matches = app_from_crate!()
         .arg(
             Arg::with_name("input_file")
@@ -24,23 +224,151 @@ pub fn accept_cli_arg() -> (Option<String>, Option<String>, Mode) {
                 .short("k")
                 .long("key_file")
                 .takes_value(true)
-                .value_name("FILE"),
+                .value_name("FILE")
+                .help(message::text(Message::HelpKeyFile)),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&Backend::NAMES)
+                .default_value("auto")
+                .help(message::text(Message::HelpBackend)),
+        )
+        .arg(
+            Arg::with_name("lang")
+                .long("lang")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&Lang::NAMES)
+                .help(message::text(Message::HelpLang)),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .global(true)
+                .help(message::text(Message::HelpJson)),
+        )
+        .subcommand(
+            crypto_sub_command("encrypt", message::text(Message::AboutEncrypt))
+                .arg(
+                    Arg::with_name("cipher")
+                        .long("cipher")
+                        .takes_value(true)
+                        .possible_values(&Cipher::NAMES)
+                        .default_value("chacha20")
+                        .help(message::text(Message::HelpCipher)),
+                )
+                .arg(
+                    Arg::with_name("deterministic")
+                        .long("deterministic")
+                        .help(message::text(Message::HelpDeterministic)),
+                )
+                .arg(
+                    Arg::with_name("ledger")
+                        .long("ledger")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help(message::text(Message::HelpLedger)),
+                ),
+        )
+        .subcommand(
+            crypto_sub_command("decrypt", message::text(Message::AboutDecrypt)).arg(
+                Arg::with_name("range")
+                    .long("range")
+                    .takes_value(true)
+                    .value_name("OFFSET:LEN")
+                    .validator(|range| parse_range(&range).map(|_| ()))
+                    .help(message::text(Message::HelpRange)),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("diagnostics").about(message::text(Message::AboutDiagnostics)),
+        )
+        .subcommand(SubCommand::with_name("selftest").about(message::text(Message::AboutSelftest)))
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about(message::text(Message::AboutBench))
+                .arg(
+                    Arg::with_name("size")
+                        .long("size")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("SIZE")
+                        .validator(|size| parse_size(&size).map(|_| ()))
+                        .help(message::text(Message::HelpBenchSize)),
+                )
+                .arg(
+                    Arg::with_name("seconds")
+                        .long("seconds")
+                        .takes_value(true)
+                        .value_name("SECONDS")
+                        .help(message::text(Message::HelpBenchSeconds)),
+                ),
         )
         .get_matches();
 
     let arg_len = std::env::args().len();
     debug!("arg_len: {}", arg_len);
-    if arg_len == 1 {
-        return (None, None, Mode::Gui);
-    }
 
-    let input_file_path = matches
-        .value_of_lossy("input_file")
-        .map(|file| file.to_string());
+    let (mode, matches, input_arg_name) = match matches.subcommand() {
+        ("encrypt", Some(sub_matches)) => (Mode::Encrypt, sub_matches, "input"),
+        ("decrypt", Some(sub_matches)) => (Mode::Decrypt, sub_matches, "input"),
+        ("diagnostics", Some(sub_matches)) => (Mode::Diagnostics, sub_matches, "input"),
+        ("bench", Some(sub_matches)) => (Mode::Bench, sub_matches, "input"),
+        ("selftest", Some(sub_matches)) => (Mode::Selftest, sub_matches, "input"),
+        _ if arg_len == 1 => (Mode::Gui, &matches, "input_file"),
+        _ => (Mode::CliCrypto, &matches, "input_file"),
+    };
+
+    let input_file_paths = values_of(matches, input_arg_name);
 
     let key_file_path = matches
         .value_of_lossy("key_file")
         .map(|file| file.to_string());
 
-    (input_file_path, key_file_path, Mode::CliCrypto)
+    let output_file_path = matches
+        .value_of_lossy("output_file")
+        .map(|file| file.to_string());
+
+    CliArg {
+        input_file_paths,
+        key_file_path,
+        output_file_path,
+        recursive: matches.is_present("recursive"),
+        include_patterns: values_of(matches, "include"),
+        exclude_patterns: values_of(matches, "exclude"),
+        jobs: matches
+            .value_of("jobs")
+            .map(|_| value_t!(matches, "jobs", usize).unwrap_or_else(|e| e.exit())),
+        threads: matches
+            .value_of("threads")
+            .map(|_| value_t!(matches, "threads", usize).unwrap_or_else(|e| e.exit())),
+        buffer_size: matches
+            .value_of("buffer_size")
+            .and_then(|size| parse_size(size).ok()),
+        pipeline_depth: matches
+            .value_of("pipeline_depth")
+            .map(|_| value_t!(matches, "pipeline_depth", usize).unwrap_or_else(|e| e.exit())),
+        range: matches
+            .value_of("range")
+            .and_then(|range| parse_range(range).ok()),
+        cipher: value_t!(matches, "cipher", Cipher).unwrap_or_default(),
+        deterministic: matches.is_present("deterministic"),
+        ledger_path: matches
+            .value_of_lossy("ledger")
+            .map(|file| file.to_string()),
+        backend: value_t!(matches, "backend", Backend).unwrap_or(Backend::Auto),
+        json: matches.is_present("json"),
+        bench_sizes: values_of(matches, "size")
+            .iter()
+            .filter_map(|size| parse_size(size).ok())
+            .collect(),
+        bench_seconds: matches
+            .value_of("seconds")
+            .map(|_| value_t!(matches, "seconds", f64).unwrap_or_else(|e| e.exit())),
+        mode,
+    }
 }
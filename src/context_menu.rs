@@ -2,6 +2,8 @@
 //! windowsの右クリックのメニューにコマンドを追加、削除します。
 //! 注意 windows でしか使うことができません。
 
+use super::message::{self, Message};
+
 /// コンテクストメニューにコマンドを追加します。
 pub fn set_to_context_menu(key_file_path: &str) -> Result<(), std::io::Error> {
     // 登録済みの設定を削除する
@@ -45,7 +47,7 @@ fn set_key_to_faile_menu(post_path: &str) -> Result<(), std::io::Error> {
     if !result_set_key.status.success() {
         return Err(std::io::Error::new(
             std::io::ErrorKind::Other,
-            "レジストリの編集ができませんでした",
+            message::text(Message::RegistryEditFailed),
         ));
     };
     Ok(())
@@ -73,7 +75,7 @@ fn set_property_to_faile_menu(
     if !result_set_key.status.success() {
         return Err(std::io::Error::new(
             std::io::ErrorKind::Other,
-            "レジストリの編集ができませんでした",
+            message::text(Message::RegistryEditFailed),
         ));
     }
     Ok(())
@@ -99,7 +101,7 @@ fn remove_key_from_file_menu(post_path: &str) -> Result<(), std::io::Error> {
     if !result_remove_key.status.success() {
         return Err(std::io::Error::new(
             std::io::ErrorKind::Other,
-            "レジストリの編集ができませんでした",
+            message::text(Message::RegistryEditFailed),
         ));
     }
     Ok(())
@@ -0,0 +1,382 @@
+//! # 非同期の暗号化・復号
+//! tokioの`AsyncRead`、`AsyncWrite`で暗号化・復号するアダプタのモジュール (`async`feature)
+//! 同期版の`Encryptor`、`Decryptor`と同じ形式で読み書きするので、どちらで暗号化したファイルも復号できます。
+//! 暗号化・復号は受け取ったデータをチャンクの分だけ処理するので、ランタイムのスレッドを長く止めません。
+//!
+//! ```no_run
+//! use crypto_tool::crypto::{AsyncEncryptReader, Key};
+//!
+//! # async fn upload() -> std::io::Result<()> {
+//! let key = Key::read_from_file("key.bin").map_err(std::io::Error::from)?;
+//! let input = tokio::fs::File::open("data.csv").await?;
+//! let mut output = tokio::fs::File::create("data.csv.c20").await?;
+//! let mut reader = AsyncEncryptReader::new(&key, input);
+//! tokio::io::copy(&mut reader, &mut output).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::chunk::BodyCipher;
+use super::{Error, Header, Key};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// 1回の`poll_write`で暗号化するサイズ(byte)の上限、1回に読み込むサイズ(byte)
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// # 暗号化するリーダー
+/// 平文のリーダーから読み込み、ヘッダーと暗号文を返します。
+pub struct AsyncEncryptReader<R> {
+    cipher: BodyCipher,
+    output: PendingRead,
+    reader: R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncEncryptReader<R> {
+    /// # 新しいナンスで作成
+    pub fn new(key: &Key, reader: R) -> Self {
+        Self::with_header(key, Header::generate(), reader)
+    }
+
+    /// # ヘッダーを指定して作成
+    /// ヘッダーには`key`で認証コードを付けて返します。
+    pub fn with_header(key: &Key, header: Header, reader: R) -> Self {
+        let header = header.authenticate(key);
+        let mut output = PendingRead::new();
+        // 先にヘッダーを返す
+        output.pending.extend_from_slice(&header.to_bytes());
+        AsyncEncryptReader {
+            cipher: BodyCipher::encryptor(key, &header),
+            output,
+            reader,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncEncryptReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.output
+            .poll_read(cx, &mut this.reader, &mut this.cipher, buf)
+    }
+}
+
+/// # 復号するリーダー
+/// 暗号文のリーダーから先頭のヘッダーを読み込んでから、復号した平文を返します。
+pub struct AsyncDecryptReader<R> {
+    state: DecryptState,
+    output: PendingRead,
+    reader: R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecryptReader<R> {
+    pub fn new(key: &Key, reader: R) -> Self {
+        AsyncDecryptReader {
+            state: DecryptState::new(key),
+            output: PendingRead::new(),
+            reader,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecryptReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        // ヘッダーを読み終わるまで読み込む
+        while let DecryptState::Header { bytes, .. } = &mut this.state {
+            let mut header = [0; Header::MAX_SIZE];
+            let size = Header::required_size(bytes)? - bytes.len();
+            let mut header_buf = ReadBuf::new(&mut header[..size]);
+            ready!(Pin::new(&mut this.reader).poll_read(cx, &mut header_buf))?;
+            if header_buf.filled().is_empty() {
+                return Poll::Ready(Err(Error::TruncatedHeader.into()));
+            }
+            bytes.extend_from_slice(header_buf.filled());
+            this.state.start_if_complete()?;
+        }
+
+        let cipher = this.state.cipher().expect("ヘッダーを読み終わっている");
+        this.output.poll_read(cx, &mut this.reader, cipher, buf)
+    }
+}
+
+/// # 暗号化するライター
+/// 書き込まれた平文を暗号化して内側のライターに書き込みます。
+/// ヘッダーは最初の書き込みの前に書き込みます。最後に`shutdown`を呼んでください。
+/// 認証付き暗号の最後のチャンクは`shutdown`で書き込みます。
+pub struct AsyncEncryptWriter<W> {
+    cipher: BodyCipher,
+    finished: bool,
+    output: PendingOutput<W>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncryptWriter<W> {
+    /// # 新しいナンスで作成
+    pub fn new(key: &Key, writer: W) -> Self {
+        Self::with_header(key, Header::generate(), writer)
+    }
+
+    /// # ヘッダーを指定して作成
+    /// ヘッダーには`key`で認証コードを付けて書き込みます。
+    pub fn with_header(key: &Key, header: Header, writer: W) -> Self {
+        let header = header.authenticate(key);
+        let mut output = PendingOutput::new(writer);
+        output.pending.extend_from_slice(&header.to_bytes());
+        AsyncEncryptWriter {
+            cipher: BodyCipher::encryptor(key, &header),
+            finished: false,
+            output,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output.writer
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.output.poll_drain(cx))?;
+
+        let size = buf.len().min(WRITE_BUFFER_SIZE);
+        this.cipher.update(&buf[..size], &mut this.output.pending)?;
+        Poll::Ready(Ok(size))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().output.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            this.cipher.finish(&mut this.output.pending)?;
+            this.finished = true;
+        }
+        this.output.poll_shutdown(cx)
+    }
+}
+
+/// # 復号するライター
+/// 書き込まれた暗号文の先頭のヘッダーを読み取り、続きを復号して内側のライターに書き込みます。
+/// 最後に`shutdown`を呼んでください。ヘッダーの途中で終わった場合はエラーを返します。
+pub struct AsyncDecryptWriter<W> {
+    state: DecryptState,
+    finished: bool,
+    output: PendingOutput<W>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncDecryptWriter<W> {
+    pub fn new(key: &Key, writer: W) -> Self {
+        AsyncDecryptWriter {
+            state: DecryptState::new(key),
+            finished: false,
+            output: PendingOutput::new(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output.writer
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncDecryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        // ヘッダーの分はライターに書き込まずに受け取る
+        if let DecryptState::Header { bytes, .. } = &mut this.state {
+            let size = (Header::required_size(bytes)? - bytes.len()).min(buf.len());
+            bytes.extend_from_slice(&buf[..size]);
+            this.state.start_if_complete()?;
+            return Poll::Ready(Ok(size));
+        }
+
+        ready!(this.output.poll_drain(cx))?;
+        let size = buf.len().min(WRITE_BUFFER_SIZE);
+        let cipher = this.state.cipher().expect("ヘッダーを読み終わっている");
+        cipher.update(&buf[..size], &mut this.output.pending)?;
+        Poll::Ready(Ok(size))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().output.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let cipher = match this.state.cipher() {
+            Some(cipher) => cipher,
+            None => return Poll::Ready(Err(Error::TruncatedHeader.into())),
+        };
+        if !this.finished {
+            cipher.finish(&mut this.output.pending)?;
+            this.finished = true;
+        }
+        this.output.poll_shutdown(cx)
+    }
+}
+
+/// # 復号の状態
+/// ヘッダーを読み終わるまでは鍵を持っておき、読み終わったら復号を始めます。
+enum DecryptState {
+    Header { key: Key, bytes: Vec<u8> },
+    Body(Box<BodyCipher>),
+}
+
+impl DecryptState {
+    fn new(key: &Key) -> Self {
+        DecryptState::Header {
+            key: key.clone(),
+            bytes: Vec::with_capacity(Header::MAX_SIZE),
+        }
+    }
+
+    /// ヘッダーを読み終わっていれば、認証コードを確かめてから復号を始める
+    fn start_if_complete(&mut self) -> Result<(), Error> {
+        if let DecryptState::Header { key, bytes } = self {
+            if bytes.len() >= Header::required_size(bytes)? {
+                let header = Header::parse(bytes)?;
+                header.verify(key)?;
+                *self = DecryptState::Body(Box::new(BodyCipher::decryptor(key, &header)));
+            }
+        }
+        Ok(())
+    }
+
+    /// ヘッダーを読み終わっていれば復号に使う`BodyCipher`を返す
+    fn cipher(&mut self) -> Option<&mut BodyCipher> {
+        match self {
+            DecryptState::Header { .. } => None,
+            DecryptState::Body(cipher) => Some(cipher),
+        }
+    }
+}
+
+/// # 読み出し待ちのデータ
+/// リーダーから読み込んで暗号化・復号したデータを、読み出されるまで持っておきます。
+struct PendingRead {
+    pending: Vec<u8>,
+    position: usize,
+    /// リーダーから読み込むバッファ
+    buffer: Vec<u8>,
+    /// リーダーを最後まで読み込んだか
+    finished: bool,
+}
+
+impl PendingRead {
+    fn new() -> Self {
+        PendingRead {
+            pending: Vec::new(),
+            position: 0,
+            buffer: vec![0; WRITE_BUFFER_SIZE],
+            finished: false,
+        }
+    }
+
+    /// # 読み出し
+    /// 読み出し待ちのデータがなければリーダーから読み込んで`cipher`で暗号化・復号します。
+    fn poll_read<R: AsyncRead + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        reader: &mut R,
+        cipher: &mut BodyCipher,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            if self.position < self.pending.len() {
+                let pending = &self.pending[self.position..];
+                let size = pending.len().min(buf.remaining());
+                buf.put_slice(&pending[..size]);
+                self.position += size;
+                return Poll::Ready(Ok(()));
+            }
+            self.pending.clear();
+            self.position = 0;
+            if self.finished {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut read_buf = ReadBuf::new(&mut self.buffer);
+            ready!(Pin::new(&mut *reader).poll_read(cx, &mut read_buf))?;
+            let size = read_buf.filled().len();
+            if size == 0 {
+                cipher.finish(&mut self.pending)?;
+                self.finished = true;
+            } else {
+                cipher.update(&self.buffer[..size], &mut self.pending)?;
+            }
+        }
+    }
+}
+
+/// # 書き込み待ちのデータ
+/// 暗号化・復号したデータを内側のライターに書き込み終わるまで持っておきます。
+struct PendingOutput<W> {
+    writer: W,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<W: AsyncWrite + Unpin> PendingOutput<W> {
+    fn new(writer: W) -> Self {
+        PendingOutput {
+            writer,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// 書き込み待ちのデータをすべて書き込む
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.position < self.pending.len() {
+            let size =
+                ready!(Pin::new(&mut self.writer).poll_write(cx, &self.pending[self.position..]))?;
+            if size == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.position += size;
+        }
+        self.pending.clear();
+        self.position = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,273 @@
+//! # ChaCha20のバックエンド選択
+//! 実行時にCPUの機能を調べて、ChaCha20の実装(AVX2、SSE2、ポータブル)を選ぶモジュール
+//! ビルド時に`-Ctarget-feature=+avx2`を指定しなくても、AVX2が使えるCPUではAVX2の実装が使われます。
+//! `chacha20`クレートはAVX2、SSE2の順に使える実装を自動で選び、外から実装を指定できないので、
+//! 指定できるのは自動選択(`auto`)とポータブル実装(`soft`)だけです。
+
+use super::soft::SoftChaCha20;
+use super::{Error, Result};
+use crate::message::{self, Message};
+use chacha20::cipher::{NewCipher, StreamCipher, StreamCipherSeek};
+use chacha20::{ChaCha20, Key, Nonce};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// # ChaCha20の実装
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// CPUの機能から自動で選ぶ
+    Auto,
+    Avx2,
+    Sse2,
+    /// CPUの拡張命令を使わない実装
+    Soft,
+}
+
+impl Backend {
+    /// 指定できるバックエンドの名前
+    /// `avx2`、`sse2`は自動で選ばれた実装を表示するためのもので、指定はできません。
+    pub const NAMES: [&'static str; 2] = ["auto", "soft"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Auto => "auto",
+            Backend::Avx2 => "avx2",
+            Backend::Sse2 => "sse2",
+            Backend::Soft => "soft",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Backend::Avx2,
+            2 => Backend::Sse2,
+            3 => Backend::Soft,
+            _ => Backend::Auto,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Backend::Auto => 0,
+            Backend::Avx2 => 1,
+            Backend::Sse2 => 2,
+            Backend::Soft => 3,
+        }
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "auto" => Ok(Backend::Auto),
+            "soft" => Ok(Backend::Soft),
+            _ => Err(message::format(Message::BackendUnknown, &[&name])),
+        }
+    }
+}
+
+/// 選択されたバックエンド
+static SELECTED_BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// # CPUの機能
+/// 実行しているCPUで使える機能を返します。
+pub fn cpu_features() -> Vec<(&'static str, bool)> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        vec![
+            ("sse2", is_x86_feature_detected!("sse2")),
+            ("avx2", is_x86_feature_detected!("avx2")),
+            ("aes", is_x86_feature_detected!("aes")),
+            ("pclmulqdq", is_x86_feature_detected!("pclmulqdq")),
+        ]
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        Vec::new()
+    }
+}
+
+/// # AES-256-GCMの実装
+/// `aes-gcm`クレートが実行時に選ぶ実装を返します。
+/// AES-NIとPCLMULQDQが使える場合は`aesni`、使えない場合はポータブル実装の`soft`です。
+pub fn aes_gcm_backend() -> &'static str {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("aes") && is_x86_feature_detected!("pclmulqdq") {
+            return "aesni";
+        }
+    }
+    "soft"
+}
+
+/// # 自動で選ばれるバックエンド
+/// `chacha20`クレートが実行時に選ぶ実装を返します。
+pub fn detect_backend() -> Backend {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return Backend::Avx2;
+        }
+        if is_x86_feature_detected!("sse2") {
+            return Backend::Sse2;
+        }
+    }
+    Backend::Soft
+}
+
+/// # バックエンドの選択
+/// 指定できるのは`Auto`と`Soft`だけです。`Avx2`、`Sse2`は`chacha20`クレートの自動選択に任せるので、
+/// 指定した場合はErrを返します。
+pub fn set_backend(backend: Backend) -> Result<()> {
+    match backend {
+        Backend::Auto | Backend::Soft => {}
+        requested => {
+            return Err(Error::UnsupportedBackend {
+                requested,
+                detected: detect_backend(),
+            });
+        }
+    }
+    SELECTED_BACKEND.store(backend.to_u8(), Ordering::SeqCst);
+    Ok(())
+}
+
+/// # 選択されているバックエンド
+/// `set_backend`で指定されたバックエンドを返します。
+pub fn selected_backend() -> Backend {
+    Backend::from_u8(SELECTED_BACKEND.load(Ordering::SeqCst))
+}
+
+/// # 使用中のバックエンド
+/// 自動選択の場合は実際に使われる実装を返します。
+pub fn active_backend() -> Backend {
+    match selected_backend() {
+        Backend::Auto => detect_backend(),
+        backend => backend,
+    }
+}
+
+/// # 1つのナンスで暗号化するサイズ(byte)
+/// IETF版のChaCha20はブロックカウンタが32bitなので、1つのナンスで256GiBまでしか暗号化できません。
+/// このサイズごとの区間(セグメント)で、ナンスの先頭8byteにセグメントの番号(リトルエンディアン)をXORします。
+/// 最初のセグメントはナンスをそのまま使うので、256GiBより小さいファイルの暗号文は変わりません。
+pub const KEYSTREAM_SEGMENT_SIZE: u64 = 64 << 32;
+
+/// # ChaCha20
+/// 選択されているバックエンドでChaCha20の鍵ストリームを生成します。
+/// 鍵ストリームが`KEYSTREAM_SEGMENT_SIZE`を超える場合は、セグメントごとのナンスに切り替えます。
+pub struct ChaCha20Cipher {
+    backend: Backend,
+    key: [u8; 32],
+    nonce: [u8; 12],
+    /// 鍵ストリームの先頭からの位置(byte)
+    position: u64,
+    /// `segment_cipher`のセグメントの番号
+    segment: u64,
+    segment_cipher: SegmentCipher,
+}
+
+/// # セグメントの鍵ストリーム
+enum SegmentCipher {
+    /// `chacha20`クレートの実装 (AVX2、SSE2を自動で選択)
+    Detected(Box<ChaCha20>),
+    /// ポータブル実装
+    Soft(Box<SoftChaCha20>),
+}
+
+impl SegmentCipher {
+    fn new(backend: Backend, key: &[u8; 32], nonce: &[u8; 12]) -> Self {
+        match backend {
+            Backend::Soft => SegmentCipher::Soft(Box::new(SoftChaCha20::new(key, nonce))),
+            _ => SegmentCipher::Detected(Box::new(ChaCha20::new(
+                Key::from_slice(key),
+                Nonce::from_slice(nonce),
+            ))),
+        }
+    }
+
+    fn apply_keystream(&mut self, data: &mut [u8]) {
+        match self {
+            SegmentCipher::Detected(cipher) => cipher.apply_keystream(data),
+            SegmentCipher::Soft(cipher) => cipher.apply_keystream(data),
+        }
+    }
+
+    fn seek(&mut self, position: u64) {
+        match self {
+            SegmentCipher::Detected(cipher) => cipher.seek(position),
+            SegmentCipher::Soft(cipher) => cipher.seek(position),
+        }
+    }
+}
+
+impl ChaCha20Cipher {
+    pub fn new(key: &[u8; 32], nonce: &[u8; 12]) -> Self {
+        Self::with_backend(selected_backend(), key, nonce)
+    }
+
+    /// # バックエンドを指定して作成
+    /// `Soft`以外のバックエンドは`chacha20`クレートが自動で選ぶ実装を使います。
+    pub fn with_backend(backend: Backend, key: &[u8; 32], nonce: &[u8; 12]) -> Self {
+        ChaCha20Cipher {
+            backend,
+            key: *key,
+            nonce: *nonce,
+            position: 0,
+            segment: 0,
+            segment_cipher: SegmentCipher::new(backend, key, nonce),
+        }
+    }
+
+    /// # 鍵ストリームの適用
+    /// セグメントの境界をまたぐ場合は、境界で次のセグメントのナンスに切り替えます。
+    pub fn apply_keystream(&mut self, mut data: &mut [u8]) {
+        while !data.is_empty() {
+            self.enter_segment();
+            let remaining = KEYSTREAM_SEGMENT_SIZE - self.position % KEYSTREAM_SEGMENT_SIZE;
+            let size = remaining.min(data.len() as u64) as usize;
+            let (head, tail) = data.split_at_mut(size);
+            self.segment_cipher.apply_keystream(head);
+            self.position += size as u64;
+            data = tail;
+        }
+    }
+
+    /// # 鍵ストリームのシーク
+    /// 鍵ストリームの位置を先頭から`position`byteの位置に移動します。
+    pub fn seek(&mut self, position: u64) {
+        self.position = position;
+        if !self.enter_segment() {
+            self.segment_cipher.seek(position % KEYSTREAM_SEGMENT_SIZE);
+        }
+    }
+
+    /// # セグメントの切り替え
+    /// 現在の位置が別のセグメントにある場合は、そのセグメントのナンスで作り直して位置を合わせます。
+    /// 作り直した場合は`true`を返します。
+    fn enter_segment(&mut self) -> bool {
+        let segment = self.position / KEYSTREAM_SEGMENT_SIZE;
+        if segment == self.segment {
+            return false;
+        }
+        let mut nonce = self.nonce;
+        for (byte, segment_byte) in nonce.iter_mut().zip(segment.to_le_bytes()) {
+            *byte ^= segment_byte;
+        }
+        self.segment = segment;
+        self.segment_cipher = SegmentCipher::new(self.backend, &self.key, &nonce);
+        self.segment_cipher
+            .seek(self.position % KEYSTREAM_SEGMENT_SIZE);
+        true
+    }
+}
+
+impl Drop for ChaCha20Cipher {
+    fn drop(&mut self) {
+        for byte in self.key.iter_mut() {
+            // 最適化で書き込みが消されないようにする
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! # チャンクの暗号化・復号
+//! ヘッダーの後ろのデータを少しずつ受け取って暗号化・復号する`BodyCipher`のモジュール
+//! 読み書きはしないので、同期・非同期のどちらのリーダー・ライターからも使えます。
+//! 認証付き暗号では最後のチャンクは必ずチャンクのサイズより短く(空の場合もある)、
+//! チャンクのサイズちょうどのチャンクは最後ではないと判定できるので、先読みせずに処理できます。
+
+use super::{ChaCha20Cipher, ChunkCipher, Error, Header, Key, Progress, Result};
+use std::io::{self, Read, Write};
+
+/// # 暗号文の本体の暗号化・復号
+pub(crate) enum BodyCipher {
+    /// 鍵ストリームを適用するだけの暗号 (ChaCha20)
+    Stream {
+        cipher: Box<ChaCha20Cipher>,
+        bytes: u64,
+    },
+    /// チャンクごとに認証タグを付ける・確かめる暗号
+    Chunks(Chunks),
+}
+
+/// # チャンクの処理の向き
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    /// 暗号化して認証タグを付ける
+    Seal,
+    /// 認証タグを確かめて復号する
+    Open,
+}
+
+impl Direction {
+    /// # チャンクの暗号化・復号
+    pub fn apply(
+        self,
+        cipher: &dyn ChunkCipher,
+        index: u64,
+        last: bool,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        match self {
+            Direction::Seal => cipher.seal(index, last, buffer),
+            Direction::Open => cipher.open(index, last, buffer),
+        }
+    }
+
+    /// # 受け取るチャンクと書き出すチャンクのサイズ(byte)
+    /// 最後のチャンク以外のサイズで、暗号化では書き出すチャンクが、復号では受け取るチャンクが認証タグの分だけ大きくなります。
+    pub fn chunk_sizes(self, header: &Header) -> (u64, u64) {
+        let chunk_size = header.chunk_size() as u64;
+        let full_size = chunk_size + header.cipher().suite().tag_size() as u64;
+        match self {
+            Direction::Seal => (chunk_size, full_size),
+            Direction::Open => (full_size, chunk_size),
+        }
+    }
+}
+
+/// # 処理中のチャンク
+pub(crate) struct Chunks {
+    cipher: Box<dyn ChunkCipher>,
+    direction: Direction,
+    /// 受け取ったデータのチャンクのサイズ(byte)
+    input_size: usize,
+    index: u64,
+    pending: Vec<u8>,
+    /// 処理した平文のサイズ(byte)
+    bytes: u64,
+}
+
+impl BodyCipher {
+    /// # 暗号化の準備
+    pub fn encryptor(key: &Key, header: &Header) -> Self {
+        Self::new(key, header, Direction::Seal)
+    }
+
+    /// # 復号の準備
+    pub fn decryptor(key: &Key, header: &Header) -> Self {
+        Self::new(key, header, Direction::Open)
+    }
+
+    /// `key`はマスター鍵で、ヘッダーのソルトからファイルの鍵を求めます。
+    /// 復号ではヘッダーの認証コードを先に確かめてください。(`Header::verify`)
+    fn new(key: &Key, header: &Header, direction: Direction) -> Self {
+        let key = header.file_key(key);
+        match header.stream_nonce() {
+            Some(nonce) => BodyCipher::Stream {
+                cipher: Box::new(ChaCha20Cipher::new(key.as_bytes(), &nonce)),
+                bytes: 0,
+            },
+            None => BodyCipher::Chunks(Chunks::new(&key, header, direction)),
+        }
+    }
+
+    /// # データの追加
+    /// 受け取ったデータを暗号化・復号できた分だけ`output`に追加します。
+    pub fn update(&mut self, data: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        match self {
+            BodyCipher::Stream { cipher, bytes } => {
+                let start = output.len();
+                output.extend_from_slice(data);
+                cipher.apply_keystream(&mut output[start..]);
+                *bytes += data.len() as u64;
+                Ok(())
+            }
+            BodyCipher::Chunks(chunks) => chunks.update(data, output),
+        }
+    }
+
+    /// # 終了
+    /// 残っているデータを最後のチャンクとして`output`に追加します。1回だけ呼んでください。
+    pub fn finish(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        match self {
+            BodyCipher::Stream { .. } => Ok(()),
+            BodyCipher::Chunks(chunks) => chunks.process(true, output),
+        }
+    }
+
+    /// # 処理した平文のサイズ(byte)
+    pub fn bytes(&self) -> u64 {
+        match self {
+            BodyCipher::Stream { bytes, .. } => *bytes,
+            BodyCipher::Chunks(chunks) => chunks.bytes,
+        }
+    }
+}
+
+impl Chunks {
+    fn new(key: &Key, header: &Header, direction: Direction) -> Self {
+        let suite = header.cipher().suite();
+        let cipher = suite.chunk_cipher(
+            key,
+            header.nonce(),
+            header.chunk_size(),
+            &header.associated_data(),
+        );
+        let (input_size, _) = direction.chunk_sizes(header);
+        Chunks {
+            cipher,
+            direction,
+            input_size: input_size as usize,
+            index: 0,
+            pending: Vec::with_capacity(header.chunk_size() as usize + suite.tag_size()),
+            bytes: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        while !data.is_empty() {
+            let size = (self.input_size - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..size]);
+            data = &data[size..];
+            // ちょうどチャンクのサイズのチャンクは最後ではない
+            if self.pending.len() == self.input_size {
+                self.process(false, output)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, last: bool, output: &mut Vec<u8>) -> Result<()> {
+        let input_size = self.pending.len();
+        self.direction
+            .apply(self.cipher.as_ref(), self.index, last, &mut self.pending)?;
+        self.index += 1;
+        // 暗号化では認証タグが増え、復号では減るので、小さい方が平文のサイズ
+        self.bytes += input_size.min(self.pending.len()) as u64;
+        output.extend_from_slice(&self.pending);
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// # ストリームの暗号化・復号
+/// リーダーのデータを`cipher`で暗号化・復号してライターに書き込み、処理した平文のサイズ(byte)を返します。
+/// 進捗には処理した平文のサイズを通知します。
+pub(crate) fn crypto_body(
+    mut cipher: BodyCipher,
+    mut reader: impl Read,
+    mut writer: impl Write,
+    buffer_size: usize,
+    progress: Progress,
+) -> Result<u64> {
+    let mut buffer = vec![0; buffer_size.max(1)];
+    let mut output = Vec::with_capacity(buffer.len());
+    loop {
+        let size = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::Io(e)),
+        };
+        let bytes = cipher.bytes();
+        output.clear();
+        cipher.update(&buffer[..size], &mut output)?;
+        writer.write_all(&output)?;
+        progress(cipher.bytes() - bytes);
+    }
+    let bytes = cipher.bytes();
+    output.clear();
+    cipher.finish(&mut output)?;
+    writer.write_all(&output)?;
+    writer.flush()?;
+    progress(cipher.bytes() - bytes);
+    Ok(cipher.bytes())
+}
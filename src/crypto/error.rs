@@ -0,0 +1,150 @@
+//! # 暗号化・復号のエラー
+//! ライブラリの関数が返すエラーをまとめたモジュール
+//! `std::io::Error`に変換できるので、`io::Result`を返す関数の中でも`?`で使えます。
+
+use super::{Backend, Cipher};
+use crate::message::{self, Message};
+use std::fmt;
+use std::io;
+
+/// # 暗号化・復号のエラー
+#[derive(Debug)]
+pub enum Error {
+    /// 読み込み・書き込みのエラー
+    Io(io::Error),
+    /// 鍵の長さが32byteではない
+    InvalidKeyLength(usize),
+    /// ヘッダーの途中でデータが終わっている
+    TruncatedHeader,
+    /// 対応していない形式のバージョン
+    UnsupportedVersion(u8),
+    /// ヘッダーの暗号のIDが分からない
+    UnsupportedCipher(u8),
+    /// ヘッダーのフラグ(予約)が0ではない
+    UnsupportedFlags(u16),
+    /// ヘッダーのチャンクのサイズが誤っている
+    InvalidChunkSize(u32),
+    /// 認証タグが一致しない (鍵が誤っているか、データが改ざん・破損している)
+    AuthenticationFailed,
+    /// チャンクを暗号化できなかった
+    EncryptionFailed,
+    /// ナンスが重複すると安全ではない暗号で決定的な暗号化が指定された
+    DeterministicUnsupported(Cipher),
+    /// 指定されたヘッダーのナンスが台帳に記録済み
+    NonceReused,
+    /// 台帳の行を読み込めない (行番号)
+    InvalidLedger(usize),
+    /// 指定できないバックエンドが指定された
+    UnsupportedBackend {
+        requested: Backend,
+        detected: Backend,
+    },
+    /// 暗号化・復号のスレッドが異常終了した
+    ThreadPanicked,
+}
+
+/// ライブラリの関数の戻り値
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::InvalidKeyLength(length) => {
+                write!(
+                    f,
+                    "{}",
+                    message::format(Message::KeyLengthInvalid, &[length])
+                )
+            }
+            Error::TruncatedHeader => write!(f, "{}", message::text(Message::HeaderTruncated)),
+            Error::UnsupportedVersion(version) => write!(
+                f,
+                "{}",
+                message::format(Message::FormatVersionUnsupported, &[version])
+            ),
+            Error::UnsupportedCipher(id) => {
+                write!(f, "{}", message::format(Message::CipherUnsupported, &[id]))
+            }
+            Error::UnsupportedFlags(flags) => write!(
+                f,
+                "{}",
+                message::format(Message::FlagsUnsupported, &[&format!("{:#06x}", flags)])
+            ),
+            Error::InvalidChunkSize(size) => {
+                write!(f, "{}", message::format(Message::ChunkSizeInvalid, &[size]))
+            }
+            Error::AuthenticationFailed => {
+                write!(f, "{}", message::text(Message::AuthenticationFailed))
+            }
+            Error::EncryptionFailed => write!(f, "{}", message::text(Message::EncryptionFailed)),
+            Error::DeterministicUnsupported(cipher) => write!(
+                f,
+                "{}",
+                message::format(Message::DeterministicUnsupported, &[&cipher.name()])
+            ),
+            Error::NonceReused => write!(f, "{}", message::text(Message::NonceReused)),
+            Error::InvalidLedger(line) => {
+                write!(f, "{}", message::format(Message::LedgerInvalid, &[line]))
+            }
+            Error::UnsupportedBackend {
+                requested,
+                detected,
+            } => write!(
+                f,
+                "{}",
+                message::format(
+                    Message::BackendUnsupported,
+                    &[&requested.name(), &detected.name()],
+                )
+            ),
+            Error::ThreadPanicked => write!(f, "{}", message::text(Message::ThreadFailed)),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        let kind = match &e {
+            Error::Io(_) => return e.into_io_error(),
+            Error::InvalidKeyLength(_) => io::ErrorKind::InvalidData,
+            Error::TruncatedHeader => io::ErrorKind::UnexpectedEof,
+            Error::UnsupportedVersion(_)
+            | Error::UnsupportedCipher(_)
+            | Error::UnsupportedFlags(_) => io::ErrorKind::Unsupported,
+            Error::InvalidChunkSize(_) | Error::AuthenticationFailed => io::ErrorKind::InvalidData,
+            Error::EncryptionFailed | Error::DeterministicUnsupported(_) | Error::NonceReused => {
+                io::ErrorKind::InvalidInput
+            }
+            Error::InvalidLedger(_) => io::ErrorKind::InvalidData,
+            Error::UnsupportedBackend { .. } => io::ErrorKind::Unsupported,
+            Error::ThreadPanicked => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+impl Error {
+    /// `Io`の場合は中の`io::Error`を取り出す
+    fn into_io_error(self) -> io::Error {
+        match self {
+            Error::Io(e) => e,
+            e => io::Error::other(e),
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! # ヘッダー
+//! 暗号化したファイルの先頭に書き込むヘッダーを扱うモジュール
+//! ヘッダーには形式のバージョン、暗号、チャンクのサイズ、ソルト、ナンスを記録し、その後ろに暗号文が続きます。
+//!
+//! | 位置 | サイズ(byte) | 内容 |
+//! | --- | --- | --- |
+//! | 0 | 8 | マジックナンバー `CRYPTOOL` |
+//! | 8 | 1 | 形式のバージョン (3) |
+//! | 9 | 1 | 暗号のID (`Cipher::id`) |
+//! | 10 | 2 | フラグ (予約、0以外は読み込めない) |
+//! | 12 | 4 | チャンクのサイズ(byte、ビッグエンディアン) |
+//! | 16 | 32 | ソルト |
+//! | 48 | 暗号による | ナンス |
+//! | 48 + ナンスのサイズ | 32 | ヘッダーの認証コード (HMAC-SHA256) |
+//!
+//! 鍵ファイルの鍵(マスター鍵)はそのまま使わず、ソルトからHKDF-SHA256でファイルごとの暗号化の鍵と
+//! ヘッダーの認証コードの鍵を求めます。ファイルごとに鍵が異なるので、別のファイルとナンスが重複しても安全です。
+//! 復号では先にヘッダーの認証コードを確かめるので、鍵の誤りやヘッダーの改ざんは本体を読む前に分かります。
+//!
+//! バージョン1(0.1.6より前)のヘッダーは12byteのナンスだけで、暗号はChaCha20です。
+//! マジックナンバーで始まらないヘッダーはバージョン1として読み込みます。
+//! ChaCha20の暗号文は256GiBごとにナンスを切り替えます。(`KEYSTREAM_SEGMENT_SIZE`)
+//!
+//! 決定的な暗号化では、ソルトとナンスを乱数ではなく鍵と平文のハッシュ値から求めるので、
+//! 同じ鍵で同じ平文を暗号化すると同じ暗号文になります。
+
+use super::{Cipher, Clock, Error, Key, OsRandom, RandomSource, Result, SystemClock, KEY_SIZE};
+use hmac::{Mac, NewMac};
+use log::debug;
+use sha3::Digest;
+use std::io::{self, Read, Write};
+
+/// ChaCha20のナンスのサイズ(byte)
+pub const NONCE_SIZE: usize = 12;
+/// 暗号のナンスのサイズの最大値(byte)
+pub const MAX_NONCE_SIZE: usize = 24;
+/// ソルトのサイズ(byte)
+pub const SALT_SIZE: usize = 32;
+/// ヘッダーの認証コードのサイズ(byte)
+pub const HEADER_MAC_SIZE: usize = 32;
+/// 省略時のチャンクのサイズ(byte)
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+/// チャンクのサイズの最大値(byte)
+pub const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// ヘッダーの先頭のマジックナンバー
+const MAGIC: [u8; 8] = *b"CRYPTOOL";
+/// ソルトより前の部分のサイズ(byte)
+const FIXED_SIZE: usize = 16;
+/// 決定的な暗号化のソルトとナンスを求めるハッシュ値の先頭に付けるラベル
+const DETERMINISTIC_LABEL: &[u8] = b"crypto_tool deterministic nonce";
+/// ファイルの暗号化の鍵を求めるHKDFのinfo
+const ENCRYPTION_KEY_INFO: &[u8] = b"crypto_tool file encryption key";
+/// ヘッダーの認証コードの鍵を求めるHKDFのinfo
+const HEADER_MAC_KEY_INFO: &[u8] = b"crypto_tool header mac key";
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+/// # ヘッダー
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    version: u8,
+    cipher: Cipher,
+    chunk_size: u32,
+    salt: [u8; SALT_SIZE],
+    nonce: [u8; MAX_NONCE_SIZE],
+    mac: [u8; HEADER_MAC_SIZE],
+}
+
+impl Header {
+    /// 現在の形式のバージョン
+    pub const VERSION: u8 = 3;
+    /// ナンスだけのヘッダーの形式のバージョン
+    pub const LEGACY_VERSION: u8 = 1;
+    /// ヘッダーのサイズの最小値(byte) バージョン1のヘッダーのサイズ
+    pub const MIN_SIZE: usize = NONCE_SIZE;
+    /// ヘッダーのサイズの最大値(byte)
+    pub const MAX_SIZE: usize = FIXED_SIZE + SALT_SIZE + MAX_NONCE_SIZE + HEADER_MAC_SIZE;
+
+    /// # ナンスを指定して作成
+    /// ChaCha20のヘッダーを作成します。ソルトはすべて0です。
+    /// 同じ鍵で同じナンスを使うと暗号文から平文が推測できるので、通常は`generate`を使います。
+    pub fn new(nonce: [u8; NONCE_SIZE]) -> Self {
+        let mut header = Self::empty(Self::VERSION, Cipher::ChaCha20);
+        header.nonce[..NONCE_SIZE].copy_from_slice(&nonce);
+        header
+    }
+
+    /// # バージョン1のヘッダーを作成
+    /// 古いバージョンで読み込めるファイルを作る場合に使います。
+    pub fn legacy(nonce: [u8; NONCE_SIZE]) -> Self {
+        Header {
+            version: Self::LEGACY_VERSION,
+            ..Self::new(nonce)
+        }
+    }
+
+    /// # 新しいソルトとナンスでヘッダーを作成
+    /// 省略時の暗号(ChaCha20)のヘッダーを作成します。
+    pub fn generate() -> Self {
+        Self::for_cipher(Cipher::default())
+    }
+
+    /// # 暗号を指定して新しいソルトとナンスでヘッダーを作成
+    /// OSの乱数と現在の日時を使います。
+    pub fn for_cipher(cipher: Cipher) -> Self {
+        Self::for_cipher_with(cipher, &OsRandom, &SystemClock)
+    }
+
+    /// # 乱数と時刻を指定してヘッダーを作成
+    /// ソルトとナンスを`random`と`clock`から作ります。
+    pub fn for_cipher_with(cipher: Cipher, random: &dyn RandomSource, clock: &dyn Clock) -> Self {
+        let mut header = Self::empty(Self::VERSION, cipher);
+        random.fill_bytes(&mut header.salt);
+        fill_nonce(
+            &mut header.nonce[..cipher.suite().nonce_size()],
+            random,
+            clock,
+        );
+        header
+    }
+
+    /// # 平文から決定的にヘッダーを作成
+    /// 鍵、暗号、チャンクのサイズ、平文(`reader`の終わりまで)のsha3_512のハッシュ値をソルトとナンスにします。
+    /// 同じ鍵と平文からは同じヘッダーになるので、同じ平文は同じ暗号文になります。
+    /// 同じ平文かどうかが分かってしまうので、ナンスが重複しても安全な暗号でだけ使えます。
+    pub fn deterministic(cipher: Cipher, key: &Key, mut reader: impl Read) -> Result<Self> {
+        if !cipher.is_misuse_resistant() {
+            return Err(Error::DeterministicUnsupported(cipher));
+        }
+        let mut header = Self::empty(Self::VERSION, cipher);
+
+        let mut hasher = sha3::Sha3_512::new();
+        hasher.update(DETERMINISTIC_LABEL);
+        hasher.update(key.as_bytes());
+        hasher.update([cipher.id()]);
+        hasher.update(header.chunk_size.to_be_bytes());
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let count = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => count,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            };
+            hasher.update(&buffer[..count]);
+        }
+        let hash = hasher.finalize();
+        let nonce_size = cipher.suite().nonce_size();
+        header.salt.copy_from_slice(&hash[..SALT_SIZE]);
+        header.nonce[..nonce_size].copy_from_slice(&hash[SALT_SIZE..SALT_SIZE + nonce_size]);
+        Ok(header)
+    }
+
+    /// # 空のヘッダー
+    /// ソルト、ナンス、認証コードがすべて0のヘッダーを作成します。
+    fn empty(version: u8, cipher: Cipher) -> Self {
+        Header {
+            version,
+            cipher,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            salt: [0; SALT_SIZE],
+            nonce: [0; MAX_NONCE_SIZE],
+            mac: [0; HEADER_MAC_SIZE],
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    /// # チャンクのサイズ(byte)
+    /// 認証付き暗号はこのサイズの平文ごとに認証タグを付けます。
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// # ソルト
+    /// ソルトがない形式(バージョン1)では空です。
+    pub fn salt(&self) -> &[u8] {
+        match self.has_salt() {
+            true => &self.salt,
+            false => &[],
+        }
+    }
+
+    pub fn nonce(&self) -> &[u8] {
+        &self.nonce[..self.cipher.suite().nonce_size()]
+    }
+
+    /// # ChaCha20のナンス
+    /// 鍵ストリームを適用するだけの暗号(ChaCha20)の場合だけナンスを返します。
+    pub(crate) fn stream_nonce(&self) -> Option<[u8; NONCE_SIZE]> {
+        match self.cipher {
+            Cipher::ChaCha20 => {
+                let mut nonce = [0; NONCE_SIZE];
+                nonce.copy_from_slice(&self.nonce[..NONCE_SIZE]);
+                Some(nonce)
+            }
+            _ => None,
+        }
+    }
+
+    /// # ソルトと認証コードがある形式か
+    /// バージョン1以外の形式にはソルトと認証コードがあります。
+    fn has_salt(&self) -> bool {
+        self.version != Self::LEGACY_VERSION
+    }
+
+    /// # 認証コードを付ける
+    /// マスター鍵から求めた鍵でヘッダーの認証コードを計算して、認証コードを付けたヘッダーを返します。
+    /// 暗号化では書き込む前にこの関数で認証コードを付けます。ソルトがない形式ではそのまま返します。
+    pub fn authenticate(mut self, key: &Key) -> Self {
+        if self.has_salt() {
+            let mac = self.mac(key).finalize().into_bytes();
+            self.mac.copy_from_slice(&mac);
+        }
+        self
+    }
+
+    /// # 認証コードの確認
+    /// 鍵が誤っているか、ヘッダーが改ざんされている場合は`Error::AuthenticationFailed`を返します。
+    /// ソルトがない形式では何もしません。
+    pub fn verify(&self, key: &Key) -> Result<()> {
+        if !self.has_salt() {
+            return Ok(());
+        }
+        self.mac(key).verify(&self.mac).map_err(|_| {
+            debug!("ヘッダーの認証コードが一致しません。");
+            Error::AuthenticationFailed
+        })
+    }
+
+    /// # ファイルの暗号化の鍵
+    /// マスター鍵とソルトからHKDF-SHA256で求めます。ソルトがない形式ではマスター鍵をそのまま使います。
+    pub(crate) fn file_key(&self, key: &Key) -> Key {
+        match self.has_salt() {
+            true => Key::new(self.derive_key(key, ENCRYPTION_KEY_INFO)),
+            false => key.clone(),
+        }
+    }
+
+    /// # HKDF-SHA256による鍵の導出
+    fn derive_key(&self, key: &Key, info: &[u8]) -> [u8; KEY_SIZE] {
+        let mut derived = [0; KEY_SIZE];
+        hkdf::Hkdf::<sha2::Sha256>::new(Some(&self.salt), key.as_bytes())
+            .expand(info, &mut derived)
+            .expect("32byteの鍵はHKDF-SHA256で導出できる");
+        derived
+    }
+
+    /// # ヘッダーの認証コードの計算の準備
+    fn mac(&self, key: &Key) -> HmacSha256 {
+        let mut mac_key = self.derive_key(key, HEADER_MAC_KEY_INFO);
+        let mut mac = HmacSha256::new_from_slice(&mac_key).expect("HMACは任意の長さの鍵を使える");
+        for byte in mac_key.iter_mut() {
+            // 最適化で書き込みが消されないようにする
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+        mac.update(&self.associated_data());
+        mac
+    }
+
+    /// # ヘッダーのサイズ(byte)
+    pub fn size(&self) -> usize {
+        match self.version {
+            Self::LEGACY_VERSION => NONCE_SIZE,
+            _ => FIXED_SIZE + SALT_SIZE + self.cipher.suite().nonce_size() + HEADER_MAC_SIZE,
+        }
+    }
+
+    /// # 暗号化したサイズ(byte)
+    /// `plain_size`byteの平文を暗号化したときの、ヘッダーを含むサイズを返します。
+    pub fn encrypted_size(&self, plain_size: u64) -> u64 {
+        let tag_size = self.cipher.suite().tag_size() as u64;
+        let chunks = match tag_size {
+            0 => 0,
+            // 最後のチャンクは必ずチャンクのサイズより小さい(空の場合もある)
+            _ => plain_size / self.chunk_size as u64 + 1,
+        };
+        self.size() as u64 + plain_size + chunks * tag_size
+    }
+
+    /// # 復号したサイズ(byte)
+    /// ヘッダーを含めて`encrypted_size`byteのデータを復号したときのサイズを返します。
+    pub fn decrypted_size(&self, encrypted_size: u64) -> u64 {
+        let body_size = encrypted_size.saturating_sub(self.size() as u64);
+        let tag_size = self.cipher.suite().tag_size() as u64;
+        if tag_size == 0 {
+            return body_size;
+        }
+        let full_size = self.chunk_size as u64 + tag_size;
+        let full_chunks = body_size / full_size;
+        full_chunks * self.chunk_size as u64 + (body_size % full_size).saturating_sub(tag_size)
+    }
+
+    /// # ヘッダーのサイズの判定
+    /// データの先頭`bytes`からヘッダー全体のサイズ(byte)を求めます。
+    /// 判定に必要なデータが足りない場合は、少なくとも必要なサイズを返します。
+    pub fn required_size(bytes: &[u8]) -> Result<usize> {
+        if bytes.len() < MAGIC.len() || !bytes.starts_with(&MAGIC) {
+            return Ok(Self::MIN_SIZE);
+        }
+        if bytes.len() < FIXED_SIZE {
+            return Ok(FIXED_SIZE);
+        }
+        let version = bytes[8];
+        if version != Self::VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let cipher = Cipher::from_id(bytes[9]).ok_or(Error::UnsupportedCipher(bytes[9]))?;
+        Ok(Self::empty(version, cipher).size())
+    }
+
+    /// # ヘッダーの解析
+    /// データの先頭からヘッダーを読み取ります。データがヘッダーより短い場合は`Error::TruncatedHeader`を返します。
+    /// 認証コードは確かめないので、復号する前に`verify`で確かめてください。
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let size = Self::required_size(bytes)?;
+        if bytes.len() < size {
+            return Err(Error::TruncatedHeader);
+        }
+        let mut nonce = [0; NONCE_SIZE];
+        nonce.copy_from_slice(&bytes[..NONCE_SIZE]);
+        if size == Self::MIN_SIZE {
+            return Ok(Self::legacy(nonce));
+        }
+
+        let cipher = Cipher::from_id(bytes[9]).ok_or(Error::UnsupportedCipher(bytes[9]))?;
+        // フラグは書き込む時に0にするので、0以外を読み込むと認証コードが元のバイト列を認証しなくなる
+        let flags = u16::from_be_bytes([bytes[10], bytes[11]]);
+        if flags != 0 {
+            return Err(Error::UnsupportedFlags(flags));
+        }
+        let mut chunk_size = [0; 4];
+        chunk_size.copy_from_slice(&bytes[12..FIXED_SIZE]);
+        let chunk_size = u32::from_be_bytes(chunk_size);
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::InvalidChunkSize(chunk_size));
+        }
+        let mut header = Header {
+            chunk_size,
+            ..Self::empty(bytes[8], cipher)
+        };
+        let mut position = FIXED_SIZE;
+        if header.has_salt() {
+            header
+                .salt
+                .copy_from_slice(&bytes[position..position + SALT_SIZE]);
+            position += SALT_SIZE;
+        }
+        let nonce_size = cipher.suite().nonce_size();
+        header.nonce[..nonce_size].copy_from_slice(&bytes[position..position + nonce_size]);
+        position += nonce_size;
+        if header.has_salt() {
+            header
+                .mac
+                .copy_from_slice(&bytes[position..position + HEADER_MAC_SIZE]);
+        }
+        Ok(header)
+    }
+
+    /// # ヘッダーの読み込み
+    /// リーダーからヘッダーを読み込みます。ヘッダーの後ろのデータは読み込みません。
+    /// 認証コードは確かめないので、復号する前に`verify`で確かめてください。
+    pub fn read_from(reader: &mut impl Read) -> Result<Self> {
+        let mut bytes = [0; Self::MAX_SIZE];
+        let mut position = 0;
+        loop {
+            let size = Self::required_size(&bytes[..position])?;
+            if position >= size {
+                return Self::parse(&bytes[..position]);
+            }
+            reader
+                .read_exact(&mut bytes[position..size])
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::UnexpectedEof => Error::TruncatedHeader,
+                    _ => Error::Io(e),
+                })?;
+            position = size;
+        }
+    }
+
+    /// # ヘッダーの書き込み
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.associated_data();
+        if self.has_salt() {
+            bytes.extend_from_slice(&self.mac);
+        }
+        bytes
+    }
+
+    /// # 認証コードを除いたヘッダー
+    /// ヘッダーの認証コードと、認証付き暗号のチャンクで一緒に認証するデータです。
+    pub(crate) fn associated_data(&self) -> Vec<u8> {
+        if self.version == Self::LEGACY_VERSION {
+            return self.nonce[..NONCE_SIZE].to_vec();
+        }
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(self.version);
+        bytes.push(self.cipher.id());
+        bytes.extend_from_slice(&0u16.to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_size.to_be_bytes());
+        bytes.extend_from_slice(self.salt());
+        bytes.extend_from_slice(self.nonce());
+        bytes
+    }
+}
+
+/// # ナンス生成
+/// 現在の日時と乱数で元になる値を生成してsha3_256でハッシュ値を求め、必要なサイズだけ`nonce`に書き込みます。
+fn fill_nonce(nonce: &mut [u8], random: &dyn RandomSource, clock: &dyn Clock) {
+    let time_stamp_nanos = clock.timestamp_nanos();
+    let time_stamp: [u8; 8] = time_stamp_nanos.to_be_bytes();
+
+    let mut random_bytes = [0; 4];
+    random.fill_bytes(&mut random_bytes);
+    let mut nonce_non_hash = time_stamp.to_vec();
+    nonce_non_hash.extend(random_bytes);
+    debug!("nonce_non_hash: {:?}", nonce_non_hash);
+
+    let mut hasher = sha3::Sha3_256::new();
+    hasher.update(&nonce_non_hash);
+    let nonce_hashed = hasher.finalize();
+
+    nonce.copy_from_slice(&nonce_hashed[..nonce.len()]);
+    debug!("nonce_hashed: {:?}", nonce);
+}
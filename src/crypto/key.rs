@@ -0,0 +1,98 @@
+//! # 鍵
+//! ChaCha20の256bitの鍵を扱うモジュール
+
+use super::{Error, OsRandom, RandomSource, Result};
+use sha3::Digest;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+/// 鍵のサイズ(byte)
+pub const KEY_SIZE: usize = 32;
+
+/// # 鍵
+/// 使い終わったらメモリ上の鍵を0で上書きします。
+/// `Debug`では鍵そのものではなくフィンガープリントを表示します。
+#[derive(Clone)]
+pub struct Key([u8; KEY_SIZE]);
+
+impl Key {
+    pub fn new(bytes: [u8; KEY_SIZE]) -> Self {
+        Key(bytes)
+    }
+
+    /// # スライスから作成
+    /// 32byte以外の場合は`Error::InvalidKeyLength`を返します。
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let mut key = [0; KEY_SIZE];
+        if bytes.len() != KEY_SIZE {
+            return Err(Error::InvalidKeyLength(bytes.len()));
+        }
+        key.copy_from_slice(bytes);
+        Ok(Key(key))
+    }
+
+    /// # 鍵の生成
+    /// OSの乱数で新しい鍵を生成します。
+    pub fn generate() -> Self {
+        Self::generate_with(&OsRandom)
+    }
+
+    /// # 乱数を指定して鍵を生成
+    pub fn generate_with(random: &dyn RandomSource) -> Self {
+        let mut key = [0; KEY_SIZE];
+        random.fill_bytes(&mut key);
+        Key(key)
+    }
+
+    /// # 鍵ファイルの読み込み
+    /// 32byteの鍵ファイルを読み込みます。
+    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let size = file.metadata()?.len();
+        if size != KEY_SIZE as u64 {
+            return Err(Error::InvalidKeyLength(size as usize));
+        }
+        let mut key = [0; KEY_SIZE];
+        file.read_exact(&mut key)?;
+        Ok(Key(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
+        &self.0
+    }
+
+    /// # 鍵のフィンガープリント
+    /// 鍵のsha3_256のハッシュ値の最初の8byteを16進数で返します。
+    /// 同じ鍵を使ったかどうかを確認するためのもので、鍵そのものは復元できません。
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = sha3::Sha3_256::new();
+        hasher.update(b"crypto_tool key fingerprint");
+        hasher.update(self.0);
+        hasher.finalize()[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl From<[u8; KEY_SIZE]> for Key {
+    fn from(bytes: [u8; KEY_SIZE]) -> Self {
+        Key(bytes)
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key({})", self.fingerprint())
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        for byte in self.0.iter_mut() {
+            // 最適化で書き込みが消されないようにする
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! # ナンスの台帳
+//! 鍵のフィンガープリントごとに、暗号化に使ったナンスと暗号化したサイズを記録するモジュール
+//! ナンスは日時と乱数から作るので、VMのスナップショットやコンテナの複製で同じナンスが作られることがあります。
+//! 台帳を指定すると、使ったことのあるナンスを作り直し、指定されたヘッダーのナンスが使用済みならエラーにします。
+//!
+//! 台帳は追記するだけのテキストファイルで、1行に1つの記録を書き込みます。
+//!
+//! ```text
+//! nonce <鍵のフィンガープリント> <暗号> <ナンス(16進数)>
+//! bytes <鍵のフィンガープリント> <暗号化したサイズ(byte)>
+//! ```
+//!
+//! 同じ台帳を複数のプロセスで使えるように、記録する間はファイルを排他ロックし、
+//! 他のプロセスが追記した行を読み込んでから重複を確かめます。
+//! 書き込み中に異常終了して改行で終わっていない最後の行は、次にロックした時に切り詰めます。
+
+use super::{Error, Header, Key, Result};
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 1つの鍵で暗号化するファイル数の目安
+/// ランダムなナンスで暗号化する回数の上限(NIST SP 800-38D)
+pub const RECOMMENDED_FILES_PER_KEY: u64 = 1 << 32;
+/// 1つの鍵で暗号化するサイズ(byte)の目安 (256TiB)
+pub const RECOMMENDED_BYTES_PER_KEY: u64 = 1 << 48;
+
+/// # 鍵の使用量
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyUsage {
+    /// 暗号化したファイル数(記録したナンスの数)
+    pub files: u64,
+    /// 暗号化したサイズ(byte)
+    pub bytes: u64,
+}
+
+impl KeyUsage {
+    /// # 目安に近づいているか
+    /// ファイル数かサイズが目安の3/4を超えた場合は`true`を返します。
+    pub fn near_limit(&self) -> bool {
+        self.files >= RECOMMENDED_FILES_PER_KEY / 4 * 3
+            || self.bytes >= RECOMMENDED_BYTES_PER_KEY / 4 * 3
+    }
+}
+
+/// # ナンスの台帳
+/// 複数のスレッド、複数のプロセスから同時に使えます。
+pub struct NonceLedger {
+    state: Mutex<LedgerState>,
+}
+
+struct LedgerState {
+    file: File,
+    /// 読み込んだ位置(byte) ここまでの行は`nonces`と`usages`に反映済み
+    position: u64,
+    /// 読み込んだ行数
+    lines: usize,
+    /// 鍵のフィンガープリントと使用済みのナンス
+    nonces: HashSet<(String, Vec<u8>)>,
+    usages: HashMap<String, KeyUsage>,
+}
+
+/// # ファイルの排他ロック
+/// 破棄するとロックを解除します。
+struct FileLock<'a>(&'a File);
+
+impl<'a> FileLock<'a> {
+    fn new(file: &'a File) -> Result<Self> {
+        file.lock()?;
+        Ok(FileLock(file))
+    }
+}
+
+impl Drop for FileLock<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+impl NonceLedger {
+    /// # 台帳を開く
+    /// ファイルがない場合は作成します。読み込めない行がある場合は`Error::InvalidLedger`を返します。
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut state = LedgerState {
+            file,
+            position: 0,
+            lines: 0,
+            nonces: HashSet::new(),
+            usages: HashMap::new(),
+        };
+        state.locked(|_| Ok(()))?;
+        debug!(
+            "台帳{:?}に{}個のナンスが記録されています。",
+            path,
+            state.nonces.len()
+        );
+
+        Ok(NonceLedger {
+            state: Mutex::new(state),
+        })
+    }
+
+    /// # ナンスの記録
+    /// ヘッダーのナンスを`key`で使ったナンスとして記録します。
+    /// 他のプロセスが記録したものも含めて使用済みのナンスの場合は、記録せずに`false`を返します。
+    pub fn record_nonce(&self, key: &Key, header: &Header) -> Result<bool> {
+        let entry = (key.fingerprint(), header.nonce().to_vec());
+        let line = format!(
+            "nonce {} {} {}\n",
+            entry.0,
+            header.cipher().name(),
+            to_hex(&entry.1)
+        );
+        self.lock().locked(|state| {
+            if state.nonces.contains(&entry) {
+                debug!("使用済みのナンスです。");
+                return Ok(false);
+            }
+            state.append(&line)?;
+            Ok(true)
+        })
+    }
+
+    /// # 暗号化したサイズの記録
+    pub fn record_bytes(&self, key: &Key, bytes: u64) -> Result<()> {
+        let line = format!("bytes {} {}\n", key.fingerprint(), bytes);
+        self.lock().locked(|state| state.append(&line))
+    }
+
+    /// # 鍵の使用量
+    /// 他のプロセスが記録したものも含めた使用量を返します。
+    pub fn usage(&self, key: &Key) -> Result<KeyUsage> {
+        let fingerprint = key.fingerprint();
+        self.lock()
+            .locked(|state| Ok(state.usages.get(&fingerprint).copied().unwrap_or_default()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LedgerState> {
+        // 書き込み中に他のスレッドが異常終了しても、記録済みの内容は使える
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl LedgerState {
+    /// # ファイルをロックして処理する
+    /// 排他ロックしてから他のプロセスが追記した行を読み込み、`f`を呼びます。
+    fn locked<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let file = self.file.try_clone()?;
+        let _lock = FileLock::new(&file)?;
+        self.read_appended()?;
+        f(self)
+    }
+
+    /// # 追記された行の読み込み
+    /// 改行で終わっていない最後の行は、書き込み中に異常終了した残りなので切り詰めます。
+    /// ロックしている間に呼びます。
+    fn read_appended(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.position))?;
+        let mut appended = Vec::new();
+        self.file.read_to_end(&mut appended)?;
+
+        let complete = appended
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        if complete < appended.len() {
+            debug!("台帳の最後の行が途中で終わっているので切り詰めます。");
+            self.file.set_len(self.position + complete as u64)?;
+        }
+
+        let text = std::str::from_utf8(&appended[..complete])
+            .map_err(|_| Error::InvalidLedger(self.lines + 1))?;
+        for line in text.lines() {
+            self.lines += 1;
+            self.apply(line)?;
+        }
+        self.position += complete as u64;
+        Ok(())
+    }
+
+    /// # 1行の記録を反映
+    fn apply(&mut self, line: &str) -> Result<()> {
+        let invalid = Error::InvalidLedger(self.lines);
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        match fields[..] {
+            [] => {}
+            ["nonce", fingerprint, _cipher, nonce] => {
+                let nonce = parse_hex(nonce).ok_or(invalid)?;
+                self.usages
+                    .entry(fingerprint.to_string())
+                    .or_default()
+                    .files += 1;
+                self.nonces.insert((fingerprint.to_string(), nonce));
+            }
+            ["bytes", fingerprint, bytes] => {
+                let bytes = bytes.parse::<u64>().map_err(|_| invalid)?;
+                let usage = self.usages.entry(fingerprint.to_string()).or_default();
+                usage.bytes = usage.bytes.saturating_add(bytes);
+            }
+            _ => return Err(invalid),
+        }
+        Ok(())
+    }
+
+    /// # 1行の追記
+    /// ロックしている間に呼び、書き込んだ行も読み込み済みとして反映します。
+    fn append(&mut self, line: &str) -> Result<()> {
+        // 1行をまとめて書き込む 途中で異常終了した場合は、次にロックした時に切り詰める
+        self.file.write_all(line.as_bytes())?;
+        self.position += line.len() as u64;
+        self.lines += 1;
+        self.apply(line)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
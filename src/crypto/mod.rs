@@ -1,43 +1,108 @@
-use chacha20::cipher::{NewCipher, StreamCipher};
-use chacha20::{ChaCha20, Key, Nonce};
+//! # 暗号化・復号
+//! ChaCha20などの暗号スイート(`Cipher`)で暗号化・復号するモジュール
+//! 通常は`Encryptor`、`Decryptor`を使います。
+//! 少しずつ書き込む場合は`CipherWriter`、途中から復号する場合は`SeekableDecryptor`を使います。
+//! 非同期で読み書きする場合は`async`featureを有効にして`AsyncEncryptReader`などを使います。
+//! ファイルの形式は`Header`の後ろに暗号文が続く形式で、復号では暗号をヘッダーから判定します。
+//!
+//! ```no_run
+//! use crypto_tool::crypto::{Decryptor, Encryptor, Key};
+//!
+//! let key = Key::read_from_file("key.bin")?;
+//! let mut encrypted = Vec::new();
+//! Encryptor::new(&key).encrypt(&b"hello"[..], &mut encrypted)?;
+//!
+//! let mut decrypted = Vec::new();
+//! Decryptor::new(&key).decrypt(&encrypted[..], &mut decrypted)?;
+//! assert_eq!(decrypted, b"hello");
+//! # Ok::<(), crypto_tool::crypto::Error>(())
+//! ```
 
-struct CipherReader<T: std::io::Read> {
-    cipher: ChaCha20,
+#[cfg(feature = "async")]
+mod async_io;
+mod backend;
+mod chunk;
+mod entropy;
+mod error;
+mod header;
+mod key;
+mod ledger;
+mod output;
+mod parallel;
+mod pipeline;
+mod seekable;
+mod soft;
+mod stream;
+mod suite;
+mod writer;
+
+#[cfg(feature = "async")]
+pub use async_io::{
+    AsyncDecryptReader, AsyncDecryptWriter, AsyncEncryptReader, AsyncEncryptWriter,
+};
+pub use backend::{
+    active_backend, aes_gcm_backend, cpu_features, detect_backend, selected_backend, set_backend,
+    Backend, ChaCha20Cipher, KEYSTREAM_SEGMENT_SIZE,
+};
+#[cfg(feature = "python")]
+pub(crate) use chunk::{crypto_body, BodyCipher};
+pub use entropy::{Clock, FixedClock, OsRandom, RandomSource, SeededRandom, SystemClock};
+pub use error::{Error, Result};
+pub use header::{
+    Header, DEFAULT_CHUNK_SIZE, HEADER_MAC_SIZE, MAX_CHUNK_SIZE, MAX_NONCE_SIZE, NONCE_SIZE,
+    SALT_SIZE,
+};
+pub use key::{Key, KEY_SIZE};
+pub use ledger::{KeyUsage, NonceLedger, RECOMMENDED_BYTES_PER_KEY, RECOMMENDED_FILES_PER_KEY};
+pub use output::OutputFile;
+pub use parallel::crypto_chacha20_parallel;
+pub use pipeline::{crypto_chacha20_pipeline, PipelineSetting};
+pub use seekable::SeekableDecryptor;
+pub use stream::{Decryptor, Encryptor, DEFAULT_PARALLEL_THRESHOLD};
+pub use suite::{ChunkCipher, Cipher, CipherSuite};
+pub use writer::CipherWriter;
+
+/// # 進捗の通知
+/// 暗号化・復号したサイズ(byte)が増えるたびに、増えたサイズを受け取ります。
+/// 複数のスレッドから呼ばれることがあります。
+pub type Progress<'a> = &'a (dyn Fn(u64) + Sync);
+
+/// 進捗を通知しない場合に使う
+pub(crate) fn no_progress(_: u64) {}
+
+struct CipherReader<'a, T: std::io::Read> {
+    cipher: ChaCha20Cipher,
     reader: T,
+    progress: Progress<'a>,
 }
 
-impl<T: std::io::Read> std::io::Read for CipherReader<T> {
+impl<T: std::io::Read> std::io::Read for CipherReader<'_, T> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let count = self.reader.read(buf)?;
-        if count == buf.len() {
-            self.cipher.apply_keystream(buf);
-        } else {
-            self.cipher.apply_keystream(&mut buf[..count])
-        }
+        self.cipher.apply_keystream(&mut buf[..count]);
+        (self.progress)(count as u64);
         Ok(count)
     }
 }
 
+/// # 暗号化・復号
+/// リーダーのデータを1スレッドで暗号化・復号してライターに書き込み、処理したサイズ(byte)を返します。
 pub fn crypto_chacha20(
     key: &[u8; 32],
     nonce: &[u8; 12],
     input_file_reader: impl std::io::Read,
     mut writer: impl std::io::Write,
-    progress_bar: indicatif::ProgressBar,
-) {
-    let key = Key::from_slice(key);
-    let nonce = Nonce::from_slice(nonce);
-
-    let cipher = ChaCha20::new(key, nonce);
+    progress: Progress,
+) -> std::io::Result<u64> {
+    let cipher = ChaCha20Cipher::new(key, nonce);
 
     let mut read_cipher = CipherReader {
         cipher,
         reader: input_file_reader,
+        progress,
     };
 
-    let pre_time = chrono::Local::now();
-    let _ = std::io::copy(&mut read_cipher, &mut progress_bar.wrap_write(&mut writer));
-    progress_bar.finish();
-    let post_time = chrono::Local::now();
-    println!("{:?}", post_time - pre_time);
+    let bytes = std::io::copy(&mut read_cipher, &mut writer)?;
+    writer.flush()?;
+    Ok(bytes)
 }
@@ -0,0 +1,80 @@
+//! # 書き出し先のファイル
+//! 暗号化・復号に失敗した場合に、途中まで書き込んだファイルを残さないための`OutputFile`のモジュール
+//! CLIとC APIのファイルの暗号化・復号で使います。
+
+use log::debug;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// # 書き出し先のファイル
+/// 書き出し先と同じディレクトリの一時ファイルに書き込み、`persist`で書き出し先の名前に変えます。
+/// `persist`せずに破棄した場合(暗号化・復号に失敗した場合)は一時ファイルを削除するので、
+/// 途中まで書き込んだファイルが残らず、同じ名前の既存のファイルも上書きされません。
+///
+/// ```no_run
+/// use crypto_tool::crypto::{Encryptor, Key, OutputFile};
+///
+/// let key = Key::read_from_file("key.bin")?;
+/// let input = std::fs::File::open("data.csv")?;
+/// let output = OutputFile::create("data.csv.c20")?;
+/// Encryptor::new(&key).encrypt_file(&input, output.file())?;
+/// output.persist()?;
+/// # Ok::<(), crypto_tool::crypto::Error>(())
+/// ```
+pub struct OutputFile {
+    file: File,
+    path: PathBuf,
+    temp_path: PathBuf,
+    persisted: bool,
+}
+
+impl OutputFile {
+    /// # 作成
+    /// 書き出し先と同じディレクトリに`.<ファイル名>.<プロセスID>.tmp`の一時ファイルを作成します。
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        Ok(OutputFile {
+            file,
+            path: path.to_path_buf(),
+            temp_path,
+            persisted: false,
+        })
+    }
+
+    /// 書き込む一時ファイル
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// 書き出し先のパス
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// # 書き出し先の名前に変える
+    /// 失敗した場合は一時ファイルを削除します。
+    pub fn persist(mut self) -> io::Result<()> {
+        std::fs::rename(&self.temp_path, &self.path)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            debug!("一時ファイル{:?}を削除します。", self.temp_path);
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! # 並列暗号化
+//! 大きなファイルを複数の領域に分けて、複数のスレッドで暗号化・復号するモジュール
+//! ChaCha20は鍵ストリームの任意の位置にシークできるので、
+//! 各スレッドは担当する領域の位置までブロックカウンタを進めてから暗号化します。
+//! 書き込みはファイルの位置を指定して行うので、出力は`crypto_chacha20`と同じになります。
+//! 認証付き暗号はチャンクごとにナンスが決まっているので、チャンクを単位にして同じように分けて処理します。
+
+use super::chunk::Direction;
+use super::{ChaCha20Cipher, Error, Header, Key, Progress, Result};
+use std::fs::File;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 1回に取り出して処理する領域のサイズ(byte) ChaCha20のブロックサイズ(64byte)の倍数にする
+const SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+/// 1回に読み込んで暗号化するサイズ(byte)
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// # 並列暗号化・復号
+/// `input_file`の`input_offset`から`length`byteを暗号化・復号して、
+/// `output_file`の`output_offset`からの位置に書き込み、処理したサイズ(byte)を返します。
+/// 領域は`SEGMENT_SIZE`ずつ順番にスレッドに割り当てます。
+/// 読み込みや書き込みに失敗した場合は残りの領域を処理せずにエラーを返します。
+#[allow(clippy::too_many_arguments)]
+pub fn crypto_chacha20_parallel(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    input_file: &File,
+    input_offset: u64,
+    output_file: &File,
+    output_offset: u64,
+    length: u64,
+    threads: usize,
+    progress: Progress,
+) -> io::Result<u64> {
+    // 書き出し先のファイルのサイズを先に確保しておく
+    output_file.set_len(output_offset + length)?;
+
+    let next_segment = AtomicU64::new(0);
+    let segment_count = length.div_ceil(SEGMENT_SIZE);
+
+    std::thread::scope(|scope| {
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let next_segment = &next_segment;
+                scope.spawn(move || -> io::Result<()> {
+                    let mut buffer = vec![0; BUFFER_SIZE];
+                    let mut crypto_segment = |segment: u64| -> io::Result<()> {
+                        let start = segment * SEGMENT_SIZE;
+                        let end = (start + SEGMENT_SIZE).min(length);
+
+                        // 領域の先頭まで鍵ストリームをシークする
+                        let mut cipher = ChaCha20Cipher::new(key, nonce);
+                        cipher.seek(start);
+
+                        let mut position = start;
+                        while position < end {
+                            let size = ((end - position) as usize).min(BUFFER_SIZE);
+                            let buffer = &mut buffer[..size];
+                            read_exact_at(input_file, buffer, input_offset + position)?;
+                            cipher.apply_keystream(buffer);
+                            write_all_at(output_file, buffer, output_offset + position)?;
+                            progress(size as u64);
+                            position += size as u64;
+                        }
+                        Ok(())
+                    };
+                    loop {
+                        let segment = next_segment.fetch_add(1, Ordering::SeqCst);
+                        if segment >= segment_count {
+                            return Ok(());
+                        }
+                        if let Err(e) = crypto_segment(segment) {
+                            // ほかのスレッドに残りの領域を処理させない
+                            next_segment.store(segment_count, Ordering::SeqCst);
+                            return Err(e);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers.into_iter().try_for_each(|worker| {
+            worker
+                .join()
+                .unwrap_or_else(|_| Err(Error::ThreadPanicked.into()))
+        })
+    })?;
+    Ok(length)
+}
+
+/// # チャンクの並列暗号化・復号
+/// 認証付き暗号の`input_file`の`input_offset`から`length`byteを`direction`に暗号化・復号して、
+/// `output_file`の`output_offset`からの位置に書き込み、処理した平文のサイズ(byte)を返します。
+/// `SEGMENT_SIZE`に収まる数のチャンクをまとめて、順番にスレッドに割り当てます。
+/// 認証に失敗した場合は残りのチャンクを処理せずにエラーを返します。
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn crypto_chunks_parallel(
+    key: &Key,
+    header: &Header,
+    direction: Direction,
+    input_file: &File,
+    input_offset: u64,
+    output_file: &File,
+    output_offset: u64,
+    length: u64,
+    threads: usize,
+    progress: Progress,
+) -> Result<u64> {
+    let cipher = header.cipher().suite().chunk_cipher(
+        &header.file_key(key),
+        header.nonce(),
+        header.chunk_size(),
+        &header.associated_data(),
+    );
+    let cipher = cipher.as_ref();
+    let (input_chunk_size, output_chunk_size) = direction.chunk_sizes(header);
+
+    // 最後のチャンクは必ずチャンクのサイズより小さい(空の場合もある)
+    let chunk_count = length / input_chunk_size + 1;
+    let last_size =
+        (length % input_chunk_size + output_chunk_size).saturating_sub(input_chunk_size);
+    let output_length = (chunk_count - 1) * output_chunk_size + last_size;
+    // 書き出し先のファイルのサイズを先に確保しておく
+    output_file.set_len(output_offset + output_length)?;
+
+    let chunks_per_segment = (SEGMENT_SIZE / input_chunk_size).max(1);
+    let segment_count = chunk_count.div_ceil(chunks_per_segment);
+    let next_segment = AtomicU64::new(0);
+
+    std::thread::scope(|scope| {
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let next_segment = &next_segment;
+                scope.spawn(move || -> Result<()> {
+                    let mut buffer =
+                        Vec::with_capacity(input_chunk_size.max(output_chunk_size) as usize);
+                    let mut crypto_chunk = |index: u64| -> Result<()> {
+                        let start = index * input_chunk_size;
+                        let size = (length - start).min(input_chunk_size) as usize;
+                        buffer.resize(size, 0);
+                        read_exact_at(input_file, &mut buffer, input_offset + start)?;
+                        direction.apply(cipher, index, index + 1 == chunk_count, &mut buffer)?;
+                        write_all_at(
+                            output_file,
+                            &buffer,
+                            output_offset + index * output_chunk_size,
+                        )?;
+                        progress(size.min(buffer.len()) as u64);
+                        Ok(())
+                    };
+                    loop {
+                        let segment = next_segment.fetch_add(1, Ordering::SeqCst);
+                        if segment >= segment_count {
+                            return Ok(());
+                        }
+                        let first = segment * chunks_per_segment;
+                        let end = (first + chunks_per_segment).min(chunk_count);
+                        if let Err(e) = (first..end).try_for_each(&mut crypto_chunk) {
+                            // ほかのスレッドに残りの領域を処理させない
+                            next_segment.store(segment_count, Ordering::SeqCst);
+                            return Err(e);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().unwrap_or(Err(Error::ThreadPanicked)))
+    })?;
+    Ok(length.min(output_length))
+}
+
+/// # 位置を指定した読み込み
+/// `offset`の位置から`buf`がいっぱいになるまで読み込みます。
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match read_at(file, buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// # 位置を指定した書き込み
+/// `offset`の位置から`buf`をすべて書き込みます。
+fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match write_at(file, buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::write_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_write(file, buf, offset)
+}
@@ -0,0 +1,122 @@
+//! # パイプライン暗号化
+//! 読み込み、暗号化、書き込みを別々のスレッドで同時に進めるモジュール
+//! 読み込みスレッドがバッファを埋めて、暗号化したバッファを書き込みスレッドが書き出します。
+//! 書き出し終わったバッファは読み込みスレッドに戻して使い回します。
+
+use super::{ChaCha20Cipher, Error, Progress};
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+
+/// # パイプラインの設定
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineSetting {
+    /// 1つのバッファのサイズ(byte)
+    pub buffer_size: usize,
+    /// 同時に使うバッファの数 0の場合はパイプラインを使わずに1スレッドで処理する
+    pub depth: usize,
+}
+
+impl Default for PipelineSetting {
+    fn default() -> Self {
+        PipelineSetting {
+            buffer_size: 1024 * 1024,
+            depth: 4,
+        }
+    }
+}
+
+/// # パイプライン暗号化・復号
+/// リーダーのデータを暗号化・復号してライターに書き込み、処理したサイズ(byte)を返します。
+/// `setting.depth`個のバッファを読み込み、暗号化、書き込みの間で受け渡します。
+pub fn crypto_chacha20_pipeline(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    mut reader: impl Read + Send,
+    mut writer: impl Write + Send,
+    setting: PipelineSetting,
+    progress: Progress,
+) -> io::Result<u64> {
+    let mut cipher = ChaCha20Cipher::new(key, nonce);
+
+    let depth = setting.depth.max(1);
+    let buffer_size = setting.buffer_size.max(1);
+
+    // 空きバッファ、読み込み済みのバッファ、暗号化済みのバッファを受け渡すチャネル
+    let (free_sender, free_receiver) = mpsc::sync_channel::<Vec<u8>>(depth);
+    let (read_sender, read_receiver) = mpsc::sync_channel::<Vec<u8>>(depth);
+    let (crypted_sender, crypted_receiver) = mpsc::sync_channel::<Vec<u8>>(depth);
+    for _ in 0..depth {
+        let _ = free_sender.send(vec![0; buffer_size]);
+    }
+
+    let bytes = std::thread::scope(|scope| {
+        // 読み込みスレッド
+        let reader_thread = scope.spawn(move || -> io::Result<()> {
+            while let Ok(mut buffer) = free_receiver.recv() {
+                buffer.resize(buffer_size, 0);
+                let size = read_full(&mut reader, &mut buffer)?;
+                if size == 0 {
+                    break;
+                }
+                buffer.truncate(size);
+                if read_sender.send(buffer).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        // 書き込みスレッド
+        let writer_thread = scope.spawn(move || -> io::Result<u64> {
+            let mut bytes = 0;
+            while let Ok(buffer) = crypted_receiver.recv() {
+                writer.write_all(&buffer)?;
+                bytes += buffer.len() as u64;
+                progress(buffer.len() as u64);
+                // 読み込みスレッドが終わっている場合は戻せなくてもよい
+                let _ = free_sender.send(buffer);
+            }
+            writer.flush()?;
+            Ok(bytes)
+        });
+
+        // 暗号化はこのスレッドで行う
+        while let Ok(mut buffer) = read_receiver.recv() {
+            cipher.apply_keystream(&mut buffer);
+            if crypted_sender.send(buffer).is_err() {
+                break;
+            }
+        }
+        drop(read_receiver);
+        drop(crypted_sender);
+
+        let read_result = join(reader_thread);
+        let write_result = join(writer_thread);
+        read_result?;
+        write_result
+    })?;
+    Ok(bytes)
+}
+
+/// # バッファがいっぱいになるまで読み込む
+/// 読み込んだサイズ(byte)を返します。最後まで読み込んだ場合はバッファのサイズより小さくなります。
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut size = 0;
+    while size < buffer.len() {
+        match reader.read(&mut buffer[size..]) {
+            Ok(0) => break,
+            Ok(n) => size += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(size)
+}
+
+/// # スレッドの終了を待つ
+/// スレッドがパニックした場合はErrを返します。
+fn join<T>(handle: std::thread::ScopedJoinHandle<io::Result<T>>) -> io::Result<T> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(Error::ThreadPanicked.into()))
+}
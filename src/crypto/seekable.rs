@@ -0,0 +1,203 @@
+//! # シークできる復号
+//! 暗号化したデータの途中から復号できる`SeekableDecryptor`のモジュール
+//! ChaCha20は鍵ストリームの位置を移動できるので、先頭から復号しなくても任意の位置を復号できます。
+//! 認証付き暗号は位置を含むチャンクだけを読み込んで、認証タグを確かめてから復号します。
+
+use super::{ChaCha20Cipher, ChunkCipher, Header, Key, Result};
+use crate::message::{self, Message};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// # シークできる復号
+/// シークできるリーダーから暗号文を読み込んで復号します。
+/// 位置は平文の位置で、ヘッダーは含みません。
+///
+/// ```no_run
+/// use crypto_tool::crypto::{Decryptor, Key};
+/// use std::io::{Read, Seek, SeekFrom};
+///
+/// let key = Key::read_from_file("key.bin")?;
+/// let input = std::fs::File::open("dump.sql.c20")?;
+/// let mut reader = Decryptor::new(&key).seekable(input)?;
+/// reader.seek(SeekFrom::Start(1024 * 1024))?;
+/// let mut table = vec![0; 4096];
+/// reader.read_exact(&mut table)?;
+/// # Ok::<(), crypto_tool::crypto::Error>(())
+/// ```
+pub struct SeekableDecryptor<R: Read + Seek> {
+    cipher: SeekCipher,
+    header: Header,
+    reader: R,
+    /// リーダー上の暗号文の先頭の位置
+    data_start: u64,
+    /// 平文の位置
+    position: u64,
+}
+
+/// # 復号の方法
+enum SeekCipher {
+    /// 鍵ストリームをシークして復号する (ChaCha20)
+    Stream(Box<ChaCha20Cipher>),
+    /// チャンクごとに復号する
+    Chunks {
+        cipher: Box<dyn ChunkCipher>,
+        /// 復号したチャンクの番号と平文
+        chunk: Option<(u64, Vec<u8>)>,
+    },
+}
+
+impl<R: Read + Seek> SeekableDecryptor<R> {
+    /// # 作成
+    /// リーダーの現在の位置からヘッダーを読み込みます。
+    pub fn new(key: &Key, mut reader: R) -> Result<Self> {
+        let header = Header::read_from(&mut reader)?;
+        header.verify(key)?;
+        let data_start = reader.stream_position()?;
+        let key = header.file_key(key);
+        let cipher = match header.stream_nonce() {
+            Some(nonce) => {
+                SeekCipher::Stream(Box::new(ChaCha20Cipher::new(key.as_bytes(), &nonce)))
+            }
+            None => SeekCipher::Chunks {
+                cipher: header.cipher().suite().chunk_cipher(
+                    &key,
+                    header.nonce(),
+                    header.chunk_size(),
+                    &header.associated_data(),
+                ),
+                chunk: None,
+            },
+        };
+        Ok(SeekableDecryptor {
+            cipher,
+            header,
+            reader,
+            data_start,
+            position: 0,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let SeekableDecryptor {
+            cipher,
+            header,
+            reader,
+            data_start,
+            position,
+        } = self;
+        let (cipher, chunk) = match cipher {
+            SeekCipher::Stream(cipher) => {
+                let count = reader.read(buf)?;
+                cipher.apply_keystream(&mut buf[..count]);
+                *position += count as u64;
+                return Ok(count);
+            }
+            SeekCipher::Chunks { cipher, chunk } => (cipher, chunk),
+        };
+
+        let chunk_size = header.chunk_size() as u64;
+        let index = *position / chunk_size;
+        let offset = (*position % chunk_size) as usize;
+        let plain_text = match chunk.take() {
+            Some((chunk_index, plain_text)) if chunk_index == index => plain_text,
+            _ => read_chunk(reader, header, *data_start, cipher.as_ref(), index)?,
+        };
+
+        // データの終わりより後ろの位置では何も読み込まない
+        let available = plain_text.get(offset..).unwrap_or_default();
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        *position += count as u64;
+        *chunk = Some((index, plain_text));
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableDecryptor<R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let end = self.reader.seek(SeekFrom::End(0))?;
+                self.header
+                    .decrypted_size(end.saturating_sub(self.data_start) + self.header.size() as u64)
+                    .checked_add_signed(offset)
+            }
+        };
+        // 暗号文の位置がu64に収まる範囲ならどこにでも移動できる
+        let position =
+            match position.filter(|position| position.checked_add(self.data_start).is_some()) {
+                Some(position) => position,
+                None => {
+                    // 末尾に移動したリーダーを元の位置に戻す
+                    self.reader
+                        .seek(SeekFrom::Start(self.data_start + self.position))?;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        message::text(Message::SeekOutOfRange),
+                    ));
+                }
+            };
+
+        match &mut self.cipher {
+            SeekCipher::Stream(cipher) => {
+                self.reader
+                    .seek(SeekFrom::Start(self.data_start + position))?;
+                cipher.seek(position);
+            }
+            // チャンクは読み込むときにシークする
+            SeekCipher::Chunks { .. } => {}
+        }
+        self.position = position;
+        Ok(position)
+    }
+}
+
+/// # チャンクの読み込み
+/// `index`番目のチャンクを読み込んで復号します。データの終わりより後ろのチャンクは空にします。
+/// データのサイズが暗号化したサイズとしてあり得ない場合は、切り詰められているので`UnexpectedEof`を返します。
+fn read_chunk(
+    reader: &mut (impl Read + Seek),
+    header: &Header,
+    data_start: u64,
+    cipher: &dyn ChunkCipher,
+    index: u64,
+) -> io::Result<Vec<u8>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    // 最後のチャンクは平文が空でも認証タグがあり、チャンクのサイズより小さいので、
+    // チャンクの境界や認証タグの途中で終わっているデータは切り詰められている
+    let encrypted_size = end.saturating_sub(data_start) + header.size() as u64;
+    if header.encrypted_size(header.decrypted_size(encrypted_size)) != encrypted_size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            message::text(Message::DataTruncated),
+        ));
+    }
+
+    let full_size = header.chunk_size() as u64 + header.cipher().suite().tag_size() as u64;
+    // データの終わりより後ろの位置は読み込んでも空なので、あふれる場合は末尾にまとめる
+    let start = index.saturating_mul(full_size).saturating_add(data_start);
+    if start >= end {
+        return Ok(Vec::new());
+    }
+    let size = full_size.min(end - start);
+    reader.seek(SeekFrom::Start(start))?;
+    let mut chunk = vec![0; size as usize];
+    reader.read_exact(&mut chunk)?;
+    cipher.open(index, start + size == end, &mut chunk)?;
+    Ok(chunk)
+}
@@ -0,0 +1,115 @@
+//! # ChaCha20のポータブル実装
+//! CPUの拡張命令を使わないChaCha20の実装です。(RFC 8439)
+//! `--backend soft`が指定された場合に使います。
+
+/// ChaCha20のブロックサイズ(byte)
+const BLOCK_SIZE: usize = 64;
+
+/// # ポータブル版ChaCha20
+pub struct SoftChaCha20 {
+    /// 定数、鍵、ブロックカウンタ、ナンスを並べた初期状態
+    state: [u32; 16],
+    /// 次に生成するブロックの番号
+    block: u64,
+    /// 生成済みの鍵ストリーム
+    keystream: [u8; BLOCK_SIZE],
+    /// `keystream`の中で次に使う位置 `BLOCK_SIZE`の場合は次のブロックを生成する
+    offset: usize,
+}
+
+impl SoftChaCha20 {
+    pub fn new(key: &[u8; 32], nonce: &[u8; 12]) -> Self {
+        let mut state = [0; 16];
+        // "expand 32-byte k"
+        state[0] = 0x6170_7865;
+        state[1] = 0x3320_646e;
+        state[2] = 0x7962_2d32;
+        state[3] = 0x6b20_6574;
+        for (i, chunk) in key.chunks_exact(4).enumerate() {
+            state[4 + i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for (i, chunk) in nonce.chunks_exact(4).enumerate() {
+            state[13 + i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        SoftChaCha20 {
+            state,
+            block: 0,
+            keystream: [0; BLOCK_SIZE],
+            offset: BLOCK_SIZE,
+        }
+    }
+
+    /// # 鍵ストリームの適用
+    /// データに鍵ストリームをXORします。
+    pub fn apply_keystream(&mut self, mut data: &mut [u8]) {
+        while !data.is_empty() {
+            if self.offset == BLOCK_SIZE {
+                self.generate();
+            }
+            let size = (BLOCK_SIZE - self.offset).min(data.len());
+            let (head, tail) = data.split_at_mut(size);
+            for (byte, key) in head
+                .iter_mut()
+                .zip(&self.keystream[self.offset..self.offset + size])
+            {
+                *byte ^= key;
+            }
+            self.offset += size;
+            data = tail;
+        }
+    }
+
+    /// # 鍵ストリームのシーク
+    /// 鍵ストリームの位置を先頭から`position`byteの位置に移動します。
+    pub fn seek(&mut self, position: u64) {
+        self.block = position / BLOCK_SIZE as u64;
+        let offset = (position % BLOCK_SIZE as u64) as usize;
+        if offset == 0 {
+            self.offset = BLOCK_SIZE;
+        } else {
+            self.generate();
+            self.offset = offset;
+        }
+    }
+
+    /// # ブロックの生成
+    /// `block`番目のブロックの鍵ストリームを生成して、ブロックの番号を進めます。
+    fn generate(&mut self) {
+        // IETF版のChaCha20のブロックカウンタは32bit
+        assert!(
+            self.block <= u32::MAX as u64,
+            "ChaCha20のブロックカウンタが上限に達しました"
+        );
+        let mut input = self.state;
+        input[12] = self.block as u32;
+        let mut working = input;
+        for _ in 0..10 {
+            quarter_round(&mut working, 0, 4, 8, 12);
+            quarter_round(&mut working, 1, 5, 9, 13);
+            quarter_round(&mut working, 2, 6, 10, 14);
+            quarter_round(&mut working, 3, 7, 11, 15);
+            quarter_round(&mut working, 0, 5, 10, 15);
+            quarter_round(&mut working, 1, 6, 11, 12);
+            quarter_round(&mut working, 2, 7, 8, 13);
+            quarter_round(&mut working, 3, 4, 9, 14);
+        }
+        for (i, word) in working.iter().enumerate() {
+            let word = word.wrapping_add(input[i]);
+            self.keystream[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        self.block += 1;
+        self.offset = 0;
+    }
+}
+
+/// # クォーターラウンド
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
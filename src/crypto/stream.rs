@@ -0,0 +1,518 @@
+//! # 暗号化・復号の入り口
+//! `Read`と`Write`の間で暗号化・復号する`Encryptor`、`Decryptor`のモジュール
+//! 暗号化ではヘッダーを生成して先頭に書き込み、復号では先頭のヘッダーを読み込んでから処理します。
+//! ファイルどうしで処理する場合は、大きなファイルを複数のスレッドで処理できます。
+//! 認証付き暗号のストリームは1スレッドでチャンクごとに処理します。
+
+use super::chunk::{crypto_body, BodyCipher, Direction};
+use super::parallel::crypto_chunks_parallel;
+use super::{
+    crypto_chacha20, crypto_chacha20_parallel, crypto_chacha20_pipeline, no_progress, Cipher,
+    CipherWriter, Clock, Error, Header, Key, NonceLedger, OsRandom, PipelineSetting, Progress,
+    RandomSource, Result, SeekableDecryptor, SystemClock,
+};
+use log::debug;
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+
+/// このサイズ(byte)以上のファイルは複数のスレッドで暗号化・復号する
+pub const DEFAULT_PARALLEL_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// 台帳に記録済みのナンスを作り直す回数
+const NONCE_ATTEMPTS: usize = 8;
+
+/// # 処理方法の設定
+/// `Encryptor`と`Decryptor`で共通の設定
+#[derive(Clone, Copy)]
+struct Setting<'a> {
+    pipeline: PipelineSetting,
+    threads: usize,
+    parallel_threshold: u64,
+    progress: Progress<'a>,
+}
+
+impl Setting<'_> {
+    /// # 複数のスレッドで処理するか
+    fn is_parallel(&self, length: u64) -> bool {
+        self.threads > 1 && length >= self.parallel_threshold
+    }
+}
+
+impl Default for Setting<'_> {
+    fn default() -> Self {
+        Setting {
+            pipeline: PipelineSetting::default(),
+            threads: 1,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            progress: &no_progress,
+        }
+    }
+}
+
+/// # 暗号化
+/// ```no_run
+/// use crypto_tool::crypto::{Encryptor, Key};
+///
+/// let key = Key::generate();
+/// let input = std::fs::File::open("data.csv")?;
+/// let output = std::fs::File::create("data.csv.c20")?;
+/// Encryptor::new(&key).threads(4).encrypt_file(&input, &output)?;
+/// # Ok::<(), crypto_tool::crypto::Error>(())
+/// ```
+pub struct Encryptor<'a> {
+    key: &'a Key,
+    header: Option<Header>,
+    cipher: Cipher,
+    deterministic: bool,
+    ledger: Option<&'a NonceLedger>,
+    random: &'a dyn RandomSource,
+    clock: &'a dyn Clock,
+    setting: Setting<'a>,
+}
+
+impl<'a> Encryptor<'a> {
+    pub fn new(key: &'a Key) -> Self {
+        Encryptor {
+            key,
+            header: None,
+            cipher: Cipher::default(),
+            deterministic: false,
+            ledger: None,
+            random: &OsRandom,
+            clock: &SystemClock,
+            setting: Setting::default(),
+        }
+    }
+
+    /// # ヘッダーの指定
+    /// 指定しない場合は新しいナンスでヘッダーを生成します。
+    pub fn header(mut self, header: Header) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    /// # 暗号の指定
+    /// 省略時はChaCha20です。`header`でヘッダーを指定した場合はヘッダーの暗号を使います。
+    pub fn cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// # 決定的な暗号化
+    /// 有効にすると乱数ではなく鍵と平文からナンスを求めるので(`Header::deterministic`)、
+    /// 同じ鍵で同じ平文を暗号化すると同じ暗号文になります。重複を排除するストレージに保存する場合に使います。
+    /// ナンスが重複しても安全な暗号(`Cipher::Aes256GcmSiv`)が必要で、他の暗号では
+    /// `Error::DeterministicUnsupported`になります。
+    /// 平文を2回読むので、`encrypt`では平文をすべてメモリに読み込みます。
+    /// `header`でヘッダーを指定した場合と、`writer`では使いません。
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// # ナンスの台帳
+    /// 使ったナンスと暗号化したサイズを台帳に記録します。
+    /// 生成したナンスが記録済みの場合は作り直し、`header`で指定したヘッダーのナンスが記録済みの場合は
+    /// `Error::NonceReused`になります。
+    /// 決定的な暗号化では同じ平文で同じナンスになるので記録しません。`writer`では使いません。
+    pub fn ledger(mut self, ledger: &'a NonceLedger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// # 乱数の指定
+    /// 新しいヘッダーのソルトとナンスに使う乱数です。省略時はOSの乱数(`OsRandom`)です。
+    pub fn random(mut self, random: &'a dyn RandomSource) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// # 時刻の指定
+    /// 新しいヘッダーのナンスに使う時刻です。省略時は現在の日時(`SystemClock`)です。
+    pub fn clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// # パイプラインの設定
+    /// バッファの数が0の場合はパイプラインを使わずに1スレッドで処理します。
+    /// 認証付き暗号ではバッファのサイズだけを使います。
+    pub fn pipeline(mut self, pipeline: PipelineSetting) -> Self {
+        self.setting.pipeline = pipeline;
+        self
+    }
+
+    /// # 大きなファイルの処理に使うスレッド数
+    /// `encrypt_file`でだけ使います。省略時は1です。
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.setting.threads = threads;
+        self
+    }
+
+    /// # 複数のスレッドで処理するファイルのサイズ(byte)
+    pub fn parallel_threshold(mut self, parallel_threshold: u64) -> Self {
+        self.setting.parallel_threshold = parallel_threshold;
+        self
+    }
+
+    /// # 進捗の通知先
+    pub fn progress(mut self, progress: Progress<'a>) -> Self {
+        self.setting.progress = progress;
+        self
+    }
+
+    /// # ストリームの暗号化
+    /// ヘッダーと暗号文をライターに書き込み、暗号化したサイズ(byte)を返します。
+    pub fn encrypt(&self, reader: impl Read + Send, mut writer: impl Write + Send) -> Result<u64> {
+        if self.is_deterministic() {
+            // ナンスを求めるために平文を先に読み込む
+            let mut plain_text = Vec::new();
+            let mut reader = reader;
+            reader.read_to_end(&mut plain_text)?;
+            let header = Header::deterministic(self.cipher, self.key, &plain_text[..])?
+                .authenticate(self.key);
+            header.write_to(&mut writer)?;
+            return crypto_stream(
+                self.key,
+                &header,
+                BodyCipher::encryptor,
+                &plain_text[..],
+                writer,
+                &self.setting,
+            );
+        }
+        let header = self.recorded_header()?;
+        header.write_to(&mut writer)?;
+        let bytes = crypto_stream(
+            self.key,
+            &header,
+            BodyCipher::encryptor,
+            reader,
+            writer,
+            &self.setting,
+        )?;
+        self.record_bytes(bytes)
+    }
+
+    /// # 暗号化するライターの作成
+    /// 書き込まれたデータを暗号化して`writer`に書き込む`CipherWriter`を返します。
+    /// パイプライン、スレッド数、進捗の設定は使いません。
+    pub fn writer<W: Write>(&self, writer: W) -> CipherWriter<W> {
+        CipherWriter::with_header(self.key, self.new_header(), writer)
+    }
+
+    /// # ファイルの暗号化
+    /// 入力ファイルを暗号化して書き出し先のファイルに書き込み、暗号化したサイズ(byte)を返します。
+    /// `threads`が2以上の場合は、`parallel_threshold`以上のファイルを複数のスレッドで処理します。
+    pub fn encrypt_file(&self, input_file: &File, output_file: &File) -> Result<u64> {
+        let input_file_size = input_file.metadata()?.len();
+        let header = if self.is_deterministic() {
+            // ナンスを求めるために平文を読んでから、読み始めた位置に戻す
+            let mut input = input_file;
+            let position = input.stream_position()?;
+            let header = Header::deterministic(self.cipher, self.key, io::BufReader::new(input))?;
+            input.seek(io::SeekFrom::Start(position))?;
+            header.authenticate(self.key)
+        } else {
+            self.recorded_header()?
+        };
+
+        if self.setting.is_parallel(input_file_size) {
+            // ヘッダーの分だけ書き出す位置をずらす
+            header.write_to(&mut &*output_file)?;
+            let bytes = crypto_file_parallel(
+                self.key,
+                &header,
+                Direction::Seal,
+                input_file,
+                0,
+                output_file,
+                header.size() as u64,
+                input_file_size,
+                &self.setting,
+            )?;
+            return self.record_bytes(bytes);
+        }
+
+        let mut writer = io::BufWriter::new(output_file);
+        header.write_to(&mut writer)?;
+        let bytes = crypto_stream(
+            self.key,
+            &header,
+            BodyCipher::encryptor,
+            io::BufReader::new(input_file),
+            writer,
+            &self.setting,
+        )?;
+        self.record_bytes(bytes)
+    }
+
+    /// # 決定的な暗号化でヘッダーを作成するか
+    fn is_deterministic(&self) -> bool {
+        self.deterministic && self.header.is_none()
+    }
+
+    /// # ヘッダーの準備
+    /// 指定されたヘッダーか、指定された暗号の新しいヘッダーに認証コードを付けて返します。
+    fn new_header(&self) -> Header {
+        self.header
+            .unwrap_or_else(|| Header::for_cipher_with(self.cipher, self.random, self.clock))
+            .authenticate(self.key)
+    }
+
+    /// # 台帳に記録したヘッダーの準備
+    /// 台帳を指定した場合は、ナンスを台帳に記録してからヘッダーを返します。
+    /// 生成したナンスが記録済みの場合は`NONCE_ATTEMPTS`回まで作り直します。
+    fn recorded_header(&self) -> Result<Header> {
+        let ledger = match self.ledger {
+            Some(ledger) => ledger,
+            None => return Ok(self.new_header()),
+        };
+        let attempts = match self.header {
+            Some(_) => 1,
+            None => NONCE_ATTEMPTS,
+        };
+        for _ in 0..attempts {
+            let header = self.new_header();
+            if ledger.record_nonce(self.key, &header)? {
+                return Ok(header);
+            }
+            debug!("記録済みのナンスなので作り直します。");
+        }
+        Err(Error::NonceReused)
+    }
+
+    /// # 暗号化したサイズの記録
+    /// 台帳を指定した場合は暗号化したサイズを記録して、そのまま返します。
+    fn record_bytes(&self, bytes: u64) -> Result<u64> {
+        if let (Some(ledger), false) = (self.ledger, self.is_deterministic()) {
+            ledger.record_bytes(self.key, bytes)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// # 復号
+/// ```no_run
+/// use crypto_tool::crypto::{Decryptor, Key};
+///
+/// let key = Key::read_from_file("key.bin")?;
+/// let input = std::fs::File::open("data.csv.c20")?;
+/// let output = std::fs::File::create("data.csv")?;
+/// Decryptor::new(&key).decrypt_file(&input, &output)?;
+/// # Ok::<(), crypto_tool::crypto::Error>(())
+/// ```
+pub struct Decryptor<'a> {
+    key: &'a Key,
+    setting: Setting<'a>,
+}
+
+impl<'a> Decryptor<'a> {
+    pub fn new(key: &'a Key) -> Self {
+        Decryptor {
+            key,
+            setting: Setting::default(),
+        }
+    }
+
+    /// # パイプラインの設定
+    /// バッファの数が0の場合はパイプラインを使わずに1スレッドで処理します。
+    /// 認証付き暗号ではバッファのサイズだけを使います。
+    pub fn pipeline(mut self, pipeline: PipelineSetting) -> Self {
+        self.setting.pipeline = pipeline;
+        self
+    }
+
+    /// # 大きなファイルの処理に使うスレッド数
+    /// `decrypt_file`でだけ使います。省略時は1です。
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.setting.threads = threads;
+        self
+    }
+
+    /// # 複数のスレッドで処理するファイルのサイズ(byte)
+    pub fn parallel_threshold(mut self, parallel_threshold: u64) -> Self {
+        self.setting.parallel_threshold = parallel_threshold;
+        self
+    }
+
+    /// # 進捗の通知先
+    pub fn progress(mut self, progress: Progress<'a>) -> Self {
+        self.setting.progress = progress;
+        self
+    }
+
+    /// # ストリームの復号
+    /// 先頭のヘッダーを読み込んでから復号し、復号したサイズ(byte)を返します。
+    pub fn decrypt(&self, mut reader: impl Read + Send, writer: impl Write + Send) -> Result<u64> {
+        let header = Header::read_from(&mut reader)?;
+        header.verify(self.key)?;
+        crypto_stream(
+            self.key,
+            &header,
+            BodyCipher::decryptor,
+            reader,
+            writer,
+            &self.setting,
+        )
+    }
+
+    /// # シークできる復号
+    /// リーダーの現在の位置からヘッダーを読み込み、任意の位置から復号できる`SeekableDecryptor`を返します。
+    /// パイプライン、スレッド数、進捗の設定は使いません。
+    pub fn seekable<R: Read + Seek>(&self, reader: R) -> Result<SeekableDecryptor<R>> {
+        SeekableDecryptor::new(self.key, reader)
+    }
+
+    /// # 範囲を指定した復号
+    /// 平文の`offset`byteの位置から`length`byteを復号してライターに書き込み、復号したサイズ(byte)を返します。
+    /// データが途中で終わった場合はそこまでを復号します。
+    pub fn decrypt_range(
+        &self,
+        reader: impl Read + Seek,
+        mut writer: impl Write,
+        offset: u64,
+        length: u64,
+    ) -> Result<u64> {
+        let mut reader = self.seekable(reader)?;
+        reader.seek(io::SeekFrom::Start(offset))?;
+
+        let mut buffer = vec![0; self.setting.pipeline.buffer_size.max(1)];
+        let mut bytes = 0;
+        while bytes < length {
+            let size = (length - bytes).min(buffer.len() as u64) as usize;
+            let count = match reader.read(&mut buffer[..size]) {
+                Ok(0) => break,
+                Ok(count) => count,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            writer.write_all(&buffer[..count])?;
+            (self.setting.progress)(count as u64);
+            bytes += count as u64;
+        }
+        writer.flush()?;
+        Ok(bytes)
+    }
+
+    /// # ファイルの復号
+    /// 入力ファイルを復号して書き出し先のファイルに書き込み、復号したサイズ(byte)を返します。
+    /// `threads`が2以上の場合は、`parallel_threshold`以上のファイルを複数のスレッドで処理します。
+    pub fn decrypt_file(&self, input_file: &File, output_file: &File) -> Result<u64> {
+        let input_file_size = input_file.metadata()?.len();
+        let mut reader = io::BufReader::new(input_file);
+        let header = Header::read_from(&mut reader)?;
+        header.verify(self.key)?;
+        let length = input_file_size.saturating_sub(header.size() as u64);
+
+        if self.setting.is_parallel(length) {
+            // ヘッダーの分だけ読み込む位置をずらす
+            return crypto_file_parallel(
+                self.key,
+                &header,
+                Direction::Open,
+                input_file,
+                header.size() as u64,
+                output_file,
+                0,
+                length,
+                &self.setting,
+            );
+        }
+
+        crypto_stream(
+            self.key,
+            &header,
+            BodyCipher::decryptor,
+            reader,
+            io::BufWriter::new(output_file),
+            &self.setting,
+        )
+    }
+}
+
+/// # ストリームの暗号化・復号
+/// ChaCha20でパイプラインのバッファ数が0の場合と、認証付き暗号の場合は1スレッドで処理します。
+/// 認証付き暗号は`body_cipher`で作成した`BodyCipher`で処理します。
+/// `key`はマスター鍵で、ヘッダーのソルトからファイルの鍵を求めます。
+fn crypto_stream(
+    key: &Key,
+    header: &Header,
+    body_cipher: fn(&Key, &Header) -> BodyCipher,
+    reader: impl Read + Send,
+    writer: impl Write + Send,
+    setting: &Setting,
+) -> Result<u64> {
+    let nonce = match header.stream_nonce() {
+        Some(nonce) => nonce,
+        None => {
+            return crypto_body(
+                body_cipher(key, header),
+                reader,
+                writer,
+                setting.pipeline.buffer_size,
+                setting.progress,
+            )
+        }
+    };
+    let key = header.file_key(key);
+    let bytes = if setting.pipeline.depth == 0 {
+        crypto_chacha20(key.as_bytes(), &nonce, reader, writer, setting.progress)?
+    } else {
+        crypto_chacha20_pipeline(
+            key.as_bytes(),
+            &nonce,
+            reader,
+            writer,
+            setting.pipeline,
+            setting.progress,
+        )?
+    };
+    Ok(bytes)
+}
+
+/// # ファイルの並列暗号化・復号
+/// ChaCha20は鍵ストリームの位置で、認証付き暗号はチャンクで領域を分けます。
+/// `key`はマスター鍵で、ヘッダーのソルトからファイルの鍵を求めます。
+#[allow(clippy::too_many_arguments)]
+fn crypto_file_parallel(
+    key: &Key,
+    header: &Header,
+    direction: Direction,
+    input_file: &File,
+    input_offset: u64,
+    output_file: &File,
+    output_offset: u64,
+    length: u64,
+    setting: &Setting,
+) -> Result<u64> {
+    debug!("{}スレッドで処理します。", setting.threads);
+    let nonce = match header.stream_nonce() {
+        Some(nonce) => nonce,
+        None => {
+            return crypto_chunks_parallel(
+                key,
+                header,
+                direction,
+                input_file,
+                input_offset,
+                output_file,
+                output_offset,
+                length,
+                setting.threads,
+                setting.progress,
+            )
+        }
+    };
+    crypto_chacha20_parallel(
+        header.file_key(key).as_bytes(),
+        &nonce,
+        input_file,
+        input_offset,
+        output_file,
+        output_offset,
+        length,
+        setting.threads,
+        setting.progress,
+    )
+    .map_err(Error::Io)
+}
@@ -0,0 +1,414 @@
+//! # 暗号スイート
+//! 暗号化に使う暗号を切り替えるための`CipherSuite`トレイトと、使える暗号の一覧のモジュール
+//! 暗号スイートは鍵・ナンス・認証タグのサイズと、チャンクの暗号化・復号をまとめたものです。
+//! 認証付き暗号(AEAD)はデータをチャンクに分けて、チャンクごとに認証タグを付けます。
+//! チャンクのナンスはヘッダーのナンスの末尾8byteにチャンクの番号をXORしたもので、
+//! ヘッダーと最後のチャンクかどうかも一緒に認証するので、チャンクの入れ替えや切り詰めを検出できます。
+//! ChaCha20は認証タグを付けないので、チャンクに分けても暗号文はつながった鍵ストリームと同じです。
+//! AES-256-GCM-SIVはナンスが重複しても鍵ストリームが漏れない暗号(nonce-misuse resistant)で、
+//! 同じナンスで暗号化した場合に分かるのは同じ位置の同じ平文のチャンクが一致することだけです。
+
+use super::{
+    active_backend, aes_gcm_backend, ChaCha20Cipher, Error, Key, Result, KEY_SIZE, NONCE_SIZE,
+};
+use crate::message::{self, Message};
+use chacha20poly1305::aead::{self, AeadInPlace, NewAead};
+
+/// # 暗号
+/// ヘッダーには`id`を記録します。IDは互換性を保つため変更しないでください。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cipher {
+    /// 認証タグを付けないChaCha20 (0.1.6より前と同じ暗号)
+    #[default]
+    ChaCha20,
+    ChaCha20Poly1305,
+    /// 24byteのナンスを使うChaCha20-Poly1305
+    XChaCha20Poly1305,
+    Aes256Gcm,
+    /// ナンスが重複しても安全なAES-256-GCM-SIV
+    Aes256GcmSiv,
+}
+
+impl Cipher {
+    /// 使える暗号
+    pub const ALL: [Cipher; 5] = [
+        Cipher::ChaCha20,
+        Cipher::ChaCha20Poly1305,
+        Cipher::XChaCha20Poly1305,
+        Cipher::Aes256Gcm,
+        Cipher::Aes256GcmSiv,
+    ];
+    /// 指定できる暗号の名前
+    pub const NAMES: [&'static str; 5] = [
+        "chacha20",
+        "chacha20poly1305",
+        "xchacha20poly1305",
+        "aes256gcm",
+        "aes256gcmsiv",
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cipher::ChaCha20 => "chacha20",
+            Cipher::ChaCha20Poly1305 => "chacha20poly1305",
+            Cipher::XChaCha20Poly1305 => "xchacha20poly1305",
+            Cipher::Aes256Gcm => "aes256gcm",
+            Cipher::Aes256GcmSiv => "aes256gcmsiv",
+        }
+    }
+
+    /// # ヘッダーに記録するID
+    pub fn id(self) -> u8 {
+        match self {
+            Cipher::ChaCha20 => 1,
+            Cipher::ChaCha20Poly1305 => 2,
+            Cipher::XChaCha20Poly1305 => 3,
+            Cipher::Aes256Gcm => 4,
+            Cipher::Aes256GcmSiv => 5,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|cipher| cipher.id() == id)
+    }
+
+    /// # 実装の名前
+    /// ChaCha20は選ばれているバックエンド、AESを使う暗号はAES-NIを使うかどうかを返します。
+    /// 実装を切り替えない暗号は`-`です。
+    pub fn backend(self) -> &'static str {
+        match self {
+            Cipher::ChaCha20 => active_backend().name(),
+            Cipher::Aes256Gcm | Cipher::Aes256GcmSiv => aes_gcm_backend(),
+            Cipher::ChaCha20Poly1305 | Cipher::XChaCha20Poly1305 => "-",
+        }
+    }
+
+    /// # 暗号スイート
+    pub fn suite(self) -> &'static dyn CipherSuite {
+        match self {
+            Cipher::ChaCha20 => &ChaCha20Suite,
+            Cipher::ChaCha20Poly1305 => &AeadSuite::<chacha20poly1305::ChaCha20Poly1305>::SUITE,
+            Cipher::XChaCha20Poly1305 => &AeadSuite::<chacha20poly1305::XChaCha20Poly1305>::SUITE,
+            Cipher::Aes256Gcm => &AeadSuite::<aes_gcm::Aes256Gcm>::SUITE,
+            Cipher::Aes256GcmSiv => &AeadSuite::<aes_gcm_siv::Aes256GcmSiv>::SUITE,
+        }
+    }
+
+    /// # ナンスが重複しても安全な暗号か
+    /// 決定的な暗号化(`Encryptor::deterministic`)はこの暗号でだけ使えます。
+    pub fn is_misuse_resistant(self) -> bool {
+        matches!(self, Cipher::Aes256GcmSiv)
+    }
+}
+
+impl std::str::FromStr for Cipher {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|cipher| cipher.name() == name)
+            .ok_or_else(|| message::format(Message::CipherUnknown, &[&name]))
+    }
+}
+
+/// # 暗号スイート
+/// 暗号を追加する場合はこのトレイトを実装して、`Cipher`に追加します。
+pub trait CipherSuite: Sync {
+    fn cipher(&self) -> Cipher;
+
+    /// 鍵のサイズ(byte)
+    fn key_size(&self) -> usize;
+
+    /// ヘッダーに記録するナンスのサイズ(byte)
+    fn nonce_size(&self) -> usize;
+
+    /// チャンクごとの認証タグのサイズ(byte) 認証しない暗号は0
+    fn tag_size(&self) -> usize;
+
+    /// # チャンクの暗号化・復号の準備
+    /// `nonce`はヘッダーのナンス、`associated_data`はチャンクと一緒に認証するデータ(ヘッダー)です。
+    fn chunk_cipher(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        chunk_size: u32,
+        associated_data: &[u8],
+    ) -> Box<dyn ChunkCipher>;
+
+    /// # 1つのメッセージの暗号化
+    /// チャンクに分けずに、`nonce`と`associated_data`をそのまま使って暗号化し、後ろに認証タグを追加します。
+    /// テストベクタで実装を確かめるために使います。認証しない暗号は鍵ストリームを適用するだけです。
+    fn seal_message(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<()>;
+
+    /// # 1つのメッセージの復号
+    /// `seal_message`で暗号化したメッセージの認証タグを確かめてから復号して、認証タグを取り除きます。
+    fn open_message(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<()>;
+}
+
+/// # チャンクの暗号化・復号
+/// チャンクは番号を指定して、どの順番でも処理できます。
+pub trait ChunkCipher: Send + Sync {
+    /// # チャンクの暗号化
+    /// `index`番目のチャンクの平文を暗号化して、後ろに認証タグを追加します。
+    fn seal(&self, index: u64, last: bool, buffer: &mut Vec<u8>) -> Result<()>;
+
+    /// # チャンクの復号
+    /// 認証タグを確かめてから復号して、認証タグを取り除きます。
+    /// 認証に失敗した場合は`Error::AuthenticationFailed`を返し、`buffer`の内容は使えません。
+    fn open(&self, index: u64, last: bool, buffer: &mut Vec<u8>) -> Result<()>;
+}
+
+/// # ChaCha20
+struct ChaCha20Suite;
+
+impl CipherSuite for ChaCha20Suite {
+    fn cipher(&self) -> Cipher {
+        Cipher::ChaCha20
+    }
+
+    fn key_size(&self) -> usize {
+        KEY_SIZE
+    }
+
+    fn nonce_size(&self) -> usize {
+        NONCE_SIZE
+    }
+
+    fn tag_size(&self) -> usize {
+        0
+    }
+
+    fn chunk_cipher(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        chunk_size: u32,
+        _associated_data: &[u8],
+    ) -> Box<dyn ChunkCipher> {
+        let mut chunk_nonce = [0; NONCE_SIZE];
+        chunk_nonce.copy_from_slice(nonce);
+        Box::new(ChaCha20Chunks {
+            key: key.clone(),
+            nonce: chunk_nonce,
+            chunk_size: chunk_size as u64,
+        })
+    }
+
+    fn seal_message(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        _associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        if nonce.len() != NONCE_SIZE {
+            return Err(Error::EncryptionFailed);
+        }
+        let mut message_nonce = [0; NONCE_SIZE];
+        message_nonce.copy_from_slice(nonce);
+        ChaCha20Cipher::new(key.as_bytes(), &message_nonce).apply_keystream(buffer);
+        Ok(())
+    }
+
+    fn open_message(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        self.seal_message(key, nonce, associated_data, buffer)
+    }
+}
+
+/// # ChaCha20のチャンク
+/// チャンクの位置まで鍵ストリームをシークして適用します。
+struct ChaCha20Chunks {
+    key: Key,
+    nonce: [u8; NONCE_SIZE],
+    chunk_size: u64,
+}
+
+impl ChaCha20Chunks {
+    fn apply_keystream(&self, index: u64, buffer: &mut [u8]) {
+        let mut cipher = ChaCha20Cipher::new(self.key.as_bytes(), &self.nonce);
+        cipher.seek(index * self.chunk_size);
+        cipher.apply_keystream(buffer);
+    }
+}
+
+impl ChunkCipher for ChaCha20Chunks {
+    fn seal(&self, index: u64, _last: bool, buffer: &mut Vec<u8>) -> Result<()> {
+        self.apply_keystream(index, buffer);
+        Ok(())
+    }
+
+    fn open(&self, index: u64, _last: bool, buffer: &mut Vec<u8>) -> Result<()> {
+        self.apply_keystream(index, buffer);
+        Ok(())
+    }
+}
+
+/// # 認証付き暗号
+/// `aead`クレートの暗号をチャンクの暗号化に使います。
+struct AeadSuite<A> {
+    cipher: Cipher,
+    _aead: std::marker::PhantomData<fn() -> A>,
+}
+
+impl AeadSuite<chacha20poly1305::ChaCha20Poly1305> {
+    const SUITE: Self = AeadSuite {
+        cipher: Cipher::ChaCha20Poly1305,
+        _aead: std::marker::PhantomData,
+    };
+}
+
+impl AeadSuite<chacha20poly1305::XChaCha20Poly1305> {
+    const SUITE: Self = AeadSuite {
+        cipher: Cipher::XChaCha20Poly1305,
+        _aead: std::marker::PhantomData,
+    };
+}
+
+impl AeadSuite<aes_gcm::Aes256Gcm> {
+    const SUITE: Self = AeadSuite {
+        cipher: Cipher::Aes256Gcm,
+        _aead: std::marker::PhantomData,
+    };
+}
+
+impl AeadSuite<aes_gcm_siv::Aes256GcmSiv> {
+    const SUITE: Self = AeadSuite {
+        cipher: Cipher::Aes256GcmSiv,
+        _aead: std::marker::PhantomData,
+    };
+}
+
+impl<A> CipherSuite for AeadSuite<A>
+where
+    A: AeadInPlace + NewAead + Send + Sync + 'static,
+{
+    fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    fn key_size(&self) -> usize {
+        <A::KeySize as aead::generic_array::typenum::Unsigned>::USIZE
+    }
+
+    fn nonce_size(&self) -> usize {
+        <A::NonceSize as aead::generic_array::typenum::Unsigned>::USIZE
+    }
+
+    fn tag_size(&self) -> usize {
+        <A::TagSize as aead::generic_array::typenum::Unsigned>::USIZE
+    }
+
+    fn chunk_cipher(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        _chunk_size: u32,
+        associated_data: &[u8],
+    ) -> Box<dyn ChunkCipher> {
+        Box::new(AeadChunks {
+            aead: A::new(aead::Key::<A>::from_slice(key.as_bytes())),
+            nonce: nonce.to_vec(),
+            associated_data: associated_data.to_vec(),
+        })
+    }
+
+    fn seal_message(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        if nonce.len() != self.nonce_size() {
+            return Err(Error::EncryptionFailed);
+        }
+        A::new(aead::Key::<A>::from_slice(key.as_bytes()))
+            .encrypt_in_place(aead::Nonce::<A>::from_slice(nonce), associated_data, buffer)
+            .map_err(|_| Error::EncryptionFailed)
+    }
+
+    fn open_message(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        if nonce.len() != self.nonce_size() {
+            return Err(Error::AuthenticationFailed);
+        }
+        A::new(aead::Key::<A>::from_slice(key.as_bytes()))
+            .decrypt_in_place(aead::Nonce::<A>::from_slice(nonce), associated_data, buffer)
+            .map_err(|_| Error::AuthenticationFailed)
+    }
+}
+
+/// # 認証付き暗号のチャンク
+struct AeadChunks<A> {
+    aead: A,
+    nonce: Vec<u8>,
+    associated_data: Vec<u8>,
+}
+
+impl<A: AeadInPlace> AeadChunks<A> {
+    /// # チャンクのナンス
+    /// ナンスの末尾8byteにチャンクの番号をXORします。
+    fn chunk_nonce(&self, index: u64) -> aead::Nonce<A> {
+        let mut nonce = aead::Nonce::<A>::clone_from_slice(&self.nonce);
+        let start = nonce.len() - 8;
+        for (byte, index_byte) in nonce[start..].iter_mut().zip(index.to_be_bytes()) {
+            *byte ^= index_byte;
+        }
+        nonce
+    }
+
+    /// # チャンクと一緒に認証するデータ
+    /// ヘッダーの後ろに、最後のチャンクかどうかを1byteで追加します。
+    fn associated_data(&self, last: bool) -> Vec<u8> {
+        let mut associated_data = self.associated_data.clone();
+        associated_data.push(last as u8);
+        associated_data
+    }
+}
+
+impl<A: AeadInPlace + Send + Sync> ChunkCipher for AeadChunks<A> {
+    fn seal(&self, index: u64, last: bool, buffer: &mut Vec<u8>) -> Result<()> {
+        self.aead
+            .encrypt_in_place(
+                &self.chunk_nonce(index),
+                &self.associated_data(last),
+                buffer,
+            )
+            .map_err(|_| Error::EncryptionFailed)
+    }
+
+    fn open(&self, index: u64, last: bool, buffer: &mut Vec<u8>) -> Result<()> {
+        self.aead
+            .decrypt_in_place(
+                &self.chunk_nonce(index),
+                &self.associated_data(last),
+                buffer,
+            )
+            .map_err(|_| Error::AuthenticationFailed)
+    }
+}
@@ -0,0 +1,130 @@
+//! # 暗号化するライター
+//! 書き込まれたデータを暗号化して内側のライターに書き込む`CipherWriter`のモジュール
+//! ログや直列化のように少しずつデータを作る場合に使います。
+
+use super::chunk::BodyCipher;
+use super::{Header, Key, Result};
+use log::debug;
+use std::io::{self, Write};
+
+/// 1回の`write`で暗号化するサイズ(byte)の上限
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// # 暗号化するライター
+/// 最初の書き込みでヘッダーを書き込み、`write`のたびに暗号化して内側のライターに書き込みます。
+/// 認証付き暗号ではチャンクがそろうまで書き込みを待ち、最後のチャンクは`finish`で書き込みます。
+/// 使い終わったら`finish`を呼んでください。
+/// `finish`を呼ばずに破棄した場合も最後のチャンクの書き込みとフラッシュは行いますが、エラーは無視されます。
+/// 書き込みに失敗した後は暗号文がずれるので、続けて使わないでください。
+///
+/// ```
+/// use crypto_tool::crypto::{Decryptor, Encryptor, Key};
+/// use std::io::Write;
+///
+/// let key = Key::new([7; 32]);
+/// let mut writer = Encryptor::new(&key).writer(Vec::new());
+/// writer.write_all(b"hello, ")?;
+/// writer.write_all(b"world")?;
+/// let encrypted = writer.finish()?;
+///
+/// let mut decrypted = Vec::new();
+/// Decryptor::new(&key).decrypt(&encrypted[..], &mut decrypted)?;
+/// assert_eq!(decrypted, b"hello, world");
+/// # Ok::<(), crypto_tool::crypto::Error>(())
+/// ```
+pub struct CipherWriter<W: Write> {
+    cipher: BodyCipher,
+    header: Header,
+    header_written: bool,
+    buffer: Vec<u8>,
+    // `finish`で取り出した後は`None`
+    writer: Option<W>,
+}
+
+impl<W: Write> CipherWriter<W> {
+    /// # 新しいナンスで作成
+    pub fn new(key: &Key, writer: W) -> Self {
+        Self::with_header(key, Header::generate(), writer)
+    }
+
+    /// # ヘッダーを指定して作成
+    /// ヘッダーには`key`で認証コードを付けて書き込みます。
+    pub fn with_header(key: &Key, header: Header, writer: W) -> Self {
+        let header = header.authenticate(key);
+        CipherWriter {
+            cipher: BodyCipher::encryptor(key, &header),
+            header,
+            header_written: false,
+            buffer: Vec::new(),
+            writer: Some(writer),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().unwrap()
+    }
+
+    /// # 書き込みの終了
+    /// 何も書き込んでいない場合もヘッダーを書き込み、最後のチャンクを書き込んでフラッシュしてから内側のライターを返します。
+    /// 失敗した場合も、破棄する時に最後のチャンクを書き込み直しません。
+    pub fn finish(mut self) -> Result<W> {
+        let result = self.write_last_chunk();
+        let writer = self.writer.take().unwrap();
+        result.map(|()| writer)
+    }
+
+    /// # 最後のチャンクの書き込み
+    fn write_last_chunk(&mut self) -> Result<()> {
+        self.write_header()?;
+        self.buffer.clear();
+        self.cipher.finish(&mut self.buffer)?;
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(&self.buffer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// # ヘッダーの書き込み
+    /// まだ書き込んでいない場合だけ書き込みます。
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            let writer = self.writer.as_mut().unwrap();
+            writer.write_all(&self.header.to_bytes())?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for CipherWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_header()?;
+
+        let size = buf.len().min(WRITE_BUFFER_SIZE);
+        self.buffer.clear();
+        self.cipher.update(&buf[..size], &mut self.buffer)?;
+        self.writer.as_mut().unwrap().write_all(&self.buffer)?;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for CipherWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_none() {
+            return;
+        }
+        debug!("finishを呼ばずにCipherWriterが破棄されました。");
+        if let Err(e) = self.write_last_chunk() {
+            debug!("CipherWriterの書き込みに失敗しました。");
+            debug!("{:?}", e);
+        }
+    }
+}
@@ -3,178 +3,674 @@
 //! ## 処理フローチャート
 //! ![](../../../../document/crypto_mode.drawio.svg)
 
+use super::cli_arg_accepter::{CliArg, DecryptRange, Mode};
 use super::crypto;
+use super::message::{self, Message};
+use super::report::Report;
 use log::debug;
-use rand::Rng;
-use sha3::Digest;
-use std::fs::File;
 use std::io::Write;
-use std::io::{self, Read};
-use std::os::windows::prelude::MetadataExt;
+use std::io::{self, Read, Seek};
 use std::str::FromStr;
 
-enum CryptoMode {
+#[derive(Clone, Copy)]
+pub enum CryptoMode {
     Encrypt,
     Decrypt,
 }
 
+/// 標準入力・標準出力を表すファイルパス
+const STDIO_PATH: &str = "-";
+
+/// # 暗号化・復号の結果
+#[derive(Clone, Copy)]
+pub struct Processed {
+    /// 暗号化・復号したサイズ(byte)
+    pub bytes: u64,
+    /// 暗号化に使った暗号、または復号したファイルのヘッダーの暗号
+    pub cipher: crypto::Cipher,
+}
+
+/// # 暗号化・復号の処理方法の設定
+#[derive(Clone)]
+pub struct ProcessSetting {
+    /// 大きなファイルの処理に使うスレッド数
+    pub threads: usize,
+    /// 読み込み、暗号化、書き込みのパイプラインの設定
+    pub pipeline: crypto::PipelineSetting,
+    /// 復号する範囲 指定された場合は範囲だけを復号する
+    pub range: Option<DecryptRange>,
+    /// 暗号化に使う暗号 復号ではヘッダーの暗号を使う
+    pub cipher: crypto::Cipher,
+    /// 同じ平文を同じ暗号文に暗号化する
+    pub deterministic: bool,
+    /// 使ったナンスを記録する台帳
+    pub ledger: Option<std::sync::Arc<crypto::NonceLedger>>,
+}
+
+impl ProcessSetting {
+    /// # CLI引数から処理方法の設定を作成する
+    /// スレッド数が指定されていない場合は`default_threads`を使います。
+    /// 台帳が指定された場合は台帳を開きます。
+    pub fn from_cli_arg(cli_arg: &CliArg, default_threads: usize) -> io::Result<Self> {
+        let default_pipeline = crypto::PipelineSetting::default();
+        let ledger = match &cli_arg.ledger_path {
+            Some(path) => Some(std::sync::Arc::new(open_ledger(path)?)),
+            None => None,
+        };
+        Ok(ProcessSetting {
+            threads: cli_arg.threads.unwrap_or(default_threads),
+            pipeline: crypto::PipelineSetting {
+                buffer_size: cli_arg.buffer_size.unwrap_or(default_pipeline.buffer_size),
+                depth: cli_arg.pipeline_depth.unwrap_or(default_pipeline.depth),
+            },
+            range: cli_arg.range,
+            cipher: cli_arg.cipher,
+            deterministic: cli_arg.deterministic,
+            ledger,
+        })
+    }
+
+    /// # 決定的な暗号化が使えるかの確認
+    /// ナンスが重複すると安全ではない暗号で決定的な暗号化が指定された場合は、
+    /// 書き出し先のファイルを作る前にエラーにします。
+    pub fn check_deterministic(&self) -> io::Result<()> {
+        if !self.deterministic || self.cipher.is_misuse_resistant() {
+            return Ok(());
+        }
+        debug!("決定的な暗号化に使えない暗号です。");
+        Err(report_crypto_error(
+            crypto::Error::DeterministicUnsupported(self.cipher),
+        ))
+    }
+
+    /// # 設定を反映した`Encryptor`
+    fn encryptor<'a>(
+        &'a self,
+        key: &'a crypto::Key,
+        progress: crypto::Progress<'a>,
+    ) -> crypto::Encryptor<'a> {
+        let encryptor = crypto::Encryptor::new(key)
+            .cipher(self.cipher)
+            .deterministic(self.deterministic);
+        let encryptor = match &self.ledger {
+            Some(ledger) => encryptor.ledger(ledger),
+            None => encryptor,
+        };
+        encryptor
+            .threads(self.threads)
+            .pipeline(self.pipeline)
+            .progress(progress)
+    }
+
+    /// # 鍵の使用量の警告
+    /// 台帳に記録した鍵の使用量が推奨される上限に近づいている場合は警告を表示します。
+    /// 暗号化は終わっているので、台帳を読み込めない場合は警告しません。
+    pub fn warn_key_usage(&self, key: &crypto::Key) {
+        let usage = match self.ledger.as_ref().map(|ledger| ledger.usage(key)) {
+            Some(Ok(usage)) => usage,
+            Some(Err(e)) => {
+                debug!("台帳を読み込めませんでした。");
+                debug!("{:?}", e);
+                return;
+            }
+            None => return,
+        };
+        if usage.near_limit() {
+            debug!("鍵の使用量が上限に近づいています。");
+            eprintln!(
+                "{}",
+                message::format(Message::LedgerNearLimit, &[&usage.files, &usage.bytes])
+            );
+        }
+    }
+
+    /// # 設定を反映した`Decryptor`
+    fn decryptor<'a>(
+        &self,
+        key: &'a crypto::Key,
+        progress: crypto::Progress<'a>,
+    ) -> crypto::Decryptor<'a> {
+        crypto::Decryptor::new(key)
+            .threads(self.threads)
+            .pipeline(self.pipeline)
+            .progress(progress)
+    }
+}
+
 /// # 暗号化・復号モード
-pub fn crypto_mode(
-    input_file_path: Option<String>,
-    key_file_path: Option<String>,
-) -> std::io::Result<()> {
-    // ファイルバッファリーダーを取得する
-    let (mut input_file_reader, input_file_size, input_file_path) = get_reader(input_file_path)?;
+pub fn crypto_mode(cli_arg: CliArg) -> std::io::Result<()> {
+    let setting = ProcessSetting::from_cli_arg(&cli_arg, default_threads())?;
+    setting.check_deterministic()?;
+
+    // 入力ファイルのパスを取得する 標準入力から読み込む場合はNone
+    let input_file_path = parse_input_path(cli_arg.input_file_paths.into_iter().next())?;
+
+    // 暗号化か復号かを決める
+    let crypto_mode = match (&cli_arg.mode, &input_file_path) {
+        (Mode::Encrypt, _) => CryptoMode::Encrypt,
+        (Mode::Decrypt, _) => CryptoMode::Decrypt,
+        (_, Some(input_file_path)) => detect_crypto_mode(input_file_path),
+        (_, None) => {
+            debug!("標準入力を使う場合は暗号化か復号かを判定できません。");
+            eprintln!("{}", message::text(Message::StdinNeedsMode));
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                message::text(Message::StdinModeUnknown),
+            ));
+        }
+    };
+
+    // 範囲を指定する場合は入力をシークするのでファイルが必要
+    if setting.range.is_some() && input_file_path.is_none() {
+        debug!("範囲を指定する場合は標準入力を使えません。");
+        eprintln!("{}", message::text(Message::RangeNeedsFile));
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            message::text(Message::RangeNeedsFile),
+        ));
+    }
 
-    // アウトプットファイルのパスを取得する
-    let (output_file_path, crypto_mode) = prepare_output_file_name(input_file_path);
+    // アウトプットファイルのパスを取得する 標準出力に書き出す場合はNone
+    let output_file_path = match cli_arg.output_file_path {
+        Some(path) if path == STDIO_PATH => None,
+        Some(path) => Some(std::path::PathBuf::from(path)),
+        None => match &input_file_path {
+            Some(input_file_path) => Some(prepare_output_file_name(
+                input_file_path.clone(),
+                crypto_mode,
+            )?),
+            None => None,
+        },
+    };
 
     // 鍵データを読み込む
-    let key = read_key(key_file_path)?;
-
-    // バッファライターを取得する
-    let mut output_file_writer =
-        std::io::BufWriter::new(match std::fs::File::create(output_file_path) {
-            Ok(file) => file,
-            Err(e) => {
-                debug!("書き込み先のファイルを作成出来ませんでした。");
-                debug!("{:?}", e);
-                println!("書き込み先のファイルを作成出来ませんでした。");
-                return Err(e);
+    let key = read_key(cli_arg.key_file_path)?;
+
+    let start_time = std::time::Instant::now();
+    let processed = match (&input_file_path, &output_file_path) {
+        // ファイルからファイルに書き出す場合は並列に処理できる
+        (Some(input_file_path), Some(output_file_path)) => crypto_file(
+            input_file_path,
+            output_file_path,
+            &key,
+            crypto_mode,
+            None,
+            &setting,
+        )?,
+        (Some(input_file_path), None) if setting.range.is_some() => {
+            crypto_range(input_file_path, get_writer(None)?, &key, None, &setting)?
+        }
+        (input_file_path, output_file_path) => {
+            // ファイルバッファリーダーを取得する
+            let (input_file_reader, input_file_size) = get_reader(input_file_path.clone())?;
+
+            // バッファライターを取得する
+            let output_file = output_file_path.as_deref().map(create_file).transpose()?;
+            let output_file_writer = get_writer(output_file.as_ref())?;
+
+            // プログレスバーのセットアップ
+            let progress_bar = prepare_progress_bar(input_file_size);
+
+            // 暗号化
+            let processed = crypto_stream(
+                input_file_reader,
+                output_file_writer,
+                &key,
+                crypto_mode,
+                progress_bar,
+                &setting,
+            )?;
+            if let Some(output_file) = output_file {
+                persist_file(output_file)?;
             }
-        });
+            processed
+        }
+    };
+    Report::new(
+        crypto_mode,
+        input_file_path.as_deref(),
+        output_file_path.as_deref(),
+        processed.bytes,
+        start_time.elapsed(),
+        Some(processed.cipher),
+        &key,
+    )
+    .print(cli_arg.json);
+    if let CryptoMode::Encrypt = crypto_mode {
+        setting.warn_key_usage(&key);
+    }
+
+    // 右クリックメニューから起動した場合はウィンドウがすぐに閉じないように待つ
+    // JSONを出力する場合は他のプログラムから起動されているので待たない
+    if let (Mode::CliCrypto, false) = (&cli_arg.mode, cli_arg.json) {
+        println!("{}", message::text(Message::PressEnterToExit));
+        let mut word = String::new();
+        std::io::stdin().read_line(&mut word).ok();
+    }
+    debug!("アプリ終了");
+    Ok(())
+}
 
-    // ナンスを用意する
-    let nonce = prepare_nonce(&mut input_file_reader, &mut output_file_writer, crypto_mode)?;
+/// # 並列処理に使うスレッド数
+/// CPUのコア数を返します。
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
 
-    // プログレスバーのセットアップ
-    let progress_bar = prepare_progress_bar(input_file_size);
+/// # ファイルの暗号化・復号
+/// 入力ファイルを暗号化・復号して書き出し先のファイルに書き込みます。
+/// プログレスバーが指定されない場合は入力ファイルのサイズでプログレスバーを作成します。
+/// 指定された場合はプログレスバーの長さを入力ファイルのサイズ(復号では復号したサイズ)に設定します。
+/// 大きなファイルは`setting.threads`個のスレッドで処理します。
+/// 暗号化・復号したサイズ(byte)と暗号を返します。
+pub fn crypto_file(
+    input_file_path: &std::path::Path,
+    output_file_path: &std::path::Path,
+    key: &crypto::Key,
+    crypto_mode: CryptoMode,
+    progress_bar: Option<indicatif::ProgressBar>,
+    setting: &ProcessSetting,
+) -> io::Result<Processed> {
+    if let (CryptoMode::Decrypt, Some(_)) = (crypto_mode, setting.range) {
+        let output_file = create_file(output_file_path)?;
+        let processed = crypto_range(
+            input_file_path,
+            io::BufWriter::new(output_file.file()),
+            key,
+            progress_bar,
+            setting,
+        )?;
+        persist_file(output_file)?;
+        return Ok(processed);
+    }
+
+    let (input_file_reader, input_file_size) = open_file(input_file_path)?;
+    let mut input_file = input_file_reader.into_inner();
+    let (cipher, length) = match crypto_mode {
+        CryptoMode::Encrypt => (setting.cipher, input_file_size),
+        CryptoMode::Decrypt => {
+            let header = peek_header(&mut input_file)?;
+            (header.cipher(), header.decrypted_size(input_file_size))
+        }
+    };
+    let progress_bar = match progress_bar {
+        Some(progress_bar) => {
+            progress_bar.set_length(length);
+            progress_bar
+        }
+        None => prepare_progress_bar(Some(length)),
+    };
+    let output_file = create_file(output_file_path)?;
 
     // 暗号化
-    crypto::crypto_chacha20(
-        &key,
-        &nonce,
+    let progress = |bytes| progress_bar.inc(bytes);
+    let result = match crypto_mode {
+        CryptoMode::Encrypt => setting
+            .encryptor(key, &progress)
+            .encrypt_file(&input_file, output_file.file()),
+        CryptoMode::Decrypt => setting
+            .decryptor(key, &progress)
+            .decrypt_file(&input_file, output_file.file()),
+    };
+    progress_bar.finish();
+    let bytes = result.map_err(report_crypto_error)?;
+    persist_file(output_file)?;
+    Ok(Processed { bytes, cipher })
+}
+
+/// # 範囲を指定した復号
+/// 入力ファイルの`setting.range`の範囲だけを復号してライターに書き込みます。
+/// 範囲が指定されていない場合はファイル全体を復号します。
+/// 復号したサイズ(byte)と暗号を返します。
+fn crypto_range(
+    input_file_path: &std::path::Path,
+    output_file_writer: impl Write,
+    key: &crypto::Key,
+    progress_bar: Option<indicatif::ProgressBar>,
+    setting: &ProcessSetting,
+) -> io::Result<Processed> {
+    let (mut input_file_reader, input_file_size) = open_file(input_file_path)?;
+    let header = peek_header(input_file_reader.get_mut())?;
+    let range = setting.range.unwrap_or(DecryptRange {
+        offset: 0,
+        length: u64::MAX,
+    });
+    debug!(
+        "{}byteの位置から{}byteを復号します。",
+        range.offset, range.length
+    );
+
+    let length = range.length.min(
+        header
+            .decrypted_size(input_file_size)
+            .saturating_sub(range.offset),
+    );
+    let progress_bar = match progress_bar {
+        Some(progress_bar) => {
+            progress_bar.set_length(length);
+            progress_bar
+        }
+        None => prepare_progress_bar(Some(length)),
+    };
+
+    let progress = |bytes| progress_bar.inc(bytes);
+    let result = setting.decryptor(key, &progress).decrypt_range(
         input_file_reader,
         output_file_writer,
-        progress_bar,
+        range.offset,
+        range.length,
     );
-    println!("Enterキーを押すと終了します");
-    let mut word = String::new();
-    std::io::stdin().read_line(&mut word).ok();
-    debug!("アプリ終了");
-    Ok(())
+    progress_bar.finish();
+    let bytes = result.map_err(report_crypto_error)?;
+    Ok(Processed {
+        bytes,
+        cipher: header.cipher(),
+    })
 }
 
-/// # ファイルバッファリーダー取得
-/// ファイル名のオプションを受け取って、バッファリーダーとファイルサイズ(byte)、ファイルパスを返します。
-fn get_reader(
-    input_file_path: Option<String>,
-) -> io::Result<(std::io::BufReader<File>, u64, std::path::PathBuf)> {
+/// # ストリームの暗号化・復号
+/// リーダーのデータを暗号化・復号してライターに書き込みます。
+/// 復号では暗号を知るために先にヘッダーを読み込み、読み込んだヘッダーを戻してから復号します。
+fn crypto_stream(
+    mut input_file_reader: impl Read + Send,
+    output_file_writer: impl Write + Send,
+    key: &crypto::Key,
+    crypto_mode: CryptoMode,
+    progress_bar: indicatif::ProgressBar,
+    setting: &ProcessSetting,
+) -> io::Result<Processed> {
+    let progress = |bytes| progress_bar.inc(bytes);
+    let (result, cipher) = match crypto_mode {
+        CryptoMode::Encrypt => (
+            setting
+                .encryptor(key, &progress)
+                .encrypt(input_file_reader, output_file_writer),
+            setting.cipher,
+        ),
+        CryptoMode::Decrypt => {
+            let header =
+                crypto::Header::read_from(&mut input_file_reader).map_err(report_crypto_error)?;
+            let input_file_reader = io::Cursor::new(header.to_bytes()).chain(input_file_reader);
+            (
+                setting
+                    .decryptor(key, &progress)
+                    .decrypt(input_file_reader, output_file_writer),
+                header.cipher(),
+            )
+        }
+    };
+    progress_bar.finish();
+    let bytes = result.map_err(report_crypto_error)?;
+    Ok(Processed { bytes, cipher })
+}
+
+/// # 台帳を開く
+fn open_ledger(path: &str) -> io::Result<crypto::NonceLedger> {
+    crypto::NonceLedger::open(path).map_err(|e| {
+        debug!("台帳を開けませんでした。");
+        debug!("{:?}", e);
+        match e {
+            crypto::Error::InvalidLedger(_) => eprintln!("{}", e),
+            _ => eprintln!("{}", message::text(Message::LedgerOpenFailed)),
+        }
+        e.into()
+    })
+}
+
+/// # ヘッダーの先読み
+/// 復号するファイルの暗号と復号したサイズを知るためにヘッダーを読み込み、ファイルの先頭に戻します。
+fn peek_header(input_file: &mut std::fs::File) -> io::Result<crypto::Header> {
+    let header = crypto::Header::read_from(input_file).map_err(report_crypto_error)?;
+    input_file.rewind()?;
+    Ok(header)
+}
+
+/// # 暗号化・復号のエラーの表示
+/// エラーの内容を表示して`io::Error`に変換します。
+fn report_crypto_error(e: crypto::Error) -> io::Error {
+    debug!("暗号化・復号に失敗しました。");
+    debug!("{:?}", e);
+    match e {
+        crypto::Error::TruncatedHeader => {
+            eprintln!("{}", message::text(Message::InputReadFailed))
+        }
+        // 鍵の誤りや改ざん、対応していない形式は内容をそのまま表示する
+        crypto::Error::AuthenticationFailed
+        | crypto::Error::UnsupportedVersion(_)
+        | crypto::Error::UnsupportedCipher(_)
+        | crypto::Error::UnsupportedFlags(_)
+        | crypto::Error::InvalidChunkSize(_)
+        | crypto::Error::DeterministicUnsupported(_)
+        | crypto::Error::NonceReused => eprintln!("{}", e),
+        _ => eprintln!("{}", message::text(Message::WriteFailed)),
+    }
+    e.into()
+}
+
+/// # 入力ファイルパスの取得
+/// ファイル名のオプションを受け取って、ファイルパスを返します。
+/// ファイル名が`-`の場合は標準入力を表す`None`を返します。
+fn parse_input_path(input_file_path: Option<String>) -> io::Result<Option<std::path::PathBuf>> {
     // ファイルパスが引数に入っていることをチェックする
     let input_path = match input_file_path {
         Some(path) => path,
         None => {
             debug!("ファイルパスが一つも入力されていませんでした。");
-            println!("ファイルパスを入力してください");
+            eprintln!("{}", message::text(Message::InputPathMissing));
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
-                "ファイルパスが一つも入力されていません。",
+                message::text(Message::InputPathMissing),
             ));
         }
     };
+    // 標準入力から読み込む
+    if input_path == STDIO_PATH {
+        debug!("標準入力から読み込みます。");
+        return Ok(None);
+    }
     // ファイルパスを特定する
-    let input_path = match std::path::PathBuf::from_str(&input_path) {
-        Ok(p) => p,
+    match std::path::PathBuf::from_str(&input_path) {
+        Ok(p) => Ok(Some(p)),
         Err(e) => {
             debug!("入力されたファイルパスが誤っています。");
             debug!("{:?}", e);
-            println!("入力されたファイルパスが誤っています。");
-            return Err(io::Error::new(
+            eprintln!("{}", message::text(Message::InputPathInvalid));
+            Err(io::Error::new(
                 io::ErrorKind::NotFound,
-                "入力されたファイルパスが誤っています。",
-            ));
+                message::text(Message::InputPathInvalid),
+            ))
         }
-    };
+    }
+}
+
+/// # ファイルバッファリーダー取得
+/// ファイルパスを受け取って、バッファリーダーとファイルサイズ(byte)を返します。
+/// ファイルパスが`None`の場合は標準入力を読み込みます。この場合ファイルサイズは`None`になります。
+fn get_reader(
+    input_file_path: Option<std::path::PathBuf>,
+) -> io::Result<(Box<dyn Read + Send>, Option<u64>)> {
+    match input_file_path {
+        Some(input_file_path) => {
+            let (input_file_reader, input_file_size) = open_file(&input_file_path)?;
+            Ok((Box::new(input_file_reader), Some(input_file_size)))
+        }
+        None => Ok((Box::new(io::BufReader::new(io::stdin())), None)),
+    }
+}
+
+/// # 標準出力に書き出すかの判定
+/// 1つのファイルを暗号化・復号して、結果を標準出力に書き出す場合は`true`を返します。
+/// `-o -`を指定した場合と、`-o`を指定せずに標準入力から読み込む場合です。
+pub fn writes_to_stdout(cli_arg: &CliArg) -> bool {
+    if !matches!(
+        cli_arg.mode,
+        Mode::Encrypt | Mode::Decrypt | Mode::CliCrypto
+    ) || super::batch_mode::is_batch(cli_arg)
+    {
+        return false;
+    }
+    match cli_arg.output_file_path.as_deref() {
+        Some(path) => path == STDIO_PATH,
+        None => matches!(cli_arg.input_file_paths.first(), Some(path) if path == STDIO_PATH),
+    }
+}
+
+/// # ファイルのオープン
+/// ファイルを開いて、バッファリーダーとファイルサイズ(byte)を返します。
+pub fn open_file(input_path: &std::path::Path) -> io::Result<(io::BufReader<std::fs::File>, u64)> {
     // ファイルをオープンする
-    let input_file = match std::fs::File::open(input_path.clone()) {
+    let input_file = match std::fs::File::open(input_path) {
         Ok(f) => f,
         Err(e) => {
             debug!("ファイルにアクセスできませんでした。");
             debug!("{:?}", e);
-            println!("ファイルにアクセスできませんでした。");
+            eprintln!("{}", message::text(Message::InputAccessFailed));
             return Err(e);
         }
     };
     // 読み込むファイルサイズを取得する
     let input_file_size = match input_file.metadata() {
-        Ok(metadata) => metadata.file_size(),
+        Ok(metadata) => metadata.len(),
         Err(e) => {
             debug!("ファイルのメタデータにアクセス出来ませんでした。");
             debug!("{:?}", e);
-            println!("ファイルのメタデータにアクセス出来ませんでした。");
+            eprintln!("{}", message::text(Message::InputMetadataFailed));
             return Err(e);
         }
     };
     // ファイルバッファリーダーを取得する
-    let input_file_reader = std::io::BufReader::new(input_file);
-    Ok((input_file_reader, input_file_size, input_path))
+    Ok((io::BufReader::new(input_file), input_file_size))
+}
+
+/// # バッファライター取得
+/// 書き出し先のファイルのバッファライターを返します。
+/// ファイルが`None`の場合は標準出力に書き出します。
+fn get_writer(output_file: Option<&crypto::OutputFile>) -> io::Result<Box<dyn Write + Send>> {
+    match output_file {
+        Some(output_file) => Ok(Box::new(io::BufWriter::new(
+            output_file.file().try_clone()?,
+        ))),
+        None => {
+            debug!("標準出力に書き出します。");
+            Ok(Box::new(io::BufWriter::new(io::stdout())))
+        }
+    }
+}
+
+/// # 書き出し先ファイルの作成
+/// 書き出し先と同じディレクトリに一時ファイルを作成します。
+fn create_file(output_file_path: &std::path::Path) -> io::Result<crypto::OutputFile> {
+    crypto::OutputFile::create(output_file_path).map_err(|e| {
+        debug!("書き込み先のファイルを作成出来ませんでした。");
+        debug!("{:?}", e);
+        eprintln!("{}", message::text(Message::OutputCreateFailed));
+        e
+    })
+}
+
+/// # 書き出し先ファイルの保存
+/// 一時ファイルを書き出し先の名前に変えます。
+fn persist_file(output_file: crypto::OutputFile) -> io::Result<()> {
+    output_file.persist().map_err(|e| {
+        debug!("一時ファイルを書き出し先の名前に変えられませんでした。");
+        debug!("{:?}", e);
+        eprintln!("{}", message::text(Message::OutputSaveFailed));
+        e
+    })
+}
+
+/// # 暗号化・復号の判定
+/// 拡張子が`.c20`だったら復号、それ以外は暗号化と判定します。
+pub fn detect_crypto_mode(input_file_path: &std::path::Path) -> CryptoMode {
+    match input_file_path.extension() {
+        Some(extension) if extension == "c20" => {
+            debug!("extension: {:?} ==c20", extension);
+            CryptoMode::Decrypt
+        }
+        extension => {
+            debug!("extension: {:?} !=c20", extension);
+            CryptoMode::Encrypt
+        }
+    }
 }
 
 /// # 書き出し先ファイル名の取得
-/// 拡張子が`.c20`だったら拡張子を削除して、
-/// それ以外には`.c20`の拡張子を追加する
-fn prepare_output_file_name(
+/// 暗号化の場合は`.c20`の拡張子を追加して、
+/// 復号の場合は`.c20`の拡張子を削除する
+/// 復号で拡張子が`.c20`ではない場合は書き出し先を決められないのでErrを返す
+pub fn prepare_output_file_name(
     input_file_path: std::path::PathBuf,
-) -> (std::path::PathBuf, CryptoMode) {
+    crypto_mode: CryptoMode,
+) -> io::Result<std::path::PathBuf> {
     let mut output_file_path = input_file_path.clone();
     let extension = input_file_path.extension();
     debug!("input_file: {:?}", output_file_path);
-    let mut crypto_mode = CryptoMode::Encrypt;
-    let output_file_path = match extension {
-        None => output_file_path.with_extension("c20"),
-        Some(extension) => {
-            if extension == "c20" {
-                debug!("extension: {:?} ==c20", extension);
-                crypto_mode = CryptoMode::Decrypt;
-                output_file_path.with_extension("")
-            } else {
-                debug!("extension: {:?} !=c20", extension);
-                let new_extension = format!("{}.c20", extension.to_str().unwrap());
-                debug!("new_extension: {}", new_extension);
-                output_file_path.set_extension(new_extension);
-                output_file_path
-            }
+    let output_file_path = match (crypto_mode, extension) {
+        (CryptoMode::Encrypt, None) => output_file_path.with_extension("c20"),
+        (CryptoMode::Encrypt, Some(extension)) => {
+            let new_extension = format!("{}.c20", extension.to_string_lossy());
+            debug!("new_extension: {}", new_extension);
+            output_file_path.set_extension(new_extension);
+            output_file_path
+        }
+        (CryptoMode::Decrypt, Some(extension)) if extension == "c20" => {
+            output_file_path.with_extension("")
+        }
+        (CryptoMode::Decrypt, _) => {
+            debug!("拡張子が.c20ではないため書き出し先のファイル名を決められませんでした。");
+            eprintln!("{}", message::text(Message::OutputPathRequired));
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                message::text(Message::OutputNameUndetermined),
+            ));
         }
     };
     debug!("output_file_path: {:?}", output_file_path);
-    (output_file_path, crypto_mode)
+    Ok(output_file_path)
 }
 
 /// # プログレスバーのセットアップ
 /// ファイルサイズ(byte)を受け取ってプログレスバーを出力します
-fn prepare_progress_bar(input_file_size: u64) -> indicatif::ProgressBar {
+/// 標準入力のようにサイズが分からない場合は、処理済みのサイズと速度だけを表示します。
+/// プログレスバーは標準エラー出力に表示されるので、標準出力に書き出すデータとは混ざりません。
+fn prepare_progress_bar(input_file_size: Option<u64>) -> indicatif::ProgressBar {
     // プログレスバーのセットアップ
-    let progress_bar = indicatif::ProgressBar::new(input_file_size);
-    progress_bar.set_style(
-        indicatif::ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({eta})"),
-    );
+    let progress_bar = match input_file_size {
+        Some(input_file_size) => {
+            let progress_bar = indicatif::ProgressBar::new(input_file_size);
+            progress_bar.set_style(
+                indicatif::ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({eta})"),
+            );
+            progress_bar
+        }
+        None => {
+            let progress_bar = indicatif::ProgressBar::new_spinner();
+            progress_bar.set_style(
+                indicatif::ProgressStyle::default_spinner()
+                    .template("[{elapsed_precise}] {spinner} {bytes} ({bytes_per_sec})"),
+            );
+            progress_bar
+        }
+    };
     // 1秒に4回プログレスバーを更新すると、少しパフォーマンスに影響出てきそう(2.5GHz 4core)
     progress_bar.set_draw_rate(4);
     progress_bar
 }
 
 /// 鍵データの読み込み
-fn read_key(key_file_path: Option<String>) -> io::Result<[u8; 32]> {
+pub fn read_key(key_file_path: Option<String>) -> io::Result<crypto::Key> {
     // ファイルパスが引数に入っていることをチェックする
     let input_path = match key_file_path {
         Some(path) => path,
         None => {
             debug!("鍵ファイル名が入力されていませんでした。");
-            println!("鍵ファイル名を入力してください");
+            eprintln!("{}", message::text(Message::KeyPathMissing));
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
-                "鍵ファイル名が入力されていませんでした。",
+                message::text(Message::KeyPathMissing),
             ));
         }
     };
@@ -184,10 +680,10 @@ fn read_key(key_file_path: Option<String>) -> io::Result<[u8; 32]> {
         Err(e) => {
             debug!("鍵ファイルパスが誤っています。");
             debug!("{:?}", e);
-            println!("入力された鍵ファイルパスが誤っています。");
+            eprintln!("{}", message::text(Message::KeyPathInvalid));
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
-                "入力された鍵ファイルパスが誤っています。",
+                message::text(Message::KeyPathInvalid),
             ));
         }
     };
@@ -197,27 +693,24 @@ fn read_key(key_file_path: Option<String>) -> io::Result<[u8; 32]> {
         Err(e) => {
             debug!("鍵ファイルにアクセスできませんでした。");
             debug!("{:?}", e);
-            println!("鍵ファイルにアクセスできませんでした。");
+            eprintln!("{}", message::text(Message::KeyAccessFailed));
             return Err(e);
         }
     };
     // 鍵ファイルサイズを取得する 鍵ファイルが32byte以外だった場合はErrを返す
     let file_size = match input_file.metadata() {
-        Ok(metadata) => metadata.file_size(),
+        Ok(metadata) => metadata.len(),
         Err(e) => {
             debug!("鍵ファイルのメタデータを取得出来ませんでした。");
             debug!("{:?}", e);
-            println!("鍵ファイルのメタデータを取得出来ませんでした。");
+            eprintln!("{}", message::text(Message::KeyMetadataFailed));
             return Err(e);
         }
     };
     if file_size != 32 {
         debug!("鍵ファイルが32byteではありませんでした。");
-        println!("鍵ファイルが32byteではありませんでした。");
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "鍵ファイルのサイズが32byte以外のため不正です",
-        ));
+        eprintln!("{}", message::text(Message::KeySizeInvalid));
+        return Err(crypto::Error::InvalidKeyLength(file_size as usize).into());
     };
 
     // 鍵ファイルを読み込む
@@ -229,81 +722,14 @@ fn read_key(key_file_path: Option<String>) -> io::Result<[u8; 32]> {
         Err(e) => {
             debug!("鍵ファイルを読み込めませんでした。");
             debug!("{:?}", e);
-            println!("鍵ファイルを読み込めませんでした");
+            eprintln!("{}", message::text(Message::KeyReadFailed));
             return Err(e);
         }
     };
     if size != 32 {
         debug!("読み取った鍵ファイルのサイズが32byte以外でした");
-        println!("鍵ファイルを読み込めませんでした");
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "読み取った鍵ファイルのサイズが32byte以外でした",
-        ));
-    }
-    Ok(key)
-}
-
-/// # ナンス生成
-/// 現在の日時と乱数で元になる値を生成してsha3_256でハッシュ値を求めて最初の12byteを出力します。
-fn generate_nonce() -> [u8; 12] {
-    let time_stamp_nanos = chrono::Utc::now().timestamp_nanos();
-    let time_stamp: [u8; 8] = time_stamp_nanos.to_be_bytes();
-
-    let mut rng = rand::thread_rng();
-    let random: [u8; 4] = rng.gen();
-    let mut nonce_non_hash = time_stamp.to_vec();
-    nonce_non_hash.extend(random);
-    debug!("nonce_non_hash: {:?}", nonce_non_hash);
-
-    let mut hasher = sha3::Sha3_256::new();
-    let _ = hasher.write(&nonce_non_hash).unwrap();
-    let nonce_hashed = hasher.finalize();
-
-    let mut nonce_return: [u8; 12] = [0; 12];
-    for i in 0..12 {
-        nonce_return[i] = nonce_hashed[i];
+        eprintln!("{}", message::text(Message::KeyReadFailed));
+        return Err(crypto::Error::InvalidKeyLength(size).into());
     }
-    debug!("nonce_hashed: {:?}", nonce_return);
-    nonce_return
-}
-
-/// # ノンスの生成/読み取り/書き込み
-/// - 暗号化モードならノンスを生成してアウトプットファイルの先頭に書き込む。
-/// - 復号モードならインプットファイルからノンスを読み込んで返す。
-fn prepare_nonce(
-    input_file_reader: &mut impl std::io::Read,
-    output_file_writer: &mut impl std::io::Write,
-    crypto_mode: CryptoMode,
-) -> io::Result<[u8; 12]> {
-    let mut nonce = [0; 12];
-    match crypto_mode {
-        CryptoMode::Encrypt => {
-            // nonceの生成
-            nonce = generate_nonce();
-            // nonceをwriterの一番最初に書き込む
-            match output_file_writer.write(&nonce) {
-                Ok(_) => (),
-                Err(e) => {
-                    debug!("書き込み先のファイルにナンスを書き込めませんでした。");
-                    debug!("{:?}", e);
-                    println!("書き込み先のファイルに書き込みが出来ませんでした。");
-                    return Err(e);
-                }
-            }
-        }
-        CryptoMode::Decrypt => {
-            // インプットファイルから先頭12byteを読み込む
-            match input_file_reader.read(&mut nonce) {
-                Ok(_) => (),
-                Err(e) => {
-                    debug!("インプットファイルから先頭12byte(ナンス)を読み込めませんでした");
-                    debug!("{:?}", e);
-                    println!("インプットファイルを読み込めませんでした。");
-                    return Err(e);
-                }
-            }
-        }
-    };
-    Ok(nonce)
+    Ok(crypto::Key::new(key))
 }
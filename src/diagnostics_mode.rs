@@ -0,0 +1,81 @@
+//! # 診断モード
+//! 使用するChaCha20、AES-256-GCMの実装と、実行しているCPUの機能を表示するモジュール
+
+use super::crypto;
+use super::message::{self, Message};
+use super::report;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// # 診断結果
+#[derive(Serialize)]
+struct Diagnostics {
+    operation: &'static str,
+    status: &'static str,
+    active_backend: &'static str,
+    selected_backend: &'static str,
+    detected_backend: &'static str,
+    aes_gcm_backend: &'static str,
+    cpu_features: BTreeMap<&'static str, bool>,
+    arch: &'static str,
+    build_avx2: bool,
+}
+
+/// # 診断モード
+/// `json`が`true`の場合は診断結果をJSONで表示します。
+pub fn diagnostics_mode(json: bool) {
+    if json {
+        report::print_json(&Diagnostics {
+            operation: "diagnostics",
+            status: "success",
+            active_backend: crypto::active_backend().name(),
+            selected_backend: crypto::selected_backend().name(),
+            detected_backend: crypto::detect_backend().name(),
+            aes_gcm_backend: crypto::aes_gcm_backend(),
+            cpu_features: crypto::cpu_features().into_iter().collect(),
+            arch: std::env::consts::ARCH,
+            build_avx2: cfg!(target_feature = "avx2"),
+        });
+        return;
+    }
+
+    println!("{}", message::text(Message::DiagnosticsBackend));
+    let backends = [
+        (Message::DiagnosticsActive, crypto::active_backend()),
+        (Message::DiagnosticsSelected, crypto::selected_backend()),
+        (Message::DiagnosticsDetected, crypto::detect_backend()),
+    ];
+    for (label, backend) in backends.iter() {
+        println!("{}", message::format(*label, &[&backend.name()]));
+    }
+    println!(
+        "{}",
+        message::format(Message::DiagnosticsAesGcm, &[&crypto::aes_gcm_backend()])
+    );
+
+    println!("{}", message::text(Message::DiagnosticsCpuFeatures));
+    for (feature, detected) in crypto::cpu_features() {
+        let detected = if detected {
+            Message::Available
+        } else {
+            Message::Unavailable
+        };
+        println!("  {}: {}", feature, message::text(detected));
+    }
+
+    println!("{}", message::text(Message::DiagnosticsBuild));
+    println!(
+        "{}",
+        message::format(Message::DiagnosticsArch, &[&std::env::consts::ARCH])
+    );
+    // ビルド時にAVX2が有効になっていると、AVX2がないCPUでは起動できない
+    let build_avx2 = if cfg!(target_feature = "avx2") {
+        Message::Enabled
+    } else {
+        Message::Disabled
+    };
+    println!(
+        "{}",
+        message::format(Message::DiagnosticsBuildAvx2, &[&message::text(build_avx2)])
+    );
+}
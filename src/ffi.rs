@@ -0,0 +1,515 @@
+//! # C API
+//! C、C++、C#などから暗号化・復号するための関数のモジュール
+//! `cdylib`としてビルドすると`libcrypto_tool.so`(Windowsでは`crypto_tool.dll`)に含まれます。
+//! ヘッダーファイルは`include/crypto_tool.h`で、`cbindgen`で生成します。
+//! 関数は`CtStatus`を返し、失敗した場合は`ct_last_error_message`でメッセージを取得できます。
+//! `CtStatus`の値と関数の引数は互換性を保つため、変更せずに追加だけにしてください。
+
+use crate::crypto::{self, Decryptor, Encryptor, Header, Key, OutputFile, NONCE_SIZE};
+use crate::message::{self, Lang, Message};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fs::File;
+use std::io::{self, Seek};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+
+/// # 処理結果
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CtStatus {
+    /// 成功
+    Ok = 0,
+    /// 引数がNULL、または誤っている
+    InvalidArgument = 1,
+    /// 鍵が32byteではない
+    InvalidKey = 2,
+    /// ヘッダーの途中でデータが終わっている
+    TruncatedHeader = 3,
+    /// 書き出し先のバッファが足りない
+    BufferTooSmall = 4,
+    /// ファイルが見つからない
+    NotFound = 5,
+    /// ファイルにアクセスする権限がない
+    PermissionDenied = 6,
+    /// その他の読み込み・書き込みのエラー
+    IoError = 7,
+    /// このCPUでは使えない
+    Unsupported = 8,
+    /// ライブラリの内部で異常終了した
+    Panic = 9,
+    /// 認証タグが一致しない (鍵が違うか、暗号文が改ざん・切り詰められている)
+    AuthenticationFailed = 10,
+    /// 対応していない形式のバージョン・暗号のファイル
+    UnsupportedFormat = 11,
+}
+
+/// # 鍵のハンドル
+/// `ct_key_generate`などで作成して、`ct_key_free`で解放します。
+pub struct CtKey(Key);
+
+/// # 進捗の通知
+/// 処理したサイズ(byte)と全体のサイズ(byte)、`user_data`を受け取ります。
+/// 呼び出したスレッドとは別のスレッドから呼ばれることがありますが、同時に呼ばれることはありません。
+pub type CtProgressCallback =
+    Option<unsafe extern "C" fn(processed: u64, total: u64, user_data: *mut c_void)>;
+
+thread_local! {
+    /// 最後に失敗した処理のメッセージ
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// # C APIのエラー
+struct FfiError {
+    status: CtStatus,
+    message: String,
+}
+
+type FfiResult<T> = Result<T, FfiError>;
+
+impl FfiError {
+    fn new(status: CtStatus, message: String) -> Self {
+        FfiError { status, message }
+    }
+
+    fn null_argument(name: &str) -> Self {
+        Self::new(
+            CtStatus::InvalidArgument,
+            message::format(Message::NullArgument, &[&name]),
+        )
+    }
+}
+
+impl From<crypto::Error> for FfiError {
+    fn from(e: crypto::Error) -> Self {
+        let status = match &e {
+            crypto::Error::Io(e) => match e.kind() {
+                io::ErrorKind::NotFound => CtStatus::NotFound,
+                io::ErrorKind::PermissionDenied => CtStatus::PermissionDenied,
+                _ => CtStatus::IoError,
+            },
+            crypto::Error::InvalidKeyLength(_) => CtStatus::InvalidKey,
+            crypto::Error::TruncatedHeader => CtStatus::TruncatedHeader,
+            crypto::Error::UnsupportedVersion(_)
+            | crypto::Error::UnsupportedCipher(_)
+            | crypto::Error::UnsupportedFlags(_)
+            | crypto::Error::InvalidChunkSize(_) => CtStatus::UnsupportedFormat,
+            crypto::Error::AuthenticationFailed => CtStatus::AuthenticationFailed,
+            crypto::Error::EncryptionFailed
+            | crypto::Error::DeterministicUnsupported(_)
+            | crypto::Error::NonceReused
+            | crypto::Error::InvalidLedger(_) => CtStatus::InvalidArgument,
+            crypto::Error::UnsupportedBackend { .. } => CtStatus::Unsupported,
+            crypto::Error::ThreadPanicked => CtStatus::Panic,
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl From<io::Error> for FfiError {
+    fn from(e: io::Error) -> Self {
+        crypto::Error::Io(e).into()
+    }
+}
+
+/// # 処理の実行
+/// パニックをCの呼び出し元に伝えないように捕まえて、エラーのメッセージを保存します。
+fn run(process: impl FnOnce() -> FfiResult<()>) -> CtStatus {
+    let result = panic::catch_unwind(AssertUnwindSafe(process)).unwrap_or_else(|_| {
+        Err(FfiError::new(
+            CtStatus::Panic,
+            message::text(Message::InternalPanic).to_string(),
+        ))
+    });
+    let (status, message) = match result {
+        Ok(()) => (CtStatus::Ok, None),
+        Err(e) => (e.status, CString::new(e.message.replace('\0', "")).ok()),
+    };
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    status
+}
+
+/// # 文字列の引数の取得
+unsafe fn c_str<'a>(value: *const c_char, name: &str) -> FfiResult<&'a str> {
+    if value.is_null() {
+        return Err(FfiError::null_argument(name));
+    }
+    CStr::from_ptr(value).to_str().map_err(|_| {
+        FfiError::new(
+            CtStatus::InvalidArgument,
+            message::format(Message::InvalidPath, &[&name]),
+        )
+    })
+}
+
+/// # バッファの引数の取得
+/// サイズが0の場合はNULLでも空のバッファとして扱います。
+unsafe fn slice<'a>(data: *const u8, len: usize, name: &str) -> FfiResult<&'a [u8]> {
+    match (data.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(FfiError::null_argument(name)),
+        (false, _) => Ok(std::slice::from_raw_parts(data, len)),
+    }
+}
+
+/// # 書き出し先のバッファの引数の取得
+unsafe fn slice_mut<'a>(data: *mut u8, len: usize, name: &str) -> FfiResult<&'a mut [u8]> {
+    match (data.is_null(), len) {
+        (_, 0) => Ok(&mut []),
+        (true, _) => Err(FfiError::null_argument(name)),
+        (false, _) => Ok(std::slice::from_raw_parts_mut(data, len)),
+    }
+}
+
+/// # 鍵の引数の取得
+unsafe fn key_ref<'a>(key: *const CtKey) -> FfiResult<&'a Key> {
+    key.as_ref()
+        .map(|key| &key.0)
+        .ok_or_else(|| FfiError::null_argument("key"))
+}
+
+/// # 作成した鍵を返す
+unsafe fn put_key(out: *mut *mut CtKey, key: FfiResult<Key>) -> FfiResult<()> {
+    if out.is_null() {
+        return Err(FfiError::null_argument("out"));
+    }
+    // 失敗した場合はNULLを返す
+    *out = ptr::null_mut();
+    let key = key?;
+    *out = Box::into_raw(Box::new(CtKey(key)));
+    Ok(())
+}
+
+/// # ライブラリのバージョン
+#[no_mangle]
+pub extern "C" fn ct_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// # 最後のエラーのメッセージ
+/// 同じスレッドで最後に失敗した関数のメッセージを返します。成功した場合はNULLを返します。
+/// 文字列は同じスレッドで次にC APIの関数を呼ぶまで有効です。
+#[no_mangle]
+pub extern "C" fn ct_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// # メッセージの言語の設定
+/// `"ja"`か`"en"`を指定します。
+///
+/// # Safety
+/// `lang`はNULL終端の文字列を指している必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_set_language(lang: *const c_char) -> CtStatus {
+    run(|| {
+        let lang = c_str(lang, "lang")?
+            .parse::<Lang>()
+            .map_err(|e| FfiError::new(CtStatus::InvalidArgument, e))?;
+        message::set_lang(lang);
+        Ok(())
+    })
+}
+
+/// # 鍵の生成
+/// OSの乱数で新しい鍵を生成して`out`に返します。
+///
+/// # Safety
+/// `out`は書き込める`CtKey *`を指している必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_key_generate(out: *mut *mut CtKey) -> CtStatus {
+    run(|| put_key(out, Ok(Key::generate())))
+}
+
+/// # バイト列から鍵の作成
+/// 32byteのバイト列から鍵を作成して`out`に返します。
+///
+/// # Safety
+/// `bytes`は`len`byte読み込める領域を、`out`は書き込める`CtKey *`を指している必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_key_from_bytes(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut CtKey,
+) -> CtStatus {
+    run(|| {
+        let key = slice(bytes, len, "bytes").and_then(|bytes| Ok(Key::from_slice(bytes)?));
+        put_key(out, key)
+    })
+}
+
+/// # 鍵ファイルの読み込み
+/// 32byteの鍵ファイルを読み込んで`out`に返します。
+///
+/// # Safety
+/// `path`はNULL終端のUTF-8の文字列を、`out`は書き込める`CtKey *`を指している必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_key_from_file(path: *const c_char, out: *mut *mut CtKey) -> CtStatus {
+    run(|| {
+        let key = c_str(path, "path").and_then(|path| Ok(Key::read_from_file(path)?));
+        put_key(out, key)
+    })
+}
+
+/// # 鍵の解放
+/// メモリ上の鍵を0で上書きしてから解放します。NULLの場合は何もしません。
+///
+/// # Safety
+/// `key`は`ct_key_*`で作成してまだ解放していない鍵か、NULLである必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_key_free(key: *mut CtKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+/// # 暗号化したサイズ
+/// `len`byteの平文を`ct_encrypt_buffer`で暗号化したときのサイズ(byte)を返します。
+#[no_mangle]
+pub extern "C" fn ct_encrypted_size(len: usize) -> usize {
+    len.saturating_add(Header::new([0; NONCE_SIZE]).size())
+}
+
+/// # 復号したサイズ
+/// `len`byteの暗号文を復号したときのサイズ(byte)の最大値を返します。
+/// 実際のサイズはヘッダーの形式と暗号によって小さくなります。
+#[no_mangle]
+pub extern "C" fn ct_decrypted_size(len: usize) -> usize {
+    len.saturating_sub(Header::MIN_SIZE)
+}
+
+/// # ファイルの暗号化
+/// `input_path`のファイルを暗号化して`output_path`に書き込みます。
+/// 大きなファイルはCPUのコア数のスレッドで処理します。`progress`はNULLでも構いません。
+/// 失敗した場合は`output_path`にファイルを残さず、既存のファイルも変更しません。
+///
+/// # Safety
+/// `key`は有効な鍵を、パスはNULL終端のUTF-8の文字列を指している必要があります。
+/// `user_data`は`progress`に渡すだけで、別のスレッドから使える必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_encrypt_file(
+    key: *const CtKey,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    progress: CtProgressCallback,
+    user_data: *mut c_void,
+) -> CtStatus {
+    run(|| {
+        crypto_file(
+            key_ref(key)?,
+            c_str(input_path, "input_path")?,
+            c_str(output_path, "output_path")?,
+            ProgressCallback::new(progress, user_data),
+            true,
+        )
+    })
+}
+
+/// # ファイルの復号
+/// `input_path`のファイルを復号して`output_path`に書き込みます。
+/// 大きなファイルはCPUのコア数のスレッドで処理します。`progress`はNULLでも構いません。
+/// 失敗した場合は`output_path`にファイルを残さず、既存のファイルも変更しません。
+///
+/// # Safety
+/// `key`は有効な鍵を、パスはNULL終端のUTF-8の文字列を指している必要があります。
+/// `user_data`は`progress`に渡すだけで、別のスレッドから使える必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_decrypt_file(
+    key: *const CtKey,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    progress: CtProgressCallback,
+    user_data: *mut c_void,
+) -> CtStatus {
+    run(|| {
+        crypto_file(
+            key_ref(key)?,
+            c_str(input_path, "input_path")?,
+            c_str(output_path, "output_path")?,
+            ProgressCallback::new(progress, user_data),
+            false,
+        )
+    })
+}
+
+/// # バッファの暗号化
+/// `input`の平文を暗号化して`output`に書き込み、書き込んだサイズを`output_len`に返します。
+/// `output_capacity`が`ct_encrypted_size(input_len)`より小さい場合は`CT_STATUS_BUFFER_TOO_SMALL`を返し、
+/// 必要なサイズを`output_len`に返します。
+///
+/// # Safety
+/// `input`は`input_len`byte読み込める領域を、`output`は`output_capacity`byte書き込める領域を、
+/// `output_len`は書き込める`size_t`を指している必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_encrypt_buffer(
+    key: *const CtKey,
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> CtStatus {
+    run(|| {
+        let key = key_ref(key)?;
+        let input = slice(input, input_len, "input")?;
+        let output = output_buffer(
+            output,
+            output_capacity,
+            output_len,
+            ct_encrypted_size(input_len),
+        )?;
+        Encryptor::new(key).encrypt(input, output)?;
+        Ok(())
+    })
+}
+
+/// # バッファの復号
+/// `input`の暗号文を復号して`output`に書き込み、書き込んだサイズを`output_len`に返します。
+/// `output_capacity`が復号したサイズより小さい場合は`CT_STATUS_BUFFER_TOO_SMALL`を返し、
+/// 必要なサイズを`output_len`に返します。`ct_decrypted_size(input_len)`あれば足ります。
+///
+/// # Safety
+/// `input`は`input_len`byte読み込める領域を、`output`は`output_capacity`byte書き込める領域を、
+/// `output_len`は書き込める`size_t`を指している必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn ct_decrypt_buffer(
+    key: *const CtKey,
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> CtStatus {
+    run(|| {
+        let key = key_ref(key)?;
+        let input = slice(input, input_len, "input")?;
+        let header = Header::parse(input)?;
+        let output = output_buffer(
+            output,
+            output_capacity,
+            output_len,
+            header.decrypted_size(input_len as u64) as usize,
+        )?;
+        Decryptor::new(key).decrypt(input, output)?;
+        Ok(())
+    })
+}
+
+/// # 書き出し先のバッファの準備
+/// 必要なサイズを`output_len`に書き込み、バッファが足りる場合は必要なサイズのスライスを返します。
+unsafe fn output_buffer<'a>(
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+    required: usize,
+) -> FfiResult<&'a mut [u8]> {
+    if output_len.is_null() {
+        return Err(FfiError::null_argument("output_len"));
+    }
+    *output_len = required;
+    if output_capacity < required {
+        return Err(FfiError::new(
+            CtStatus::BufferTooSmall,
+            message::format(Message::OutputBufferTooSmall, &[&required]),
+        ));
+    }
+    slice_mut(output, required, "output")
+}
+
+/// # ファイルの暗号化・復号
+/// `output_path`と同じディレクトリの一時ファイルに書き込み、成功してから`output_path`に名前を変えます。
+/// 失敗した場合は一時ファイルを削除するので、途中まで書き込んだファイルは残りません。
+fn crypto_file(
+    key: &Key,
+    input_path: &str,
+    output_path: &str,
+    progress: ProgressCallback,
+    encrypt: bool,
+) -> FfiResult<()> {
+    let output_file = OutputFile::create(output_path)?;
+    crypto_file_to(key, input_path, output_file.file(), progress, encrypt)?;
+    output_file.persist()?;
+    Ok(())
+}
+
+/// # ファイルの暗号化・復号の書き込み
+fn crypto_file_to(
+    key: &Key,
+    input_path: &str,
+    output_file: &File,
+    progress: ProgressCallback,
+    encrypt: bool,
+) -> FfiResult<()> {
+    let input_file = File::open(input_path)?;
+    let input_file_size = input_file.metadata()?.len();
+    let threads = std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1);
+
+    let total = match encrypt {
+        true => input_file_size,
+        false => {
+            // 復号したサイズはヘッダーの暗号で決まるので、ヘッダーを読んでから先頭に戻す
+            let header = Header::read_from(&mut &input_file)?;
+            (&input_file).rewind()?;
+            header.decrypted_size(input_file_size)
+        }
+    };
+    let progress = progress.total(total);
+    let report = |bytes| progress.report(bytes);
+    if encrypt {
+        Encryptor::new(key)
+            .threads(threads)
+            .progress(&report)
+            .encrypt_file(&input_file, output_file)?;
+    } else {
+        Decryptor::new(key)
+            .threads(threads)
+            .progress(&report)
+            .decrypt_file(&input_file, output_file)?;
+    }
+    Ok(())
+}
+
+/// # Cの進捗の通知先
+/// 処理したサイズを合計して、コールバックを1つずつ呼び出します。
+struct ProgressCallback {
+    callback: CtProgressCallback,
+    user_data: *mut c_void,
+    total: u64,
+    processed: Mutex<u64>,
+}
+
+// `user_data`は呼び出し元が別のスレッドから使えることを保証し、コールバックはMutexで1つずつ呼ぶ
+unsafe impl Sync for ProgressCallback {}
+
+impl ProgressCallback {
+    fn new(callback: CtProgressCallback, user_data: *mut c_void) -> Self {
+        ProgressCallback {
+            callback,
+            user_data,
+            total: 0,
+            processed: Mutex::new(0),
+        }
+    }
+
+    fn total(self, total: u64) -> Self {
+        ProgressCallback { total, ..self }
+    }
+
+    fn report(&self, bytes: u64) {
+        if let Some(callback) = self.callback {
+            let mut processed = self
+                .processed
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            *processed += bytes;
+            unsafe { callback(*processed, self.total, self.user_data) };
+        }
+    }
+}
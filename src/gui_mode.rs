@@ -1,4 +1,5 @@
 use super::context_menu;
+use super::message::{self, Message};
 use log::debug;
 use native_windows_gui as nwg;
 use std::rc::Rc;
@@ -33,13 +34,13 @@ pub fn gui() -> std::io::Result<()> {
         .unwrap();
 
     nwg::Button::builder()
-        .text("右クリックメニューにCrypto Toolを追加します。")
+        .text(message::text(Message::GuiAddContextMenu))
         .parent(&window)
         .build(&mut button_set_context_menu)
         .unwrap();
 
     nwg::Button::builder()
-        .text("右クリックメニューからCrypto Toolを削除します。")
+        .text(message::text(Message::GuiRemoveContextMenu))
         .parent(&window)
         .build(&mut button_remove_context_menu)
         .unwrap();
@@ -88,29 +89,29 @@ pub fn gui() -> std::io::Result<()> {
                     debug!("右クリックメニューに追加します");
                     let mut file_dialog = Default::default();
                     let _ = nwg::FileDialog::builder()
-                        .title("使用する鍵ファイルを選択してください")
+                        .title(message::text(Message::GuiSelectKeyFile))
                         .action(nwg::FileDialogAction::Open)
                         .multiselect(false)
                         .build(&mut file_dialog);
                     file_dialog.run(Some(&events_window.handle));
                     let file_path = file_dialog.get_selected_item();
-                    match  file_path {
+                    match file_path {
                         Ok(file) => {
                             // コンテクストメニューにセットする
                             if context_menu::set_to_context_menu(file.to_str().unwrap()).is_ok() {
                                 nwg::modal_info_message(
                                     &events_window.handle,
                                     "Digest Tool",
-                                    "右クリックメニューに追加しました。",
+                                    message::text(Message::GuiAdded),
                                 );
                             } else {
                                 nwg::modal_info_message(
                                     &events_window.handle,
                                     "Digest Tool",
-                                    "右クリックメニューに追加できませんでした。",
+                                    message::text(Message::GuiAddFailed),
                                 );
                             }
-                        },
+                        }
                         Err(e) => {
                             debug!("{:?}", e);
                         }
@@ -119,10 +120,9 @@ pub fn gui() -> std::io::Result<()> {
                     // ボタンを有効化する
                     events_button_set_context_menu.set_enabled(true);
                     events_button_remove_context_menu.set_enabled(true);
-                    
                 } else if handle == button_remove_context_menu.handle {
                     debug!("右クリックメニューにから削除します");
-                    
+
                     // ボタンを無効化する
                     events_button_set_context_menu.set_enabled(false);
                     events_button_remove_context_menu.set_enabled(false);
@@ -132,13 +132,13 @@ pub fn gui() -> std::io::Result<()> {
                         nwg::modal_info_message(
                             &events_window.handle,
                             "Digest Tool",
-                            "右クリックメニューから削除しました。",
+                            message::text(Message::GuiRemoved),
                         );
                     } else {
                         nwg::modal_info_message(
                             &events_window.handle,
                             "Digest Tool",
-                            "右クリックメニューから削除できませんでした。",
+                            message::text(Message::GuiRemoveFailed),
                         );
                     }
                     // ボタンを有効化する
@@ -0,0 +1,13 @@
+//! # 暗号化ツールのライブラリ
+//! CryptoToolの暗号化・復号をほかのプログラムから使うためのライブラリです。
+//! CLIとGUIもこのライブラリを使っています。
+//! - `crypto` 鍵、ヘッダー、`Encryptor`、`Decryptor`とエラー
+//! - `message` 日本語と英語のメッセージカタログ
+//! - `ffi` C、C++、C#などから使うためのC API
+//! - `python` Pythonのバインディング (`python`feature)
+
+pub mod crypto;
+pub mod ffi;
+pub mod message;
+#[cfg(feature = "python")]
+mod python;
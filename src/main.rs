@@ -1,21 +1,90 @@
 //! # 暗号化ツール
+mod batch_mode;
+mod bench_mode;
 mod cli_arg_accepter;
+#[cfg(all(windows, feature = "windows-shell"))]
 mod context_menu;
-mod crypto;
 mod crypto_mode;
+mod diagnostics_mode;
+#[cfg(all(windows, feature = "gui"))]
 mod gui_mode;
+mod report;
+mod selftest_mode;
+
+use crypto_tool::{crypto, message};
 
 /// ツールのエントリーポイント
 fn main() {
     env_logger::init();
 
-    let (input_file_path, key_file_path, mode) = cli_arg_accepter::accept_cli_arg();
-    match mode {
-        cli_arg_accepter::Mode::CliCrypto => {
-            let _ = crypto_mode::crypto_mode(input_file_path, key_file_path);
+    let cli_arg = cli_arg_accepter::accept_cli_arg();
+
+    let json = cli_arg.json;
+    let operation = match cli_arg.mode {
+        cli_arg_accepter::Mode::Encrypt => "encrypt",
+        cli_arg_accepter::Mode::Decrypt => "decrypt",
+        cli_arg_accepter::Mode::Diagnostics => "diagnostics",
+        cli_arg_accepter::Mode::Bench => "bench",
+        cli_arg_accepter::Mode::Selftest => "selftest",
+        cli_arg_accepter::Mode::CliCrypto | cli_arg_accepter::Mode::Gui => "crypto",
+    };
+    // 標準出力に書き出す場合は、失敗の結果を標準エラー出力に表示する
+    let stdout_in_use = crypto_mode::writes_to_stdout(&cli_arg);
+
+    // ChaCha20の実装を選択する
+    if let Err(e) = crypto::set_backend(cli_arg.backend) {
+        if !json {
+            eprintln!("{}", e);
         }
-        cli_arg_accepter::Mode::Gui => {
-            let _ = gui_mode::gui();
+        exit_with_error(operation, json, stdout_in_use, &std::io::Error::from(e));
+    }
+
+    let result = match cli_arg.mode {
+        cli_arg_accepter::Mode::Gui => gui(),
+        cli_arg_accepter::Mode::Diagnostics => {
+            diagnostics_mode::diagnostics_mode(json);
+            Ok(())
         }
+        cli_arg_accepter::Mode::Bench => bench_mode::BenchSetting::from_cli_arg(&cli_arg)
+            .map(|setting| bench_mode::bench_mode(setting, json)),
+        cli_arg_accepter::Mode::Selftest => selftest_mode::selftest_mode(json),
+        _ if batch_mode::is_batch(&cli_arg) => batch_mode::batch_mode(cli_arg),
+        _ => crypto_mode::crypto_mode(cli_arg),
+    };
+    if let Err(e) = result {
+        exit_with_error(operation, json, stdout_in_use, &e);
+    }
+}
+
+/// # GUIモード
+#[cfg(all(windows, feature = "gui"))]
+fn gui() -> std::io::Result<()> {
+    let _ = gui_mode::gui();
+    Ok(())
+}
+
+/// # GUIモード
+/// GUIを含まないビルドでは使い方を表示して終了します。
+#[cfg(not(all(windows, feature = "gui")))]
+fn gui() -> std::io::Result<()> {
+    eprintln!("{}", message::text(message::Message::GuiUnavailable));
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        message::text(message::Message::GuiUnavailable),
+    ))
+}
+
+/// # エラー終了
+/// `--json`が指定された場合はエラーの内容をJSONで表示してから終了します。
+/// 一括処理でファイルごとの結果を表示済みの場合は表示しません。
+fn exit_with_error(
+    operation: &'static str,
+    json: bool,
+    stdout_in_use: bool,
+    e: &std::io::Error,
+) -> ! {
+    if json && !batch_mode::is_reported(e) {
+        report::print_failure(operation, e, stdout_in_use);
     }
+    std::process::exit(1);
 }
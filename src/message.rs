@@ -0,0 +1,607 @@
+//! # メッセージカタログ
+//! ユーザーに表示するメッセージを言語ごとにまとめたモジュール
+//! CLIとGUIのどちらもこのカタログからメッセージを取得します。
+//! 言語は`--lang`で指定するか、環境変数`LC_ALL`、`LC_MESSAGES`、`LANG`から選びます。
+//! 環境変数が設定されていない場合は日本語を使います。
+//!
+//! メッセージを追加する場合は`Message`に追加して、`Message::texts`に日本語と英語の文を追加します。
+//! 値を埋め込む場合は文の中に`{}`を書いて`format`で置き換えます。
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// # 表示する言語
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lang {
+    Ja,
+    En,
+}
+
+impl Lang {
+    /// 指定できる言語の名前
+    pub const NAMES: [&'static str; 2] = ["ja", "en"];
+
+    /// # ロケールから言語を選ぶ
+    /// `ja_JP.UTF-8`のようなロケールの名前から言語を選びます。日本語以外は英語にします。
+    pub fn from_locale(locale: &str) -> Self {
+        if locale.starts_with("ja") {
+            Lang::Ja
+        } else {
+            Lang::En
+        }
+    }
+}
+
+impl std::str::FromStr for Lang {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "ja" => Ok(Lang::Ja),
+            "en" => Ok(Lang::En),
+            _ => Err(format!("unknown language: {}", name)),
+        }
+    }
+}
+
+/// 選択されている言語 0: 日本語、1: 英語
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+/// # 言語の選択
+pub fn set_lang(lang: Lang) {
+    let value = match lang {
+        Lang::Ja => 0,
+        Lang::En => 1,
+    };
+    LANG.store(value, Ordering::SeqCst);
+}
+
+/// # 選択されている言語
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::SeqCst) {
+        1 => Lang::En,
+        _ => Lang::Ja,
+    }
+}
+
+/// # 環境変数から言語を選ぶ
+/// `LC_ALL`、`LC_MESSAGES`、`LANG`の順に最初に設定されている値を使います。
+/// どれも設定されていない場合は日本語を返します。
+pub fn detect_lang() -> Lang {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|locale| Lang::from_locale(&locale))
+        .unwrap_or(Lang::Ja)
+}
+
+/// # メッセージ
+#[derive(Clone, Copy, Debug)]
+pub enum Message {
+    // CLI引数
+    HelpInput,
+    HelpKeyFile,
+    HelpOutput,
+    HelpRecursive,
+    HelpInclude,
+    HelpExclude,
+    HelpJobs,
+    HelpThreads,
+    HelpBufferSize,
+    HelpPipelineDepth,
+    HelpRange,
+    HelpCipher,
+    HelpDeterministic,
+    HelpLedger,
+    HelpBackend,
+    HelpJson,
+    HelpLang,
+    HelpBenchSize,
+    HelpBenchSeconds,
+    AboutEncrypt,
+    AboutDecrypt,
+    AboutDiagnostics,
+    AboutBench,
+    AboutSelftest,
+    InvalidSize,
+    InvalidRange,
+    InvalidSeconds,
+    CipherUnknown,
+    BackendUnknown,
+
+    // 暗号化・復号
+    StdinNeedsMode,
+    StdinModeUnknown,
+    RangeNeedsFile,
+    PressEnterToExit,
+    WriteFailed,
+    InputPathMissing,
+    InputPathInvalid,
+    InputAccessFailed,
+    InputMetadataFailed,
+    InputReadFailed,
+    OutputCreateFailed,
+    OutputSaveFailed,
+    OutputPathRequired,
+    OutputNameUndetermined,
+    KeyPathMissing,
+    KeyPathInvalid,
+    KeyAccessFailed,
+    KeyMetadataFailed,
+    KeySizeInvalid,
+    KeyReadFailed,
+    BackendUnsupported,
+    ThreadFailed,
+    KeyLengthInvalid,
+    HeaderTruncated,
+    FormatVersionUnsupported,
+    CipherUnsupported,
+    FlagsUnsupported,
+    ChunkSizeInvalid,
+    AuthenticationFailed,
+    EncryptionFailed,
+    DeterministicUnsupported,
+    NonceReused,
+    LedgerInvalid,
+    LedgerOpenFailed,
+    LedgerNearLimit,
+    SeekOutOfRange,
+    DataTruncated,
+
+    // C API
+    NullArgument,
+    InvalidPath,
+    OutputBufferTooSmall,
+    InternalPanic,
+
+    // Python
+    PythonCryptoError,
+
+    // 一括処理
+    BatchStdin,
+    InvalidPattern,
+    ReadDirFailed,
+    OutputDirCreateFailed,
+    DuplicateOutput,
+    BatchFailed,
+    SummaryResult,
+    SummaryInput,
+    SummaryOutput,
+    SummaryError,
+    SummaryCount,
+    OverallProgress,
+
+    // 処理結果
+    Report,
+    StandardOutput,
+
+    // 診断
+    DiagnosticsBackend,
+    DiagnosticsActive,
+    DiagnosticsSelected,
+    DiagnosticsDetected,
+    DiagnosticsAesGcm,
+    DiagnosticsCpuFeatures,
+    DiagnosticsBuild,
+    DiagnosticsArch,
+    DiagnosticsBuildAvx2,
+    Available,
+    Unavailable,
+    Enabled,
+    Disabled,
+
+    // 自己診断
+    SelftestPassed,
+    SelftestFailed,
+    SelftestFailedCount,
+    SelftestAllPassed,
+
+    // ベンチマーク
+    BenchHeaderCipher,
+    BenchHeaderBackend,
+    BenchHeaderSize,
+    BenchHeaderSpeed,
+    BenchHeaderCyclesPerByte,
+
+    // GUI
+    GuiUnavailable,
+    GuiAddContextMenu,
+    GuiRemoveContextMenu,
+    GuiSelectKeyFile,
+    GuiAdded,
+    GuiAddFailed,
+    GuiRemoved,
+    GuiRemoveFailed,
+    RegistryEditFailed,
+}
+
+impl Message {
+    /// # 日本語と英語の文
+    fn texts(self) -> (&'static str, &'static str) {
+        use Message::*;
+        match self {
+            HelpInput => (
+                "入力ファイルまたはディレクトリ (`-`で標準入力)",
+                "Input files or directories (`-` for standard input)",
+            ),
+            HelpKeyFile => ("鍵ファイル (32byte)", "Key file (32 bytes)"),
+            HelpOutput => (
+                "出力ファイル (`-`で標準出力)、複数のファイルを処理する場合は出力ディレクトリ",
+                "Output file (`-` for standard output), or the output directory when processing multiple files",
+            ),
+            HelpRecursive => (
+                "ディレクトリ内のファイルを再帰的に処理します",
+                "Process files in directories recursively",
+            ),
+            HelpInclude => ("処理するファイルのパターン", "Pattern of files to process"),
+            HelpExclude => ("処理しないファイルのパターン", "Pattern of files to skip"),
+            HelpJobs => (
+                "同時に処理するファイル数 (省略時はCPUのコア数)",
+                "Number of files processed at the same time (default: number of CPU cores)",
+            ),
+            HelpThreads => (
+                "大きなファイルの処理に使うスレッド数",
+                "Number of threads used for a large file",
+            ),
+            HelpBufferSize => (
+                "読み込み・書き込みのバッファのサイズ (例: 4M, 512K)",
+                "Size of the read/write buffers (e.g. 4M, 512K)",
+            ),
+            HelpPipelineDepth => (
+                "同時に使うバッファの数 (0でパイプラインを使わない)",
+                "Number of buffers in flight (0 disables the pipeline)",
+            ),
+            HelpRange => (
+                "復号する範囲 (例: 1G:4M は1GiBの位置から4MiB)",
+                "Range to decrypt (e.g. 1G:4M is 4 MiB from offset 1 GiB)",
+            ),
+            HelpCipher => (
+                "暗号化に使う暗号 (復号ではヘッダーから判定)",
+                "Cipher for encryption (decryption reads it from the header)",
+            ),
+            HelpLedger => (
+                "使ったナンスを記録する台帳のファイル (使用済みのナンスは使いません)",
+                "Ledger file recording used nonces (used nonces are never reused)",
+            ),
+            HelpDeterministic => (
+                "同じ平文を同じ暗号文に暗号化します (aes256gcmsivだけ)",
+                "Encrypt identical plaintexts to identical ciphertexts (aes256gcmsiv only)",
+            ),
+            HelpBackend => (
+                "ChaCha20の実装 (省略時はCPUの機能から自動で選択、softは拡張命令を使わない独自の実装) AVX2、SSE2は自動選択だけで指定できません",
+                "ChaCha20 implementation (default: chosen from the CPU features; soft: this tool's own portable implementation). AVX2 and SSE2 are only chosen automatically",
+            ),
+            HelpJson => ("処理結果をJSONで表示します", "Print results as JSON"),
+            HelpLang => (
+                "表示する言語 (省略時は環境変数LANGから選択)",
+                "Display language (default: chosen from the LANG environment variable)",
+            ),
+            HelpBenchSize => (
+                "計測するバッファのサイズ (省略時は1K, 64K, 1M, 16M)",
+                "Buffer sizes to measure (default: 1K, 64K, 1M, 16M)",
+            ),
+            HelpBenchSeconds => (
+                "1つの組み合わせを計測する時間 (省略時は0.5秒)",
+                "Seconds to measure each combination (default: 0.5)",
+            ),
+            AboutEncrypt => ("ファイルを暗号化します", "Encrypt files"),
+            AboutDecrypt => ("ファイルを復号します", "Decrypt files"),
+            AboutDiagnostics => (
+                "使用するChaCha20の実装とCPUの機能を表示します",
+                "Show the ChaCha20 implementation in use and the CPU features",
+            ),
+            AboutBench => (
+                "メモリ上のバッファを暗号化して処理速度を計測します",
+                "Measure throughput by encrypting in-memory buffers",
+            ),
+            AboutSelftest => (
+                "テストベクタと暗号化・復号の往復で実装が正しく動くかを確認します",
+                "Check the implementation with test vectors and encryption round trips",
+            ),
+            InvalidSize => ("サイズの指定が誤っています: {}", "Invalid size: {}"),
+            InvalidRange => (
+                "範囲の指定が誤っています。offset:lenで指定してください: {}",
+                "Invalid range, expected offset:len: {}",
+            ),
+            InvalidSeconds => (
+                "計測する時間の指定が誤っています。0より大きい秒数を指定してください: {}",
+                "Invalid duration, expected a positive number of seconds: {}",
+            ),
+            CipherUnknown => ("暗号の指定が誤っています: {}", "Unknown cipher: {}"),
+            BackendUnknown => (
+                "バックエンドの指定が誤っています: {}",
+                "Unknown backend: {}",
+            ),
+
+            StdinNeedsMode => (
+                "標準入力を使う場合はencryptかdecryptを指定してください。",
+                "Specify encrypt or decrypt when reading from standard input.",
+            ),
+            StdinModeUnknown => (
+                "標準入力を使う場合は暗号化か復号かを判定できません。",
+                "Cannot tell whether to encrypt or decrypt standard input.",
+            ),
+            RangeNeedsFile => (
+                "範囲を指定する場合は標準入力を使えません。",
+                "Standard input cannot be used with --range.",
+            ),
+            PressEnterToExit => ("Enterキーを押すと終了します", "Press Enter to exit"),
+            WriteFailed => (
+                "暗号化・復号したデータを書き込めませんでした。",
+                "Could not write the encrypted or decrypted data.",
+            ),
+            InputPathMissing => ("ファイルパスを入力してください", "Specify a file path."),
+            InputPathInvalid => (
+                "入力されたファイルパスが誤っています。",
+                "The file path is invalid.",
+            ),
+            InputAccessFailed => (
+                "ファイルにアクセスできませんでした。",
+                "Could not access the file.",
+            ),
+            InputMetadataFailed => (
+                "ファイルのメタデータにアクセス出来ませんでした。",
+                "Could not read the file metadata.",
+            ),
+            InputReadFailed => (
+                "インプットファイルを読み込めませんでした。",
+                "Could not read the input file.",
+            ),
+            OutputCreateFailed => (
+                "書き込み先のファイルを作成出来ませんでした。",
+                "Could not create the output file.",
+            ),
+            OutputSaveFailed => (
+                "書き込み先のファイルを保存出来ませんでした。",
+                "Could not save the output file.",
+            ),
+            OutputPathRequired => (
+                "書き出し先のファイルを-oで指定してください。",
+                "Specify the output file with -o.",
+            ),
+            OutputNameUndetermined => (
+                "書き出し先のファイル名を決められませんでした。",
+                "Could not determine the output file name.",
+            ),
+            KeyPathMissing => ("鍵ファイル名を入力してください", "Specify a key file."),
+            KeyPathInvalid => (
+                "入力された鍵ファイルパスが誤っています。",
+                "The key file path is invalid.",
+            ),
+            KeyAccessFailed => (
+                "鍵ファイルにアクセスできませんでした。",
+                "Could not access the key file.",
+            ),
+            KeyMetadataFailed => (
+                "鍵ファイルのメタデータを取得出来ませんでした。",
+                "Could not read the key file metadata.",
+            ),
+            KeySizeInvalid => (
+                "鍵ファイルが32byteではありませんでした。",
+                "The key file is not 32 bytes long.",
+            ),
+            KeyReadFailed => ("鍵ファイルを読み込めませんでした", "Could not read the key file."),
+            BackendUnsupported => (
+                "バックエンド{}は指定できません。autoかsoftを指定してください。(自動で選ばれるバックエンド: {})",
+                "The {} backend cannot be selected. Use auto or soft. (automatic backend: {})",
+            ),
+            ThreadFailed => (
+                "暗号化・復号のスレッドが異常終了しました。",
+                "An encryption thread terminated abnormally.",
+            ),
+            KeyLengthInvalid => (
+                "鍵が32byteではありません。({}byte)",
+                "The key is not 32 bytes long. ({} bytes)",
+            ),
+            HeaderTruncated => (
+                "ヘッダーを読み込む前にデータが終わりました。",
+                "The data ended before the header.",
+            ),
+            FormatVersionUnsupported => (
+                "対応していない形式のファイルです。(バージョン: {})",
+                "The file format is not supported. (version {})",
+            ),
+            CipherUnsupported => (
+                "対応していない暗号のファイルです。(暗号のID: {})",
+                "The file uses an unsupported cipher. (cipher id {})",
+            ),
+            FlagsUnsupported => (
+                "対応していないフラグのファイルです。(フラグ: {})",
+                "The file has unsupported header flags. (flags {})",
+            ),
+            ChunkSizeInvalid => (
+                "ヘッダーのチャンクのサイズが誤っています。({}byte)",
+                "The chunk size in the header is invalid. ({} bytes)",
+            ),
+            AuthenticationFailed => (
+                "認証に失敗しました。鍵が誤っているか、データが改ざん・破損しています。",
+                "Authentication failed. The key is wrong or the data is tampered with or corrupted.",
+            ),
+            EncryptionFailed => ("暗号化に失敗しました。", "Encryption failed."),
+            NonceReused => (
+                "ヘッダーのナンスは台帳に記録済みです。同じ鍵で同じナンスは使えません。",
+                "The header nonce is already recorded in the ledger. A nonce cannot be reused with the same key.",
+            ),
+            LedgerInvalid => (
+                "台帳の{}行目を読み込めません。",
+                "Cannot parse line {} of the ledger.",
+            ),
+            LedgerOpenFailed => ("台帳を開けませんでした。", "Failed to open the ledger."),
+            LedgerNearLimit => (
+                "警告: この鍵で暗号化した量が推奨される上限に近づいています。({}ファイル、{}byte) 新しい鍵を作成してください。",
+                "Warning: the data encrypted with this key is nearing the recommended limit. ({} files, {} bytes) Please create a new key.",
+            ),
+            DeterministicUnsupported => (
+                "決定的な暗号化にはナンスが重複しても安全な暗号(aes256gcmsiv)を指定してください。(暗号: {})",
+                "Deterministic encryption requires a nonce-misuse-resistant cipher (aes256gcmsiv). (cipher {})",
+            ),
+            SeekOutOfRange => (
+                "シークする位置が範囲外です。",
+                "The seek position is out of range.",
+            ),
+            DataTruncated => (
+                "暗号文が途中で切り詰められています。",
+                "The encrypted data is truncated.",
+            ),
+
+            NullArgument => ("引数がNULLです: {}", "Argument is NULL: {}"),
+            InvalidPath => (
+                "パスがUTF-8の文字列ではありません: {}",
+                "The path is not a UTF-8 string: {}",
+            ),
+            OutputBufferTooSmall => (
+                "書き出し先のバッファが足りません。({}byte必要です)",
+                "The output buffer is too small. ({} bytes needed)",
+            ),
+            InternalPanic => (
+                "ライブラリの内部で異常終了しました。",
+                "The library terminated abnormally.",
+            ),
+
+            PythonCryptoError => ("暗号化・復号のエラー", "Encryption or decryption error"),
+
+            BatchStdin => (
+                "複数のファイルを処理する場合は標準入力を使えません。",
+                "Standard input cannot be used when processing multiple files.",
+            ),
+            InvalidPattern => (
+                "ファイルパターンが誤っています: {}",
+                "Invalid file pattern: {}",
+            ),
+            ReadDirFailed => (
+                "ディレクトリを読み込めませんでした: {}",
+                "Could not read the directory: {}",
+            ),
+            OutputDirCreateFailed => (
+                "書き出し先のディレクトリを作成出来ませんでした。",
+                "Could not create the output directory.",
+            ),
+            DuplicateOutput => (
+                "書き出し先が同じになるファイルがあります: {} と {}",
+                "These files have the same output path: {} and {}",
+            ),
+            BatchFailed => (
+                "{}個のファイルの処理に失敗しました。",
+                "Failed to process {} file(s).",
+            ),
+            SummaryResult => ("結果", "Result"),
+            SummaryInput => ("入力", "Input"),
+            SummaryOutput => ("出力", "Output"),
+            SummaryError => ("エラー", "Error"),
+            SummaryCount => (
+                "成功: {}  スキップ: {}  失敗: {}",
+                "Succeeded: {}  Skipped: {}  Failed: {}",
+            ),
+            OverallProgress => (
+                "[{elapsed_precise}] [{wide_bar}] {pos}/{len} ファイル",
+                "[{elapsed_precise}] [{wide_bar}] {pos}/{len} files",
+            ),
+
+            Report => (
+                "処理サイズ: {} MB ({} byte)\n処理時間: {} 秒\n処理速度: {} MB/s\n暗号: {} ({})\n鍵のフィンガープリント: {}\n出力先: {}",
+                "Processed: {} MB ({} bytes)\nTime: {} s\nThroughput: {} MB/s\nCipher: {} ({})\nKey fingerprint: {}\nOutput: {}",
+            ),
+            StandardOutput => ("標準出力", "standard output"),
+
+            DiagnosticsBackend => ("バックエンド", "Backend"),
+            DiagnosticsActive => ("  使用中: {}", "  active: {}"),
+            DiagnosticsSelected => ("  指定: {}", "  selected: {}"),
+            DiagnosticsDetected => ("  自動選択: {}", "  automatic: {}"),
+            DiagnosticsAesGcm => ("  AES-256-GCM: {}", "  AES-256-GCM: {}"),
+            DiagnosticsCpuFeatures => ("CPUの機能", "CPU features"),
+            DiagnosticsBuild => ("ビルド", "Build"),
+            DiagnosticsArch => ("  アーキテクチャ: {}", "  architecture: {}"),
+            DiagnosticsBuildAvx2 => ("  ビルド時のAVX2: {}", "  AVX2 at build time: {}"),
+            Available => ("あり", "yes"),
+            Unavailable => ("なし", "no"),
+            Enabled => ("有効", "enabled"),
+            Disabled => ("無効", "disabled"),
+
+            SelftestPassed => ("成功: {}", "ok: {}"),
+            SelftestFailed => ("失敗: {}", "FAILED: {}"),
+            SelftestFailedCount => (
+                "{}個の確認に失敗しました。",
+                "{} check(s) failed.",
+            ),
+            SelftestAllPassed => (
+                "すべての確認に成功しました。",
+                "All checks passed.",
+            ),
+
+            BenchHeaderCipher => ("暗号", "cipher"),
+            BenchHeaderBackend => ("実装", "backend"),
+            BenchHeaderSize => ("サイズ", "size"),
+            BenchHeaderSpeed => ("MB/s", "MB/s"),
+            BenchHeaderCyclesPerByte => ("サイクル/byte", "cycles/byte"),
+
+            GuiUnavailable => (
+                "このビルドにはGUIが含まれていません。使い方は--helpで確認してください。",
+                "This build does not include the GUI. See --help for usage.",
+            ),
+            GuiAddContextMenu => (
+                "右クリックメニューにCrypto Toolを追加します。",
+                "Add Crypto Tool to the context menu.",
+            ),
+            GuiRemoveContextMenu => (
+                "右クリックメニューからCrypto Toolを削除します。",
+                "Remove Crypto Tool from the context menu.",
+            ),
+            GuiSelectKeyFile => (
+                "使用する鍵ファイルを選択してください",
+                "Select the key file to use",
+            ),
+            GuiAdded => (
+                "右クリックメニューに追加しました。",
+                "Added to the context menu.",
+            ),
+            GuiAddFailed => (
+                "右クリックメニューに追加できませんでした。",
+                "Could not add to the context menu.",
+            ),
+            GuiRemoved => (
+                "右クリックメニューから削除しました。",
+                "Removed from the context menu.",
+            ),
+            GuiRemoveFailed => (
+                "右クリックメニューから削除できませんでした。",
+                "Could not remove from the context menu.",
+            ),
+            RegistryEditFailed => (
+                "レジストリの編集ができませんでした",
+                "Could not edit the registry.",
+            ),
+        }
+    }
+}
+
+/// # メッセージの取得
+/// 選択されている言語の文を返します。
+pub fn text(message: Message) -> &'static str {
+    let (ja, en) = message.texts();
+    match lang() {
+        Lang::Ja => ja,
+        Lang::En => en,
+    }
+}
+
+/// # 値を埋め込んだメッセージの取得
+/// 文の中の`{}`を順番に`args`の値で置き換えます。
+pub fn format(message: Message, args: &[&dyn Display]) -> String {
+    let mut args = args.iter();
+    let mut parts = text(message).split("{}");
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+/// # 表示幅の計算
+/// 全角文字は半角2文字分の幅として数えます。表を揃えて表示するために使います。
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
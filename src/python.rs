@@ -0,0 +1,310 @@
+//! # Pythonのバインディング
+//! PyO3でPythonから暗号化・復号するための`crypto_tool`モジュール (`python`feature)
+//! ファイルのようなオブジェクト(`read`、`write`を持つオブジェクト)の間で、CLIと同じ形式で暗号化・復号します。
+//!
+//! ```python
+//! import crypto_tool
+//!
+//! key = crypto_tool.Key.from_file("key.bin")
+//! with open("data.csv.c20", "rb") as src, open("data.csv", "wb") as dst:
+//!     crypto_tool.decrypt(key, src, dst)
+//! ```
+
+use crate::crypto::{
+    self, crypto_body, no_progress, BodyCipher, Cipher, Header, Key, PipelineSetting,
+};
+use crate::message::{self, Message};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes};
+use std::io::{self, Read, Write};
+
+// 説明は選択されている言語で、モジュールの初期化時に設定する
+create_exception!(crypto_tool, CryptoError, PyException);
+
+/// # Pythonの例外への変換
+/// 読み込み・書き込みのエラーは`OSError`、鍵の長さの誤りは`ValueError`、それ以外は`CryptoError`にします。
+fn to_py_err(e: crypto::Error) -> PyErr {
+    match e {
+        crypto::Error::Io(e) => e.into(),
+        crypto::Error::InvalidKeyLength(_) => PyValueError::new_err(e.to_string()),
+        e => CryptoError::new_err(e.to_string()),
+    }
+}
+
+/// # 鍵
+#[pyclass(name = "Key", module = "crypto_tool", frozen)]
+struct PyKey {
+    key: Key,
+}
+
+#[pymethods]
+impl PyKey {
+    /// 32byteの`bytes`から鍵を作成します。
+    #[new]
+    fn new(bytes: &[u8]) -> PyResult<Self> {
+        let key = Key::from_slice(bytes).map_err(to_py_err)?;
+        Ok(PyKey { key })
+    }
+
+    /// 32byteの鍵ファイルを読み込みます。
+    #[staticmethod]
+    fn from_file(path: std::path::PathBuf) -> PyResult<Self> {
+        let key = Key::read_from_file(path).map_err(to_py_err)?;
+        Ok(PyKey { key })
+    }
+
+    /// OSの乱数で新しい鍵を生成します。
+    #[staticmethod]
+    fn generate() -> Self {
+        PyKey {
+            key: Key::generate(),
+        }
+    }
+
+    /// 鍵のフィンガープリント(16進数)
+    #[getter]
+    fn fingerprint(&self) -> String {
+        self.key.fingerprint()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Key({})", self.key.fingerprint())
+    }
+}
+
+/// # 暗号の名前の解析
+fn parse_cipher(name: &str) -> PyResult<Cipher> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+/// # ヘッダー
+#[pyclass(name = "Header", module = "crypto_tool", frozen)]
+struct PyHeader {
+    header: Header,
+}
+
+#[pymethods]
+impl PyHeader {
+    /// ヘッダーのサイズの最小値(byte)
+    #[classattr]
+    const MIN_SIZE: usize = Header::MIN_SIZE;
+
+    /// ヘッダーのサイズの最大値(byte)
+    #[classattr]
+    const MAX_SIZE: usize = Header::MAX_SIZE;
+
+    /// データの先頭からヘッダーを読み取ります。
+    #[staticmethod]
+    fn parse(data: &[u8]) -> PyResult<Self> {
+        let header = Header::parse(data).map_err(to_py_err)?;
+        Ok(PyHeader { header })
+    }
+
+    /// 形式のバージョン
+    #[getter]
+    fn version(&self) -> u8 {
+        self.header.version()
+    }
+
+    /// 暗号の名前
+    #[getter]
+    fn cipher(&self) -> &'static str {
+        self.header.cipher().name()
+    }
+
+    /// チャンクのサイズ(byte)
+    #[getter]
+    fn chunk_size(&self) -> u32 {
+        self.header.chunk_size()
+    }
+
+    /// ヘッダーのサイズ(byte)
+    #[getter]
+    fn size(&self) -> usize {
+        self.header.size()
+    }
+
+    /// ソルト (バージョン1の形式では空)
+    #[getter]
+    fn salt<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.header.salt())
+    }
+
+    /// ナンス
+    #[getter]
+    fn nonce<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.header.nonce())
+    }
+
+    fn __repr__(&self) -> String {
+        let nonce: String = self
+            .header
+            .nonce()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!(
+            "Header(cipher={}, nonce={})",
+            self.header.cipher().name(),
+            nonce
+        )
+    }
+}
+
+/// # ファイルのようなオブジェクトのリーダー
+struct PyReader<'py> {
+    file: Bound<'py, PyAny>,
+}
+
+impl Read for PyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.file.call_method1("read", (buf.len(),))?;
+        let size = if let Ok(bytes) = data.cast::<PyBytes>() {
+            copy_to(buf, bytes.as_bytes())?
+        } else if let Ok(bytes) = data.cast::<PyByteArray>() {
+            copy_to(buf, &bytes.to_vec())?
+        } else if data.is_none() {
+            // ノンブロッキングのファイルでデータがない
+            return Err(io::ErrorKind::WouldBlock.into());
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "read() should return bytes",
+            ));
+        };
+        Ok(size)
+    }
+}
+
+/// 読み込んだデータをバッファにコピーする
+fn copy_to(buf: &mut [u8], data: &[u8]) -> io::Result<usize> {
+    if data.len() > buf.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "read() returned more bytes than requested",
+        ));
+    }
+    buf[..data.len()].copy_from_slice(data);
+    Ok(data.len())
+}
+
+/// # ファイルのようなオブジェクトのライター
+struct PyWriter<'py> {
+    file: Bound<'py, PyAny>,
+}
+
+impl Write for PyWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self
+            .file
+            .call_method1("write", (PyBytes::new(self.file.py(), buf),))?;
+        // 書き込んだサイズを返さないオブジェクトはすべて書き込んだとみなす
+        match written.extract::<Option<usize>>()? {
+            Some(size) => Ok(size.min(buf.len())),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.file.hasattr("flush")? {
+            self.file.call_method0("flush")?;
+        }
+        Ok(())
+    }
+}
+
+/// # ストリームの暗号化
+/// `reader`の平文を`cipher`で暗号化して、ヘッダーと暗号文を`writer`に書き込みます。
+/// 暗号化したサイズ(byte)を返します。
+#[pyfunction]
+#[pyo3(signature = (key, reader, writer, cipher = "chacha20"))]
+fn encrypt(
+    key: &PyKey,
+    reader: Bound<'_, PyAny>,
+    writer: Bound<'_, PyAny>,
+    cipher: &str,
+) -> PyResult<u64> {
+    let header = Header::for_cipher(parse_cipher(cipher)?).authenticate(&key.key);
+    let mut writer = PyWriter { file: writer };
+    header.write_to(&mut writer).map_err(to_py_err)?;
+    crypto_body(
+        BodyCipher::encryptor(&key.key, &header),
+        PyReader { file: reader },
+        writer,
+        PipelineSetting::default().buffer_size,
+        &no_progress,
+    )
+    .map_err(to_py_err)
+}
+
+/// # ストリームの復号
+/// `reader`の先頭のヘッダーを読み込んでから復号して、平文を`writer`に書き込みます。
+/// 復号したサイズ(byte)を返します。
+#[pyfunction]
+fn decrypt(key: &PyKey, reader: Bound<'_, PyAny>, writer: Bound<'_, PyAny>) -> PyResult<u64> {
+    let mut reader = PyReader { file: reader };
+    let header = Header::read_from(&mut reader).map_err(to_py_err)?;
+    header.verify(&key.key).map_err(to_py_err)?;
+    crypto_body(
+        BodyCipher::decryptor(&key.key, &header),
+        reader,
+        PyWriter { file: writer },
+        PipelineSetting::default().buffer_size,
+        &no_progress,
+    )
+    .map_err(to_py_err)
+}
+
+/// # ヘッダーの読み込み
+/// `reader`の先頭からヘッダーを読み込みます。読み込んだ分だけ`reader`の位置が進みます。
+#[pyfunction]
+fn read_header(reader: Bound<'_, PyAny>) -> PyResult<PyHeader> {
+    let header = Header::read_from(&mut PyReader { file: reader }).map_err(to_py_err)?;
+    Ok(PyHeader { header })
+}
+
+/// # バイト列の暗号化
+#[pyfunction]
+#[pyo3(signature = (key, data, cipher = "chacha20"))]
+fn encrypt_bytes<'py>(
+    py: Python<'py>,
+    key: &PyKey,
+    data: &[u8],
+    cipher: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let header = Header::for_cipher(parse_cipher(cipher)?);
+    let mut encrypted = Vec::with_capacity(header.encrypted_size(data.len() as u64) as usize);
+    crypto::Encryptor::new(&key.key)
+        .header(header)
+        .encrypt(data, &mut encrypted)
+        .map_err(to_py_err)?;
+    Ok(PyBytes::new(py, &encrypted))
+}
+
+/// # バイト列の復号
+#[pyfunction]
+fn decrypt_bytes<'py>(py: Python<'py>, key: &PyKey, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let mut decrypted = Vec::with_capacity(data.len().saturating_sub(Header::MIN_SIZE));
+    crypto::Decryptor::new(&key.key)
+        .decrypt(data, &mut decrypted)
+        .map_err(to_py_err)?;
+    Ok(PyBytes::new(py, &decrypted))
+}
+
+#[pymodule]
+fn crypto_tool(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    let crypto_error = m.py().get_type::<CryptoError>();
+    crypto_error.setattr("__doc__", message::text(Message::PythonCryptoError))?;
+    m.add("CryptoError", crypto_error)?;
+    m.add_class::<PyKey>()?;
+    m.add_class::<PyHeader>()?;
+    m.add_function(wrap_pyfunction!(encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(read_header, m)?)?;
+    m.add_function(wrap_pyfunction!(encrypt_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt_bytes, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,223 @@
+//! # 処理結果のレポート
+//! 暗号化・復号の後に、処理したサイズ、時間、処理速度、暗号、鍵のフィンガープリント、出力先を表示するモジュール
+//! 通常は人が読める形式で標準エラー出力に表示します。
+//! `--json`を指定した場合はジョブスケジューラなどで集計できるように1行のJSONで表示します。
+//! JSONはどのコマンドでも`operation`と`status`を持ち、失敗した場合は`error`に`code`と`message`を入れます。
+
+use super::crypto;
+use super::crypto_mode::CryptoMode;
+use super::message::{self, Message};
+use log::debug;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// # 処理結果
+#[derive(Serialize)]
+pub struct Report {
+    /// `encrypt`または`decrypt`
+    pub operation: &'static str,
+    /// `success`、`skipped`、`failed`のいずれか
+    pub status: &'static str,
+    /// 入力ファイルのパス 標準入力の場合はNone
+    pub input: Option<String>,
+    /// 出力ファイルのパス 標準出力の場合はNone
+    pub output: Option<String>,
+    /// 処理したサイズ(byte)
+    pub bytes: u64,
+    /// 処理にかかった時間(秒)
+    pub seconds: f64,
+    /// 処理速度(MB/s、1MB = 10^6 byte)
+    pub megabytes_per_second: f64,
+    /// 暗号の名前 復号するファイルのヘッダーを読めなかった場合はNone
+    pub cipher: Option<&'static str>,
+    pub backend: &'static str,
+    pub key_fingerprint: String,
+    /// 失敗した場合のエラー
+    pub error: Option<ErrorReport>,
+}
+
+/// # エラーの内容
+#[derive(Serialize)]
+pub struct ErrorReport {
+    /// 変わらない識別子 (`not_found`など)
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ErrorReport {
+    pub fn new(e: &io::Error) -> Self {
+        ErrorReport {
+            code: error_code(e),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// # コマンドが失敗した場合の結果
+#[derive(Serialize)]
+struct FailureReport {
+    operation: &'static str,
+    status: &'static str,
+    error: ErrorReport,
+}
+
+impl Report {
+    pub fn new(
+        crypto_mode: CryptoMode,
+        input: Option<&Path>,
+        output: Option<&Path>,
+        bytes: u64,
+        elapsed: Duration,
+        cipher: Option<crypto::Cipher>,
+        key: &crypto::Key,
+    ) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        let megabytes = bytes as f64 / 1_000_000.0;
+        Report {
+            operation: match crypto_mode {
+                CryptoMode::Encrypt => "encrypt",
+                CryptoMode::Decrypt => "decrypt",
+            },
+            status: "success",
+            input: input.map(|path| path.display().to_string()),
+            output: output.map(|path| path.display().to_string()),
+            bytes,
+            seconds,
+            megabytes_per_second: if seconds > 0.0 {
+                megabytes / seconds
+            } else {
+                0.0
+            },
+            cipher: cipher.map(crypto::Cipher::name),
+            backend: cipher
+                .map_or_else(|| crypto::active_backend().name(), crypto::Cipher::backend),
+            key_fingerprint: key.fingerprint(),
+            error: None,
+        }
+    }
+
+    /// # 処理しなかったファイルの結果にする
+    pub fn skipped(mut self) -> Self {
+        self.status = "skipped";
+        self
+    }
+
+    /// # 失敗したファイルの結果にする
+    pub fn failed(mut self, e: &io::Error) -> Self {
+        self.status = "failed";
+        self.error = Some(ErrorReport::new(e));
+        self
+    }
+
+    /// # レポートの表示
+    /// 人が読める形式は標準エラー出力に表示します。
+    /// JSONは標準出力に表示しますが、暗号文を標準出力に書き出した場合は標準エラー出力に表示します。
+    pub fn print(&self, json: bool) {
+        if !json {
+            eprintln!("{}", self.to_text());
+        } else if self.output.is_none() {
+            eprintln!("{}", self.to_json());
+        } else {
+            println!("{}", self.to_json());
+        }
+    }
+
+    /// # JSON形式
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// # 人が読める形式
+    pub fn to_text(&self) -> String {
+        message::format(
+            Message::Report,
+            &[
+                &format!("{:.2}", self.bytes as f64 / 1_000_000.0),
+                &self.bytes,
+                &format!("{:.3}", self.seconds),
+                &format!("{:.2}", self.megabytes_per_second),
+                &self.cipher.unwrap_or("-"),
+                &self.backend,
+                &self.key_fingerprint,
+                &self
+                    .output
+                    .as_deref()
+                    .unwrap_or_else(|| message::text(Message::StandardOutput)),
+            ],
+        )
+    }
+}
+
+/// # JSONの表示
+/// 1つの結果を1行のJSONで標準出力に表示します。
+pub fn print_json(value: &impl Serialize) {
+    match serde_json::to_string(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => debug!("JSONに変換出来ませんでした: {:?}", e),
+    }
+}
+
+/// # コマンドの失敗の表示
+/// `--json`が指定された場合にコマンドが失敗したことをJSONで表示します。
+/// `Report::print`と同じく、暗号文・平文を標準出力に書き出す場合は標準エラー出力に表示します。
+pub fn print_failure(operation: &'static str, e: &io::Error, stdout_in_use: bool) {
+    let failure = FailureReport {
+        operation,
+        status: "failed",
+        error: ErrorReport::new(e),
+    };
+    if stdout_in_use {
+        match serde_json::to_string(&failure) {
+            Ok(json) => eprintln!("{}", json),
+            Err(e) => debug!("JSONに変換出来ませんでした: {:?}", e),
+        }
+    } else {
+        print_json(&failure);
+    }
+}
+
+/// # エラーコード
+/// エラーの種類を、メッセージが変わっても変わらない識別子に変換します。
+/// 暗号化・復号のエラー(`crypto::Error`)は、`io::ErrorKind`ではなくエラーごとの識別子にします。
+pub fn error_code(e: &io::Error) -> &'static str {
+    if let Some(e) = e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<crypto::Error>())
+    {
+        return crypto_error_code(e);
+    }
+    match e.kind() {
+        io::ErrorKind::NotFound => "not_found",
+        io::ErrorKind::PermissionDenied => "permission_denied",
+        io::ErrorKind::AlreadyExists => "already_exists",
+        io::ErrorKind::InvalidInput => "invalid_input",
+        io::ErrorKind::InvalidData => "invalid_data",
+        io::ErrorKind::UnexpectedEof => "unexpected_eof",
+        io::ErrorKind::Unsupported => "unsupported",
+        io::ErrorKind::BrokenPipe => "broken_pipe",
+        _ if e.raw_os_error().is_some() => "io_error",
+        _ => "failed",
+    }
+}
+
+/// # 暗号化・復号のエラーコード
+fn crypto_error_code(e: &crypto::Error) -> &'static str {
+    match e {
+        crypto::Error::Io(e) => error_code(e),
+        crypto::Error::InvalidKeyLength(_) => "invalid_key_length",
+        crypto::Error::TruncatedHeader => "truncated_header",
+        crypto::Error::UnsupportedVersion(_) => "unsupported_version",
+        crypto::Error::UnsupportedCipher(_) => "unsupported_cipher",
+        crypto::Error::UnsupportedFlags(_) => "unsupported_flags",
+        crypto::Error::InvalidChunkSize(_) => "invalid_chunk_size",
+        crypto::Error::AuthenticationFailed => "authentication_failed",
+        crypto::Error::EncryptionFailed => "encryption_failed",
+        crypto::Error::DeterministicUnsupported(_) => "deterministic_unsupported",
+        crypto::Error::NonceReused => "nonce_reused",
+        crypto::Error::InvalidLedger(_) => "invalid_ledger",
+        crypto::Error::UnsupportedBackend { .. } => "unsupported_backend",
+        crypto::Error::ThreadPanicked => "thread_panicked",
+    }
+}
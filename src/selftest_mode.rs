@@ -0,0 +1,294 @@
+//! # 自己診断モード
+//! RFC 8439のテストベクタと、すべての暗号と形式のバージョンでの暗号化・復号を実行して、
+//! ビルドした実装が正しく動くかを確認するモジュール
+//! ビルド時の最適化(AVX2など)やCPUの違いで結果が変わっていないかを、本番の環境で使い始める前に確認します。
+//!
+//! ChaCha20のテストベクタは`crypto::crypto_chacha20`と`crypto::crypto_chacha20_pipeline`で、
+//! 使用中のバックエンドと`soft`のそれぞれで確認します。
+//! Poly1305はChaCha20-Poly1305のテストベクタで一緒に確認します。
+//! 1つでも失敗した場合はエラーで終了します。
+
+use super::crypto::{self, Backend, Cipher, Decryptor, Encryptor, Header, Key, RandomSource};
+use super::message::{self, Message};
+use super::report;
+use log::debug;
+use serde::Serialize;
+use std::io;
+
+/// 往復で暗号化・復号するデータのサイズ(byte) 複数のチャンクに分かれるサイズ
+const ROUND_TRIP_SIZE: usize = 3 * crypto::DEFAULT_CHUNK_SIZE as usize + 123;
+
+/// RFC 8439 2.4.2、2.8.2の平文
+const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+/// RFC 8439 A.1 Test Vector #1 (鍵とナンスがすべて0、ブロックカウンタが0の鍵ストリーム)
+const CHACHA20_ZERO_KEYSTREAM: &str = "76b8e0ada0f13d90405d6ae55386bd28bdd219b8a08ded1aa836efcc8b770dc7\
+                                       da41597c5157488d7724e03fb8d84a376a43b8f41518a11cc387b669b2ee6586";
+
+/// RFC 8439 2.4.2 (ブロックカウンタが1の暗号文)
+const CHACHA20_NONCE: &str = "000000000000004a00000000";
+const CHACHA20_CIPHER_TEXT: &str = "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b\
+                                    f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8\
+                                    07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736\
+                                    5af90bbf74a35be6b40b8eedf2785e42874d";
+
+/// RFC 8439 2.8.2
+const AEAD_NONCE: &str = "070000004041424344454647";
+const AEAD_ASSOCIATED_DATA: &str = "50515253c0c1c2c3c4c5c6c7";
+const AEAD_CIPHER_TEXT: &str = "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+                                3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+                                92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+                                3ff4def08e4b7a9de576d26586cec64b6116\
+                                1ae10b594f09e26a7e902ecbd0600691";
+
+/// # 確認の結果
+#[derive(Serialize)]
+struct Check {
+    name: String,
+    passed: bool,
+}
+
+/// # 自己診断の結果
+#[derive(Serialize)]
+struct SelftestReport {
+    operation: &'static str,
+    status: &'static str,
+    checks: Vec<Check>,
+}
+
+/// # 自己診断モード
+/// `json`が`true`の場合は結果をJSONで表示します。
+/// 失敗した確認がある場合はエラーを返します。
+pub fn selftest_mode(json: bool) -> io::Result<()> {
+    let mut checks = Vec::new();
+
+    // ChaCha20はバックエンドを切り替えて確認し、最後に元のバックエンドに戻す
+    {
+        let restore = RestoreBackend(crypto::selected_backend());
+        let mut backends = vec![restore.0, Backend::Soft];
+        backends.dedup();
+        for backend in backends {
+            crypto::set_backend(backend)?;
+            let active = crypto::active_backend();
+            let name = |test: &str| format!("RFC 8439 {} ({})", test, active.name());
+            checks.push(run(name("A.1 ChaCha20"), chacha20_zero_keystream()));
+            checks.push(run(name("2.4.2 ChaCha20"), chacha20_cipher_text(false)));
+            checks.push(run(
+                name("2.4.2 ChaCha20 pipeline"),
+                chacha20_cipher_text(true),
+            ));
+        }
+    }
+    checks.push(run(
+        "RFC 8439 2.8.2 ChaCha20-Poly1305".to_string(),
+        chacha20_poly1305(),
+    ));
+
+    let key = Key::generate();
+    let mut plain_text = vec![0; ROUND_TRIP_SIZE];
+    crypto::OsRandom.fill_bytes(&mut plain_text);
+    for cipher in Cipher::ALL {
+        let encryptor = Encryptor::new(&key).cipher(cipher);
+        checks.push(run(
+            format!("{} v{}", cipher.name(), Header::VERSION),
+            round_trip(&key, &encryptor, &plain_text),
+        ));
+        if cipher.is_misuse_resistant() {
+            checks.push(run(
+                format!("{} v{} deterministic", cipher.name(), Header::VERSION),
+                round_trip(&key, &encryptor.deterministic(true), &plain_text),
+            ));
+        }
+    }
+    let mut nonce = [0; crypto::NONCE_SIZE];
+    crypto::OsRandom.fill_bytes(&mut nonce);
+    let encryptor = Encryptor::new(&key).header(Header::legacy(nonce));
+    checks.push(run(
+        format!("chacha20 v{}", Header::LEGACY_VERSION),
+        round_trip(&key, &encryptor, &plain_text),
+    ));
+
+    let failed_count = checks.iter().filter(|check| !check.passed).count();
+    if json {
+        report::print_json(&SelftestReport {
+            operation: "selftest",
+            status: if failed_count == 0 {
+                "success"
+            } else {
+                "failed"
+            },
+            checks,
+        });
+    } else {
+        for check in &checks {
+            let result = if check.passed {
+                Message::SelftestPassed
+            } else {
+                Message::SelftestFailed
+            };
+            println!("{}", message::format(result, &[&check.name]));
+        }
+    }
+
+    if failed_count > 0 {
+        let error = message::format(Message::SelftestFailedCount, &[&failed_count]);
+        eprintln!("{}", error);
+        return Err(io::Error::other(error));
+    }
+    if !json {
+        println!("{}", message::text(Message::SelftestAllPassed));
+    }
+    Ok(())
+}
+
+/// # 選ばれていたバックエンドに戻す
+/// 確認の途中でエラーになった場合や異常終了した場合も、破棄する時に元のバックエンドに戻します。
+struct RestoreBackend(Backend);
+
+impl Drop for RestoreBackend {
+    fn drop(&mut self) {
+        if let Err(e) = crypto::set_backend(self.0) {
+            debug!("バックエンドを戻せませんでした: {:?}", e);
+        }
+    }
+}
+
+/// # 確認の結果の作成
+/// エラーになった確認は失敗にします。
+fn run(name: String, result: crypto::Result<bool>) -> Check {
+    let passed = match result {
+        Ok(passed) => passed,
+        Err(e) => {
+            debug!("{}: {:?}", name, e);
+            false
+        }
+    };
+    debug!("{}: {}", name, passed);
+    Check { name, passed }
+}
+
+/// # RFC 8439 A.1 Test Vector #1
+/// すべて0の平文を暗号化すると、鍵ストリームがそのまま暗号文になります。
+fn chacha20_zero_keystream() -> crypto::Result<bool> {
+    let expected = parse_hex(CHACHA20_ZERO_KEYSTREAM);
+    let mut cipher_text = Vec::new();
+    crypto::crypto_chacha20(
+        &[0; 32],
+        &[0; 12],
+        &vec![0; expected.len()][..],
+        &mut cipher_text,
+        &|_| {},
+    )?;
+    Ok(cipher_text == expected)
+}
+
+/// # RFC 8439 2.4.2
+/// テストベクタはブロックカウンタが1から始まるので、1ブロック分の0を先頭に付けて暗号化し、その部分を捨てます。
+/// `pipeline`が`true`の場合はブロックの途中で分かれる小さなバッファのパイプラインで暗号化します。
+fn chacha20_cipher_text(pipeline: bool) -> crypto::Result<bool> {
+    let key = sequential_key(0);
+    let mut nonce = [0; 12];
+    nonce.copy_from_slice(&parse_hex(CHACHA20_NONCE));
+    let mut plain_text = vec![0; 64];
+    plain_text.extend_from_slice(SUNSCREEN);
+
+    let mut cipher_text = Vec::new();
+    if pipeline {
+        let setting = crypto::PipelineSetting {
+            buffer_size: 7,
+            depth: 2,
+        };
+        crypto::crypto_chacha20_pipeline(
+            &key,
+            &nonce,
+            &plain_text[..],
+            &mut cipher_text,
+            setting,
+            &|_| {},
+        )?;
+    } else {
+        crypto::crypto_chacha20(&key, &nonce, &plain_text[..], &mut cipher_text, &|_| {})?;
+    }
+    Ok(cipher_text[64..] == parse_hex(CHACHA20_CIPHER_TEXT)[..])
+}
+
+/// # RFC 8439 2.8.2
+/// ファイルの暗号化と同じ`Cipher::ChaCha20Poly1305`の暗号スイートで、
+/// 暗号化した結果が一致することと、復号できること、認証タグを変えると復号できないことを確認します。
+/// チャンクの暗号化(`ChunkCipher`)は、最後のチャンクかどうかを追加データの後ろに付けた
+/// 1つのメッセージの暗号化と一致することを確認します。
+fn chacha20_poly1305() -> crypto::Result<bool> {
+    let suite = Cipher::ChaCha20Poly1305.suite();
+    let key = Key::new(sequential_key(0x80));
+    let nonce = parse_hex(AEAD_NONCE);
+    let associated_data = parse_hex(AEAD_ASSOCIATED_DATA);
+    let expected = parse_hex(AEAD_CIPHER_TEXT);
+
+    let mut cipher_text = SUNSCREEN.to_vec();
+    suite.seal_message(&key, &nonce, &associated_data, &mut cipher_text)?;
+    let mut plain_text = expected.clone();
+    suite.open_message(&key, &nonce, &associated_data, &mut plain_text)?;
+    let mut tampered = expected.clone();
+    if let Some(byte) = tampered.last_mut() {
+        *byte ^= 1;
+    }
+    let rejected = suite
+        .open_message(&key, &nonce, &associated_data, &mut tampered)
+        .is_err();
+
+    let mut chunk = SUNSCREEN.to_vec();
+    suite
+        .chunk_cipher(&key, &nonce, crypto::DEFAULT_CHUNK_SIZE, &associated_data)
+        .seal(0, true, &mut chunk)?;
+    let mut message = SUNSCREEN.to_vec();
+    let mut chunk_associated_data = associated_data.clone();
+    chunk_associated_data.push(1);
+    suite.seal_message(&key, &nonce, &chunk_associated_data, &mut message)?;
+
+    Ok(cipher_text == expected && plain_text == SUNSCREEN && rejected && chunk == message)
+}
+
+/// # 暗号化・復号の往復
+/// 暗号化したデータを復号して元に戻ることと、途中から復号した範囲が一致することを確認します。
+fn round_trip(key: &Key, encryptor: &Encryptor, plain_text: &[u8]) -> crypto::Result<bool> {
+    let mut encrypted = Vec::new();
+    encryptor.encrypt(plain_text, &mut encrypted)?;
+    decrypts_to(key, &encrypted, plain_text)
+}
+
+/// # 復号の確認
+fn decrypts_to(key: &Key, encrypted: &[u8], plain_text: &[u8]) -> crypto::Result<bool> {
+    let decryptor = Decryptor::new(key);
+    let mut decrypted = Vec::new();
+    decryptor.decrypt(encrypted, &mut decrypted)?;
+
+    // チャンクの境界をまたぐ範囲
+    let offset = crypto::DEFAULT_CHUNK_SIZE as usize - 100;
+    let length = 1000;
+    let mut range = Vec::new();
+    decryptor.decrypt_range(
+        io::Cursor::new(encrypted),
+        &mut range,
+        offset as u64,
+        length as u64,
+    )?;
+    Ok(decrypted == plain_text && range[..] == plain_text[offset..offset + length])
+}
+
+/// # `start`から1ずつ増える32byteの鍵
+fn sequential_key(start: u8) -> [u8; 32] {
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = start + i as u8;
+    }
+    key
+}
+
+/// # 16進数の読み込み
+/// テストベクタの定数にだけ使うので、誤りがある場合は異常終了します。
+fn parse_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("テストベクタの16進数"))
+        .collect()
+}
@@ -0,0 +1,169 @@
+//! 非同期のアダプタと同期版の`Encryptor`、`Decryptor`が同じ形式で読み書きできることを確認する
+#![cfg(feature = "async")]
+
+use crypto_tool::crypto::{
+    AsyncDecryptReader, AsyncDecryptWriter, AsyncEncryptReader, AsyncEncryptWriter, Cipher,
+    Decryptor, Encryptor, Header, Key,
+};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn key() -> Key {
+    Key::new([0x42; 32])
+}
+
+fn header() -> Header {
+    Header::new([7; 12])
+}
+
+/// ブロックの境界をまたぐように半端なサイズにする
+fn plain_text() -> Vec<u8> {
+    (0..200_003).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn encrypt_sync(plain_text: &[u8]) -> Vec<u8> {
+    let key = key();
+    let mut encrypted = Vec::new();
+    Encryptor::new(&key)
+        .header(header())
+        .encrypt(plain_text, &mut encrypted)
+        .unwrap();
+    encrypted
+}
+
+fn decrypt_sync(encrypted: &[u8]) -> Vec<u8> {
+    let mut decrypted = Vec::new();
+    Decryptor::new(&key())
+        .decrypt(encrypted, &mut decrypted)
+        .unwrap();
+    decrypted
+}
+
+#[tokio::test]
+async fn encrypt_reader_matches_sync() {
+    let plain_text = plain_text();
+    let mut reader = AsyncEncryptReader::with_header(&key(), header(), &plain_text[..]);
+    let mut encrypted = Vec::new();
+    reader.read_to_end(&mut encrypted).await.unwrap();
+
+    assert_eq!(encrypted, encrypt_sync(&plain_text));
+    assert_eq!(decrypt_sync(&encrypted), plain_text);
+}
+
+#[tokio::test]
+async fn decrypt_reader_reads_sync_output() {
+    let plain_text = plain_text();
+    let encrypted = encrypt_sync(&plain_text);
+    let mut reader = AsyncDecryptReader::new(&key(), &encrypted[..]);
+    let mut decrypted = Vec::new();
+    reader.read_to_end(&mut decrypted).await.unwrap();
+
+    assert_eq!(decrypted, plain_text);
+}
+
+#[tokio::test]
+async fn encrypt_writer_matches_sync() {
+    let plain_text = plain_text();
+    let mut writer = AsyncEncryptWriter::with_header(&key(), header(), Vec::new());
+    // 書き込みを細かく分けても同じ暗号文になる
+    for chunk in plain_text.chunks(1000) {
+        writer.write_all(chunk).await.unwrap();
+    }
+    writer.shutdown().await.unwrap();
+    let encrypted = writer.into_inner();
+
+    assert_eq!(encrypted, encrypt_sync(&plain_text));
+}
+
+#[tokio::test]
+async fn encrypt_writer_writes_header_for_empty_input() {
+    let mut writer = AsyncEncryptWriter::with_header(&key(), header(), Vec::new());
+    writer.shutdown().await.unwrap();
+
+    assert_eq!(writer.into_inner(), encrypt_sync(&[]));
+}
+
+#[tokio::test]
+async fn decrypt_writer_reads_sync_output() {
+    let plain_text = plain_text();
+    let encrypted = encrypt_sync(&plain_text);
+    let mut writer = AsyncDecryptWriter::new(&key(), Vec::new());
+    // ヘッダーの途中で分かれても復号できる
+    for chunk in encrypted.chunks(5) {
+        writer.write_all(chunk).await.unwrap();
+    }
+    writer.shutdown().await.unwrap();
+
+    assert_eq!(writer.into_inner(), plain_text);
+}
+
+#[tokio::test]
+async fn round_trip_through_duplex() {
+    let plain_text = plain_text();
+    let (client, server) = tokio::io::duplex(4096);
+
+    let key = key();
+    let sender = {
+        let plain_text = plain_text.clone();
+        let key = key.clone();
+        tokio::spawn(async move {
+            let mut writer = AsyncEncryptWriter::new(&key, client);
+            writer.write_all(&plain_text).await?;
+            writer.shutdown().await
+        })
+    };
+
+    let mut reader = AsyncDecryptReader::new(&key, server);
+    let mut decrypted = Vec::new();
+    reader.read_to_end(&mut decrypted).await.unwrap();
+    sender.await.unwrap().unwrap();
+
+    assert_eq!(decrypted, plain_text);
+}
+
+#[tokio::test]
+async fn aead_round_trip_matches_sync() {
+    let plain_text = plain_text();
+    for cipher in [Cipher::XChaCha20Poly1305, Cipher::Aes256Gcm] {
+        let header = Header::for_cipher(cipher);
+        let mut writer = AsyncEncryptWriter::with_header(&key(), header, Vec::new());
+        for chunk in plain_text.chunks(1000) {
+            writer.write_all(chunk).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+        let encrypted = writer.into_inner();
+
+        let mut expected = Vec::new();
+        Encryptor::new(&key())
+            .header(header)
+            .encrypt(&plain_text[..], &mut expected)
+            .unwrap();
+        assert_eq!(encrypted, expected);
+
+        // 長さの違うヘッダーも途中で分かれて届いても読み込める
+        let mut writer = AsyncDecryptWriter::new(&key(), Vec::new());
+        for chunk in encrypted.chunks(5) {
+            writer.write_all(chunk).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+        assert_eq!(writer.into_inner(), plain_text);
+
+        let mut reader = AsyncDecryptReader::new(&key(), &encrypted[..]);
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).await.unwrap();
+        assert_eq!(decrypted, plain_text);
+    }
+}
+
+#[tokio::test]
+async fn truncated_header_is_an_error() {
+    let mut reader = AsyncDecryptReader::new(&key(), &[0u8; 5][..]);
+    let mut decrypted = Vec::new();
+    let error = reader.read_to_end(&mut decrypted).await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut writer = AsyncDecryptWriter::new(&key(), Vec::new());
+    writer.write_all(&[0; 5]).await.unwrap();
+    let error = writer.shutdown().await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,27 @@
+//! 指定できるバックエンドが、実際に切り替えられる`auto`と`soft`だけであることを確認する
+
+use crypto_tool::crypto::{self, Backend, Error};
+
+#[test]
+fn only_auto_and_soft_can_be_selected() {
+    assert_eq!(Backend::NAMES, ["auto", "soft"]);
+    for name in Backend::NAMES {
+        let backend = name.parse::<Backend>().unwrap();
+        crypto::set_backend(backend).unwrap();
+        assert_eq!(crypto::selected_backend(), backend);
+    }
+    assert_eq!(crypto::active_backend(), Backend::Soft);
+
+    // 自動選択に任せる実装は指定できず、選択されているバックエンドは変わらない
+    for backend in [Backend::Avx2, Backend::Sse2] {
+        assert!(backend.name().parse::<Backend>().is_err());
+        assert!(matches!(
+            crypto::set_backend(backend),
+            Err(Error::UnsupportedBackend { requested, .. }) if requested == backend
+        ));
+        assert_eq!(crypto::selected_backend(), Backend::Soft);
+    }
+
+    crypto::set_backend(Backend::Auto).unwrap();
+    assert_eq!(crypto::active_backend(), crypto::detect_backend());
+}
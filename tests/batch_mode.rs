@@ -0,0 +1,133 @@
+//! 複数のファイル、ディレクトリをまとめて処理する場合に、処理するファイルを正しく選ぶことを確認する
+
+mod common;
+
+use common::TempDir;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn key_path(dir: &Path) -> PathBuf {
+    let key = dir.join("key.bin");
+    std::fs::write(&key, [0x42; 32]).unwrap();
+    key
+}
+
+fn run(args: &[&std::ffi::OsStr]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crypto_tool"))
+        .args(["--lang", "en"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// JSONの結果のうち、指定した`status`の`input`のファイル名
+fn inputs_with_status(output: &Output, status: &str) -> Vec<String> {
+    let mut names = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|value| value["status"] == status)
+        .map(|value| {
+            let input = value["input"].as_str().unwrap();
+            Path::new(input)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[cfg(unix)]
+#[test]
+fn directory_symlinks_are_not_followed() {
+    let dir = TempDir::new("batch_symlink");
+    let key = key_path(&dir);
+    let data = dir.join("data");
+    std::fs::create_dir_all(data.join("sub")).unwrap();
+    std::fs::write(data.join("a.txt"), b"a").unwrap();
+    std::fs::write(data.join("sub").join("b.txt"), b"b").unwrap();
+    // 親のディレクトリへのリンクでループさせる
+    std::os::unix::fs::symlink(&data, data.join("sub").join("loop")).unwrap();
+
+    let output = run(&[
+        "encrypt".as_ref(),
+        "--json".as_ref(),
+        "-r".as_ref(),
+        "-k".as_ref(),
+        key.as_os_str(),
+        "-o".as_ref(),
+        dir.join("out").as_os_str(),
+        data.as_os_str(),
+    ]);
+    assert!(output.status.success());
+    assert_eq!(inputs_with_status(&output, "success"), ["a.txt", "b.txt"]);
+}
+
+#[test]
+fn encrypted_files_in_directories_are_skipped() {
+    let dir = TempDir::new("batch_skip_c20");
+    let key = key_path(&dir);
+    let data = dir.join("data");
+    std::fs::create_dir(&data).unwrap();
+    std::fs::write(data.join("a.txt"), b"a").unwrap();
+    let encrypt = || {
+        run(&[
+            "encrypt".as_ref(),
+            "--json".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            data.as_os_str(),
+        ])
+    };
+
+    let output = encrypt();
+    assert!(output.status.success());
+    assert_eq!(inputs_with_status(&output, "success"), ["a.txt"]);
+
+    // 書き出した.c20のファイルを暗号化し直さない
+    let output = encrypt();
+    assert!(output.status.success());
+    assert_eq!(inputs_with_status(&output, "success"), ["a.txt"]);
+    assert_eq!(inputs_with_status(&output, "skipped"), ["a.txt.c20"]);
+    assert!(!data.join("a.txt.c20.c20").exists());
+}
+
+#[test]
+fn duplicate_outputs_are_rejected() {
+    let dir = TempDir::new("batch_duplicate");
+    let key = key_path(&dir);
+    for sub in ["a", "b"] {
+        std::fs::create_dir(dir.join(sub)).unwrap();
+        std::fs::write(dir.join(sub).join("x.txt"), sub).unwrap();
+    }
+    let out = dir.join("out");
+
+    // どちらも out/x.txt.c20 に書き出すことになる
+    let output = run(&[
+        "encrypt".as_ref(),
+        "-k".as_ref(),
+        key.as_os_str(),
+        "-o".as_ref(),
+        out.as_os_str(),
+        dir.join("a").join("x.txt").as_os_str(),
+        dir.join("b").join("x.txt").as_os_str(),
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the same output path"), "{}", stderr);
+    assert!(!out.exists());
+
+    // 書き出し先を指定しなければ、それぞれのディレクトリに書き出す
+    let output = run(&[
+        "encrypt".as_ref(),
+        "-k".as_ref(),
+        key.as_os_str(),
+        dir.join("a").join("x.txt").as_os_str(),
+        dir.join("b").join("x.txt").as_os_str(),
+    ]);
+    assert!(output.status.success());
+    assert!(dir.join("a").join("x.txt.c20").exists());
+    assert!(dir.join("b").join("x.txt.c20").exists());
+}
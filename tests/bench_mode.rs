@@ -0,0 +1,40 @@
+//! `bench`の引数の確認と、小さいバッファでの計測結果を確認する
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crypto_tool"))
+        .args(["--lang", "en", "bench"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn invalid_seconds_are_rejected() {
+    for seconds in ["0", "-1", "NaN", "inf"] {
+        let output = run(&["--size", "1K", &format!("--seconds={}", seconds)]);
+        assert!(!output.status.success(), "{}", seconds);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("expected a positive number of seconds"),
+            "{}",
+            stderr
+        );
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn bench_reports_each_cipher() {
+    let output = run(&["--json", "--size", "1K", "--seconds", "0.01"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines() {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(value["operation"], "bench");
+        assert_eq!(value["size"], 1024);
+        assert!(value["megabytes_per_second"].as_f64().unwrap() > 0.0);
+    }
+    assert!(stdout.contains(r#""cipher":"aes256gcmsiv""#));
+}
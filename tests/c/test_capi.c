@@ -0,0 +1,265 @@
+/*
+ * C APIのテスト
+ * make -C tests/c test
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "crypto_tool.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                  \
+    do {                                                                  \
+        if (!(condition)) {                                               \
+            fprintf(stderr, "%s:%d: CHECK failed: %s\n", __FILE__,        \
+                    __LINE__, #condition);                                \
+            failures++;                                                   \
+        }                                                                 \
+    } while (0)
+
+#define CHECK_STATUS(expression, expected)                                \
+    do {                                                                  \
+        CtStatus status_ = (expression);                                  \
+        if (status_ != (expected)) {                                      \
+            const char *message_ = ct_last_error_message();               \
+            fprintf(stderr, "%s:%d: %s returned %d, expected %d (%s)\n",  \
+                    __FILE__, __LINE__, #expression, (int)status_,        \
+                    (int)(expected), message_ ? message_ : "no message");  \
+            failures++;                                                   \
+        }                                                                 \
+    } while (0)
+
+static const uint8_t KEY_BYTES[32] = {
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a,
+    0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15,
+    0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+};
+
+/* 進捗の通知を記録する */
+struct progress {
+    uint64_t calls;
+    uint64_t processed;
+    uint64_t total;
+};
+
+static void on_progress(uint64_t processed, uint64_t total, void *user_data) {
+    struct progress *progress = user_data;
+    progress->calls++;
+    progress->processed = processed;
+    progress->total = total;
+}
+
+static uint8_t *make_plain_text(size_t len) {
+    uint8_t *data = malloc(len);
+    for (size_t i = 0; i < len; i++) {
+        data[i] = (uint8_t)(i * 31 % 251);
+    }
+    return data;
+}
+
+static int write_file(const char *path, const uint8_t *data, size_t len) {
+    FILE *file = fopen(path, "wb");
+    if (!file) {
+        return 0;
+    }
+    size_t written = fwrite(data, 1, len, file);
+    fclose(file);
+    return written == len;
+}
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    if (!file) {
+        return NULL;
+    }
+    fseek(file, 0, SEEK_END);
+    *len = (size_t)ftell(file);
+    fseek(file, 0, SEEK_SET);
+    uint8_t *data = malloc(*len ? *len : 1);
+    if (fread(data, 1, *len, file) != *len) {
+        free(data);
+        data = NULL;
+    }
+    fclose(file);
+    return data;
+}
+
+static void test_version(void) {
+    const char *version = ct_version();
+    CHECK(version != NULL && strlen(version) > 0);
+}
+
+static void test_keys(void) {
+    CtKey *key = NULL;
+    CHECK_STATUS(ct_key_from_bytes(KEY_BYTES, 31, &key), CT_STATUS_INVALID_KEY);
+    CHECK(key == NULL);
+    CHECK(ct_last_error_message() != NULL);
+
+    CHECK_STATUS(ct_key_from_bytes(KEY_BYTES, 32, &key), CT_STATUS_OK);
+    CHECK(key != NULL);
+    CHECK(ct_last_error_message() == NULL);
+    ct_key_free(key);
+
+    CHECK_STATUS(ct_key_generate(&key), CT_STATUS_OK);
+    ct_key_free(key);
+    ct_key_free(NULL);
+
+    CHECK_STATUS(ct_key_generate(NULL), CT_STATUS_INVALID_ARGUMENT);
+    CHECK_STATUS(ct_key_from_file("/nonexistent/key.bin", &key),
+                 CT_STATUS_NOT_FOUND);
+}
+
+static void test_buffer_round_trip(CtKey *key) {
+    const size_t len = 100003;
+    uint8_t *plain_text = make_plain_text(len);
+    size_t encrypted_len = 0;
+
+    /* バッファが足りない場合は必要なサイズを返す */
+    uint8_t small[4];
+    CHECK_STATUS(ct_encrypt_buffer(key, plain_text, len, small, sizeof(small),
+                                   &encrypted_len),
+                 CT_STATUS_BUFFER_TOO_SMALL);
+    CHECK(encrypted_len == ct_encrypted_size(len));
+
+    uint8_t *encrypted = malloc(encrypted_len);
+    CHECK_STATUS(ct_encrypt_buffer(key, plain_text, len, encrypted,
+                                   encrypted_len, &encrypted_len),
+                 CT_STATUS_OK);
+    CHECK(encrypted_len == ct_encrypted_size(len));
+    CHECK(memcmp(encrypted + (encrypted_len - len), plain_text, len) != 0);
+
+    size_t decrypted_len = 0;
+    uint8_t *decrypted = malloc(len);
+    CHECK_STATUS(ct_decrypt_buffer(key, encrypted, encrypted_len, decrypted,
+                                   len, &decrypted_len),
+                 CT_STATUS_OK);
+    CHECK(decrypted_len == len);
+    CHECK(memcmp(decrypted, plain_text, len) == 0);
+
+    /* ヘッダーより短い暗号文 */
+    CHECK_STATUS(ct_decrypt_buffer(key, encrypted, 5, decrypted, len,
+                                   &decrypted_len),
+                 CT_STATUS_TRUNCATED_HEADER);
+
+    /* 空の平文でもヘッダーは書き込む */
+    uint8_t header_only[128];
+    CHECK_STATUS(ct_encrypt_buffer(key, NULL, 0, header_only,
+                                   sizeof(header_only), &encrypted_len),
+                 CT_STATUS_OK);
+    CHECK(encrypted_len == ct_encrypted_size(0));
+
+    CHECK_STATUS(ct_encrypt_buffer(NULL, plain_text, len, encrypted,
+                                   encrypted_len, &encrypted_len),
+                 CT_STATUS_INVALID_ARGUMENT);
+
+    free(plain_text);
+    free(encrypted);
+    free(decrypted);
+}
+
+static void test_file_round_trip(CtKey *key, const char *dir) {
+    char plain_path[512], encrypted_path[512], decrypted_path[512];
+    snprintf(plain_path, sizeof(plain_path), "%s/plain.bin", dir);
+    snprintf(encrypted_path, sizeof(encrypted_path), "%s/plain.bin.c20", dir);
+    snprintf(decrypted_path, sizeof(decrypted_path), "%s/decrypted.bin", dir);
+
+    const size_t len = 3 * 1024 * 1024 + 7;
+    uint8_t *plain_text = make_plain_text(len);
+    CHECK(write_file(plain_path, plain_text, len));
+
+    struct progress progress = {0, 0, 0};
+    CHECK_STATUS(ct_encrypt_file(key, plain_path, encrypted_path, on_progress,
+                                 &progress),
+                 CT_STATUS_OK);
+    CHECK(progress.calls > 0);
+    CHECK(progress.processed == len);
+    CHECK(progress.total == len);
+
+    memset(&progress, 0, sizeof(progress));
+    CHECK_STATUS(ct_decrypt_file(key, encrypted_path, decrypted_path,
+                                 on_progress, &progress),
+                 CT_STATUS_OK);
+    CHECK(progress.processed == len);
+    CHECK(progress.total == len);
+
+    size_t decrypted_len = 0;
+    uint8_t *decrypted = read_file(decrypted_path, &decrypted_len);
+    CHECK(decrypted != NULL && decrypted_len == len);
+    CHECK(decrypted != NULL && memcmp(decrypted, plain_text, len) == 0);
+
+    /* ファイルとバッファは同じ形式 */
+    size_t encrypted_len = 0;
+    uint8_t *encrypted = read_file(encrypted_path, &encrypted_len);
+    uint8_t *from_buffer = malloc(len);
+    size_t from_buffer_len = 0;
+    CHECK_STATUS(ct_decrypt_buffer(key, encrypted, encrypted_len, from_buffer,
+                                   len, &from_buffer_len),
+                 CT_STATUS_OK);
+    CHECK(from_buffer_len == len && memcmp(from_buffer, plain_text, len) == 0);
+
+    /* 進捗の通知は省略できる */
+    CHECK_STATUS(ct_decrypt_file(key, encrypted_path, decrypted_path, NULL,
+                                 NULL),
+                 CT_STATUS_OK);
+
+    CHECK_STATUS(ct_decrypt_file(key, "/nonexistent/file.c20", decrypted_path,
+                                 NULL, NULL),
+                 CT_STATUS_NOT_FOUND);
+    CHECK_STATUS(ct_encrypt_file(key, NULL, decrypted_path, NULL, NULL),
+                 CT_STATUS_INVALID_ARGUMENT);
+
+    /* 失敗した場合は書き出し先のファイルを残さない */
+    remove(decrypted_path);
+    CHECK(write_file(plain_path, (const uint8_t *)"short", 5));
+    CHECK_STATUS(ct_decrypt_file(key, plain_path, decrypted_path, NULL, NULL),
+                 CT_STATUS_TRUNCATED_HEADER);
+    CHECK(read_file(decrypted_path, &decrypted_len) == NULL);
+    CtKey *other_key = NULL;
+    CHECK_STATUS(ct_key_generate(&other_key), CT_STATUS_OK);
+    CHECK(ct_decrypt_file(other_key, encrypted_path, decrypted_path, NULL,
+                          NULL) != CT_STATUS_OK);
+    CHECK(read_file(decrypted_path, &decrypted_len) == NULL);
+    ct_key_free(other_key);
+
+    remove(plain_path);
+    remove(encrypted_path);
+    remove(decrypted_path);
+    free(plain_text);
+    free(decrypted);
+    free(encrypted);
+    free(from_buffer);
+}
+
+static void test_language(void) {
+    CtKey *key = NULL;
+    CHECK_STATUS(ct_set_language("en"), CT_STATUS_OK);
+    CHECK_STATUS(ct_key_from_bytes(KEY_BYTES, 1, &key), CT_STATUS_INVALID_KEY);
+    const char *message = ct_last_error_message();
+    CHECK(message != NULL && strstr(message, "32 bytes") != NULL);
+
+    CHECK_STATUS(ct_set_language("fr"), CT_STATUS_INVALID_ARGUMENT);
+}
+
+int main(int argc, char **argv) {
+    const char *dir = argc > 1 ? argv[1] : ".";
+    CtKey *key = NULL;
+
+    test_version();
+    test_keys();
+    CHECK_STATUS(ct_key_from_bytes(KEY_BYTES, sizeof(KEY_BYTES), &key),
+                 CT_STATUS_OK);
+    test_buffer_round_trip(key);
+    test_file_round_trip(key, dir);
+    test_language();
+    ct_key_free(key);
+
+    if (failures > 0) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return 1;
+    }
+    printf("C API tests passed (crypto_tool %s)\n", ct_version());
+    return 0;
+}
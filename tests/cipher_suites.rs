@@ -0,0 +1,647 @@
+//! すべての暗号スイートで暗号化・復号できること、改ざんや切り詰めを検出できること、
+//! バージョン1(ナンスだけのヘッダー)のファイルを復号できることを確認する
+
+use crypto_tool::crypto::{
+    ChaCha20Cipher, Cipher, Decryptor, Encryptor, Error, Header, Key, DEFAULT_CHUNK_SIZE,
+};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+fn key() -> Key {
+    Key::new([0x42; 32])
+}
+
+fn plain_text(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+/// チャンクの境界の前後と、複数のチャンクにまたがるサイズ
+fn sizes() -> Vec<usize> {
+    let chunk = DEFAULT_CHUNK_SIZE as usize;
+    vec![0, 1, chunk - 1, chunk, chunk + 1, 3 * chunk + 12345]
+}
+
+fn encrypt(cipher: Cipher, plain_text: &[u8]) -> Vec<u8> {
+    let mut encrypted = Vec::new();
+    Encryptor::new(&key())
+        .cipher(cipher)
+        .encrypt(plain_text, &mut encrypted)
+        .unwrap();
+    encrypted
+}
+
+fn decrypt(encrypted: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decrypted = Vec::new();
+    Decryptor::new(&key()).decrypt(encrypted, &mut decrypted)?;
+    Ok(decrypted)
+}
+
+#[test]
+fn round_trip_all_ciphers() {
+    for cipher in Cipher::ALL {
+        for size in sizes() {
+            let plain_text = plain_text(size);
+            let encrypted = encrypt(cipher, &plain_text);
+            let header = Header::parse(&encrypted).unwrap();
+
+            assert_eq!(header.cipher(), cipher);
+            assert_eq!(header.version(), Header::VERSION);
+            assert_eq!(
+                encrypted.len() as u64,
+                header.encrypted_size(size as u64),
+                "{} {}",
+                cipher.name(),
+                size
+            );
+            assert_eq!(header.decrypted_size(encrypted.len() as u64), size as u64);
+            assert_eq!(decrypt(&encrypted).unwrap(), plain_text);
+        }
+    }
+}
+
+#[test]
+fn writer_matches_encryptor() {
+    for cipher in Cipher::ALL {
+        let plain_text = plain_text(3 * DEFAULT_CHUNK_SIZE as usize + 7);
+        let header = Header::for_cipher(cipher);
+        let mut expected = Vec::new();
+        Encryptor::new(&key())
+            .header(header)
+            .encrypt(&plain_text[..], &mut expected)
+            .unwrap();
+
+        // 書き込みを細かく分けても同じ暗号文になる
+        let mut writer = Encryptor::new(&key()).header(header).writer(Vec::new());
+        for chunk in plain_text.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let encrypted = writer.finish().unwrap();
+
+        assert_eq!(encrypted, expected, "{}", cipher.name());
+    }
+}
+
+#[test]
+fn seekable_reads_any_range() {
+    let chunk = DEFAULT_CHUNK_SIZE as u64;
+    for cipher in Cipher::ALL {
+        let plain_text = plain_text(3 * chunk as usize + 100);
+        let encrypted = encrypt(cipher, &plain_text);
+        let mut reader = Decryptor::new(&key())
+            .seekable(std::io::Cursor::new(&encrypted))
+            .unwrap();
+
+        for (offset, length) in [
+            (0, 10),
+            (chunk - 5, 10),
+            (2 * chunk + 1, chunk),
+            (3 * chunk, 200),
+        ] {
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            let mut decrypted = Vec::new();
+            reader
+                .by_ref()
+                .take(length)
+                .read_to_end(&mut decrypted)
+                .unwrap();
+
+            let end = (offset + length).min(plain_text.len() as u64);
+            assert_eq!(decrypted, &plain_text[offset as usize..end as usize]);
+        }
+        let end = reader.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(end, plain_text.len() as u64);
+    }
+}
+
+#[test]
+fn seekable_seeks_into_chunks() {
+    let chunk = DEFAULT_CHUNK_SIZE as u64;
+    for cipher in Cipher::ALL {
+        let plain_text = plain_text(2 * chunk as usize + 100);
+        let encrypted = encrypt(cipher, &plain_text);
+        let mut reader = Decryptor::new(&key())
+            .seekable(std::io::Cursor::new(&encrypted))
+            .unwrap();
+        let read = |reader: &mut dyn Read, length: usize| {
+            let mut decrypted = vec![0; length];
+            reader.read_exact(&mut decrypted).unwrap();
+            decrypted
+        };
+
+        // チャンクの途中に移動して、次のチャンクにまたがって読み込む
+        let position = reader.seek(SeekFrom::Start(chunk / 2)).unwrap();
+        assert_eq!(position, chunk / 2);
+        let range = position as usize..(position + chunk) as usize;
+        assert_eq!(read(&mut reader, range.len()), &plain_text[range]);
+
+        // 読み込んだ位置から戻って、同じチャンクの途中を読み込む
+        let position = reader.seek(SeekFrom::Current(-10)).unwrap();
+        assert_eq!(position, chunk / 2 + chunk - 10);
+        let range = position as usize..position as usize + 20;
+        assert_eq!(read(&mut reader, 20), &plain_text[range]);
+
+        // 末尾から最後のチャンクの途中に移動する
+        let position = reader.seek(SeekFrom::End(-50)).unwrap();
+        assert_eq!(position, plain_text.len() as u64 - 50);
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, &plain_text[position as usize..]);
+
+        // 先頭より前には移動できない
+        assert!(reader
+            .seek(SeekFrom::Current(-(plain_text.len() as i64) - 1))
+            .is_err());
+        assert!(reader
+            .seek(SeekFrom::End(-(plain_text.len() as i64) - 1))
+            .is_err());
+    }
+}
+
+#[test]
+fn seekable_reports_truncated_data() {
+    let chunk = DEFAULT_CHUNK_SIZE as usize;
+    for cipher in Cipher::ALL
+        .iter()
+        .copied()
+        .filter(|c| *c != Cipher::ChaCha20)
+    {
+        let encrypted = encrypt(cipher, &plain_text(2 * chunk + 100));
+        let header = Header::parse(&encrypted).unwrap();
+        let full_chunk = chunk + cipher.suite().tag_size();
+        let read_at = |data: &[u8], position: u64| {
+            let mut reader = Decryptor::new(&key())
+                .seekable(std::io::Cursor::new(data))
+                .unwrap();
+            reader.seek(SeekFrom::Start(position)).unwrap();
+            let mut byte = [0];
+            reader.read(&mut byte).map_err(|e| e.kind())
+        };
+
+        // チャンクの境界、認証タグの途中、ヘッダーの直後で切り詰める
+        for length in [
+            header.size() + 2 * full_chunk,
+            header.size() + full_chunk + 3,
+            header.size(),
+        ] {
+            let truncated = &encrypted[..length];
+            for position in [0, chunk as u64 / 2, 3 * chunk as u64] {
+                assert_eq!(
+                    read_at(truncated, position),
+                    Err(std::io::ErrorKind::UnexpectedEof),
+                    "{} {} {}",
+                    cipher.name(),
+                    length,
+                    position
+                );
+            }
+        }
+
+        // チャンクの途中で切り詰めると、最後のチャンクとして認証できない
+        let truncated = &encrypted[..header.size() + full_chunk + 50];
+        assert_eq!(
+            read_at(truncated, chunk as u64 + 10),
+            Err(std::io::ErrorKind::InvalidData)
+        );
+        assert_eq!(read_at(truncated, 10), Ok(1));
+    }
+}
+
+#[test]
+fn tampered_chunk_fails_authentication() {
+    for cipher in Cipher::ALL
+        .iter()
+        .copied()
+        .filter(|c| *c != Cipher::ChaCha20)
+    {
+        let mut encrypted = encrypt(cipher, &plain_text(2 * DEFAULT_CHUNK_SIZE as usize));
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+
+        assert!(matches!(
+            decrypt(&encrypted),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+}
+
+#[test]
+fn truncated_chunks_fail_authentication() {
+    let chunk = DEFAULT_CHUNK_SIZE as usize;
+    for cipher in Cipher::ALL
+        .iter()
+        .copied()
+        .filter(|c| *c != Cipher::ChaCha20)
+    {
+        let encrypted = encrypt(cipher, &plain_text(2 * chunk + 10));
+        let header = Header::parse(&encrypted).unwrap();
+        let full_chunk = chunk + cipher.suite().tag_size();
+
+        // 最後のチャンクを取り除くと、残りのチャンクは最後のチャンクとして認証できない
+        let truncated = &encrypted[..header.size() + 2 * full_chunk];
+        assert!(matches!(
+            decrypt(truncated),
+            Err(Error::AuthenticationFailed)
+        ));
+        // ヘッダーだけにしても空の最後のチャンクがないので認証できない
+        let truncated = &encrypted[..header.size()];
+        assert!(matches!(
+            decrypt(truncated),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+}
+
+#[test]
+fn wrong_key_fails_authentication() {
+    let encrypted = encrypt(Cipher::Aes256Gcm, b"secret");
+    let mut decrypted = Vec::new();
+    let result = Decryptor::new(&Key::new([0x24; 32])).decrypt(&encrypted[..], &mut decrypted);
+
+    assert!(matches!(result, Err(Error::AuthenticationFailed)));
+}
+
+#[test]
+fn chunk_is_message_with_last_flag() {
+    // チャンクは、最後のチャンクかどうかを追加データの後ろに付けた1つのメッセージと同じ
+    let associated_data = b"header".to_vec();
+    for cipher in Cipher::ALL {
+        let suite = cipher.suite();
+        let header = Header::for_cipher(cipher);
+        let nonce = header.nonce();
+        for last in [false, true] {
+            let mut chunk = plain_text(1000);
+            suite
+                .chunk_cipher(&key(), nonce, DEFAULT_CHUNK_SIZE, &associated_data)
+                .seal(0, last, &mut chunk)
+                .unwrap();
+            let mut message_associated_data = associated_data.clone();
+            message_associated_data.push(last as u8);
+            let mut message = plain_text(1000);
+            suite
+                .seal_message(&key(), nonce, &message_associated_data, &mut message)
+                .unwrap();
+            assert_eq!(chunk, message, "{}", cipher.name());
+            assert_eq!(chunk.len(), 1000 + suite.tag_size());
+
+            suite
+                .open_message(&key(), nonce, &message_associated_data, &mut message)
+                .unwrap();
+            assert_eq!(message, plain_text(1000));
+        }
+    }
+}
+
+#[test]
+fn legacy_file_is_nonce_and_keystream() {
+    let nonce = [7; 12];
+    let plain_text = plain_text(100_003);
+
+    // バージョン1のファイルはナンスの後ろにChaCha20の暗号文が続く
+    let mut legacy = nonce.to_vec();
+    let mut body = plain_text.clone();
+    ChaCha20Cipher::new(key().as_bytes(), &nonce).apply_keystream(&mut body);
+    legacy.extend_from_slice(&body);
+
+    let header = Header::parse(&legacy).unwrap();
+    assert_eq!(header.version(), Header::LEGACY_VERSION);
+    assert_eq!(header.cipher(), Cipher::ChaCha20);
+    assert_eq!(decrypt(&legacy).unwrap(), plain_text);
+
+    // バージョン1のヘッダーを指定すると同じファイルを作れる
+    let mut encrypted = Vec::new();
+    Encryptor::new(&key())
+        .header(Header::legacy(nonce))
+        .encrypt(&plain_text[..], &mut encrypted)
+        .unwrap();
+    assert_eq!(encrypted, legacy);
+}
+
+#[test]
+fn unknown_version_and_cipher_are_rejected() {
+    let mut encrypted = encrypt(Cipher::ChaCha20Poly1305, b"data");
+    encrypted[9] = 0xff;
+    assert!(matches!(
+        decrypt(&encrypted),
+        Err(Error::UnsupportedCipher(0xff))
+    ));
+
+    encrypted[8] = 9;
+    assert!(matches!(
+        decrypt(&encrypted),
+        Err(Error::UnsupportedVersion(9))
+    ));
+}
+
+/// テストごとに別の一時ファイルのパス
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("crypto_tool_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn parallel_files_match_stream() {
+    let plain_text = plain_text(40 * DEFAULT_CHUNK_SIZE as usize + 321);
+    let plain_path = temp_path("parallel.bin");
+    let encrypted_path = temp_path("parallel.c20");
+    let decrypted_path = temp_path("parallel.out");
+    std::fs::write(&plain_path, &plain_text).unwrap();
+
+    for cipher in Cipher::ALL {
+        let header = Header::for_cipher(cipher);
+        let mut expected = Vec::new();
+        Encryptor::new(&key())
+            .header(header)
+            .encrypt(&plain_text[..], &mut expected)
+            .unwrap();
+
+        // しきい値を0にして必ず複数のスレッドで処理する
+        let bytes = Encryptor::new(&key())
+            .header(header)
+            .threads(4)
+            .parallel_threshold(0)
+            .encrypt_file(
+                &std::fs::File::open(&plain_path).unwrap(),
+                &std::fs::File::create(&encrypted_path).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(bytes, plain_text.len() as u64);
+        assert_eq!(
+            std::fs::read(&encrypted_path).unwrap(),
+            expected,
+            "{}",
+            cipher.name()
+        );
+
+        let bytes = Decryptor::new(&key())
+            .threads(4)
+            .parallel_threshold(0)
+            .decrypt_file(
+                &std::fs::File::open(&encrypted_path).unwrap(),
+                &std::fs::File::create(&decrypted_path).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(bytes, plain_text.len() as u64);
+        assert_eq!(std::fs::read(&decrypted_path).unwrap(), plain_text);
+    }
+
+    // 途中のチャンクを改ざんすると複数のスレッドで処理しても検出できる
+    let mut encrypted = encrypt(Cipher::Aes256Gcm, &plain_text);
+    encrypted[20 * DEFAULT_CHUNK_SIZE as usize] ^= 1;
+    std::fs::write(&encrypted_path, &encrypted).unwrap();
+    let result = Decryptor::new(&key())
+        .threads(4)
+        .parallel_threshold(0)
+        .decrypt_file(
+            &std::fs::File::open(&encrypted_path).unwrap(),
+            &std::fs::File::create(&decrypted_path).unwrap(),
+        );
+    assert!(matches!(result, Err(Error::AuthenticationFailed)));
+
+    for path in [plain_path, encrypted_path, decrypted_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn parallel_chacha20_matches_stream_across_segments() {
+    // 並列処理で1つのスレッドに割り当てる領域(16MiB)の境界を2回またぎ、ブロックの途中で終わる
+    let plain_text = plain_text(2 * 16 * 1024 * 1024 + 12345);
+    let plain_path = temp_path("parallel_segments.bin");
+    let encrypted_path = temp_path("parallel_segments.c20");
+    let decrypted_path = temp_path("parallel_segments.out");
+    std::fs::write(&plain_path, &plain_text).unwrap();
+
+    let header = Header::for_cipher(Cipher::ChaCha20);
+    let mut expected = Vec::new();
+    Encryptor::new(&key())
+        .header(header)
+        .encrypt(&plain_text[..], &mut expected)
+        .unwrap();
+
+    Encryptor::new(&key())
+        .header(header)
+        .threads(3)
+        .parallel_threshold(0)
+        .encrypt_file(
+            &std::fs::File::open(&plain_path).unwrap(),
+            &std::fs::File::create(&encrypted_path).unwrap(),
+        )
+        .unwrap();
+    assert!(std::fs::read(&encrypted_path).unwrap() == expected);
+
+    Decryptor::new(&key())
+        .threads(3)
+        .parallel_threshold(0)
+        .decrypt_file(
+            &std::fs::File::open(&encrypted_path).unwrap(),
+            &std::fs::File::create(&decrypted_path).unwrap(),
+        )
+        .unwrap();
+    assert!(std::fs::read(&decrypted_path).unwrap() == plain_text);
+
+    for path in [plain_path, encrypted_path, decrypted_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn repeated_nonce_only_reveals_identical_chunks() {
+    // ナンスが重複しても、AES-256-GCM-SIVでは異なる平文のチャンクから鍵ストリームは分からない
+    let header = Header::for_cipher(Cipher::Aes256GcmSiv);
+    let chunk = DEFAULT_CHUNK_SIZE as usize;
+    let first = plain_text(2 * chunk);
+    let mut second = first.clone();
+    second[chunk + 1] ^= 1;
+
+    let mut encrypted = Vec::new();
+    for plain_text in [&first, &second] {
+        let mut output = Vec::new();
+        Encryptor::new(&key())
+            .header(header)
+            .encrypt(&plain_text[..], &mut output)
+            .unwrap();
+        encrypted.push(output);
+    }
+
+    // 同じ平文の最初のチャンクは同じ暗号文になる
+    let full_chunk = chunk + Cipher::Aes256GcmSiv.suite().tag_size();
+    let first_chunk = header.size()..header.size() + full_chunk;
+    assert_eq!(encrypted[0][first_chunk.clone()], encrypted[1][first_chunk]);
+
+    // 1byteだけ異なるチャンクは、暗号文のXORが平文のXORにならない
+    let second_chunk = header.size() + full_chunk..header.size() + 2 * full_chunk;
+    let differences = encrypted[0][second_chunk.clone()]
+        .iter()
+        .zip(&encrypted[1][second_chunk])
+        .filter(|(a, b)| a != b)
+        .count();
+    assert!(differences > full_chunk / 2);
+}
+
+#[test]
+fn deterministic_encryption_is_repeatable() {
+    let plain_text = plain_text(2 * DEFAULT_CHUNK_SIZE as usize + 5);
+    let encrypt = |plain_text: &[u8]| {
+        let mut encrypted = Vec::new();
+        Encryptor::new(&key())
+            .cipher(Cipher::Aes256GcmSiv)
+            .deterministic(true)
+            .encrypt(plain_text, &mut encrypted)
+            .unwrap();
+        encrypted
+    };
+
+    let encrypted = encrypt(&plain_text);
+    assert_eq!(encrypt(&plain_text), encrypted);
+    assert_eq!(decrypt(&encrypted).unwrap(), plain_text);
+    assert_eq!(
+        Header::parse(&encrypted).unwrap(),
+        Header::deterministic(Cipher::Aes256GcmSiv, &key(), &plain_text[..])
+            .unwrap()
+            .authenticate(&key())
+    );
+
+    // 平文や鍵が異なればナンスも異なる
+    let mut other = plain_text.clone();
+    other[0] ^= 1;
+    assert_ne!(
+        encrypt(&other)[..Header::MAX_SIZE],
+        encrypted[..Header::MAX_SIZE]
+    );
+    let header = Header::deterministic(Cipher::Aes256GcmSiv, &Key::new([1; 32]), &plain_text[..]);
+    assert_ne!(
+        header.unwrap().nonce(),
+        Header::parse(&encrypted).unwrap().nonce()
+    );
+
+    // ファイルの暗号化もストリームと同じ暗号文になる
+    let plain_path = temp_path("deterministic.bin");
+    let encrypted_path = temp_path("deterministic.c20");
+    std::fs::write(&plain_path, &plain_text).unwrap();
+    for threads in [1, 4] {
+        Encryptor::new(&key())
+            .cipher(Cipher::Aes256GcmSiv)
+            .deterministic(true)
+            .threads(threads)
+            .parallel_threshold(0)
+            .encrypt_file(
+                &std::fs::File::open(&plain_path).unwrap(),
+                &std::fs::File::create(&encrypted_path).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(std::fs::read(&encrypted_path).unwrap(), encrypted);
+    }
+    for path in [plain_path, encrypted_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn deterministic_needs_misuse_resistant_cipher() {
+    for cipher in Cipher::ALL
+        .iter()
+        .copied()
+        .filter(|c| !c.is_misuse_resistant())
+    {
+        let result = Encryptor::new(&key())
+            .cipher(cipher)
+            .deterministic(true)
+            .encrypt(&b"data"[..], Vec::new());
+        assert!(matches!(
+            result,
+            Err(Error::DeterministicUnsupported(c)) if c == cipher
+        ));
+    }
+}
+
+#[test]
+fn wrong_key_fails_header_authentication() {
+    let wrong_key = Key::new([0x24; 32]);
+    for cipher in Cipher::ALL {
+        // 本体を認証しないChaCha20でも、ヘッダーの認証コードで鍵の誤りが分かる
+        let encrypted = encrypt(cipher, b"secret");
+        let result = Decryptor::new(&wrong_key).decrypt(&encrypted[..], Vec::new());
+        assert!(matches!(result, Err(Error::AuthenticationFailed)));
+
+        let result = Decryptor::new(&wrong_key).seekable(std::io::Cursor::new(&encrypted));
+        assert!(matches!(result, Err(Error::AuthenticationFailed)));
+    }
+}
+
+#[test]
+fn tampered_header_fails_authentication() {
+    let encrypted = encrypt(Cipher::ChaCha20, &plain_text(1000));
+    let header = Header::parse(&encrypted).unwrap();
+
+    // ソルト、ナンス、認証コードのどこを書き換えても認証できない
+    for position in [20, header.size() - 40, header.size() - 1] {
+        let mut tampered = encrypted.clone();
+        tampered[position] ^= 1;
+        assert!(matches!(
+            decrypt(&tampered),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+}
+
+#[test]
+fn nonzero_flags_are_rejected() {
+    // フラグは認証コードの対象なので、書き換えたファイルは読み込まない
+    let encrypted = encrypt(Cipher::ChaCha20Poly1305, &plain_text(1000));
+    for (position, flags) in [(10, 0x0100), (11, 0x0001)] {
+        let mut tampered = encrypted.clone();
+        tampered[position] ^= 1;
+        assert!(matches!(
+            Header::parse(&tampered),
+            Err(Error::UnsupportedFlags(found)) if found == flags
+        ));
+        assert!(matches!(
+            decrypt(&tampered),
+            Err(Error::UnsupportedFlags(_))
+        ));
+    }
+}
+
+#[test]
+fn salt_separates_file_keys() {
+    // ナンスが同じでもソルトが異なればファイルの鍵が異なるので、鍵ストリームは重複しない
+    let header = Header::for_cipher(Cipher::ChaCha20);
+    let mut bytes = header.to_bytes();
+    bytes[20] ^= 1;
+    let other = Header::parse(&bytes).unwrap();
+    assert_eq!(other.nonce(), header.nonce());
+    assert_ne!(other.salt(), header.salt());
+
+    let plain_text = plain_text(1000);
+    let mut encrypted = Vec::new();
+    for header in [header, other] {
+        let mut output = Vec::new();
+        Encryptor::new(&key())
+            .header(header)
+            .encrypt(&plain_text[..], &mut output)
+            .unwrap();
+        assert_eq!(decrypt(&output).unwrap(), plain_text);
+        encrypted.push(output[header.size()..].to_vec());
+    }
+    assert_ne!(encrypted[0], encrypted[1]);
+
+    // ChaCha20の鍵ストリームはマスター鍵のものとも異なる
+    let mut master_key_body = plain_text;
+    let mut nonce = [0; 12];
+    nonce.copy_from_slice(header.nonce());
+    ChaCha20Cipher::new(key().as_bytes(), &nonce).apply_keystream(&mut master_key_body);
+    assert_ne!(encrypted[0], master_key_body);
+}
+
+#[test]
+fn version_2_is_rejected() {
+    // 公開しなかったバージョン2(ソルトと認証コードがない形式)は読み込まない
+    let mut encrypted = encrypt(Cipher::ChaCha20, b"data");
+    encrypted[8] = 2;
+    assert!(matches!(
+        Header::parse(&encrypted),
+        Err(Error::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        decrypt(&encrypted),
+        Err(Error::UnsupportedVersion(2))
+    ));
+}
@@ -0,0 +1,145 @@
+//! `CipherWriter`で少しずつ書き込んだデータを`Decryptor`で復号できること、
+//! `finish`を呼ばずに破棄した場合や書き込みに失敗した場合の動作を確認する
+
+use crypto_tool::crypto::{Cipher, Decryptor, Encryptor, Error, Header, Key, DEFAULT_CHUNK_SIZE};
+use std::io::{self, Write};
+
+fn key() -> Key {
+    Key::new([0x42; 32])
+}
+
+fn plain_text(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn decrypt(encrypted: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decrypted = Vec::new();
+    Decryptor::new(&key()).decrypt(encrypted, &mut decrypted)?;
+    Ok(decrypted)
+}
+
+#[test]
+fn small_writes_across_chunks_round_trip() {
+    let chunk = DEFAULT_CHUNK_SIZE as usize;
+    for cipher in Cipher::ALL {
+        for size in [0, 1, chunk - 1, chunk, chunk + 1, 2 * chunk + 12345] {
+            let plain_text = plain_text(size);
+            let mut writer = Encryptor::new(&key()).cipher(cipher).writer(Vec::new());
+            // チャンクの大きさで割り切れない大きさに分けて書き込む
+            for part in plain_text.chunks(4099) {
+                writer.write_all(part).unwrap();
+            }
+            let encrypted = writer.finish().unwrap();
+
+            let header = Header::parse(&encrypted).unwrap();
+            assert_eq!(header.cipher(), cipher);
+            assert_eq!(
+                encrypted.len() as u64,
+                header.encrypted_size(size as u64),
+                "{} {}",
+                cipher.name(),
+                size
+            );
+            assert_eq!(decrypt(&encrypted).unwrap(), plain_text);
+        }
+    }
+}
+
+#[test]
+fn drop_writes_the_last_chunk() {
+    let plain_text = plain_text(DEFAULT_CHUNK_SIZE as usize + 10);
+    for cipher in Cipher::ALL {
+        let header = Header::for_cipher(cipher);
+        let finished = {
+            let mut writer = Encryptor::new(&key()).header(header).writer(Vec::new());
+            writer.write_all(&plain_text).unwrap();
+            writer.finish().unwrap()
+        };
+
+        let mut dropped = Vec::new();
+        {
+            let mut writer = Encryptor::new(&key()).header(header).writer(&mut dropped);
+            writer.write_all(&plain_text).unwrap();
+        }
+
+        assert_eq!(dropped, finished, "{}", cipher.name());
+        assert_eq!(decrypt(&dropped).unwrap(), plain_text);
+    }
+
+    // 何も書き込まずに破棄しても、ヘッダーと空の最後のチャンクを書き込む
+    let mut dropped = Vec::new();
+    drop(
+        Encryptor::new(&key())
+            .cipher(Cipher::ChaCha20Poly1305)
+            .writer(&mut dropped),
+    );
+    assert!(decrypt(&dropped).unwrap().is_empty());
+}
+
+#[test]
+fn last_chunk_is_authenticated_as_last() {
+    let chunk = DEFAULT_CHUNK_SIZE as usize;
+    for cipher in Cipher::ALL
+        .iter()
+        .copied()
+        .filter(|c| *c != Cipher::ChaCha20)
+    {
+        let mut writer = Encryptor::new(&key()).cipher(cipher).writer(Vec::new());
+        for part in plain_text(2 * chunk).chunks(1000) {
+            writer.write_all(part).unwrap();
+        }
+        let encrypted = writer.finish().unwrap();
+        let header = Header::parse(&encrypted).unwrap();
+
+        // ちょうどチャンクの境界で終わるので、最後は空のチャンクになる
+        let tag_size = cipher.suite().tag_size();
+        assert_eq!(
+            encrypted.len(),
+            header.size() + 2 * (chunk + tag_size) + tag_size
+        );
+        let without_last = &encrypted[..encrypted.len() - tag_size];
+        assert!(matches!(
+            decrypt(without_last),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+}
+
+/// 指定したサイズを超えて書き込むと失敗し、書き込みの回数を数えるライター
+struct FailingWriter<'a> {
+    capacity: usize,
+    written: usize,
+    writes: &'a mut usize,
+}
+
+impl Write for FailingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        *self.writes += 1;
+        if self.written + buf.len() > self.capacity {
+            return Err(io::Error::other("full"));
+        }
+        self.written += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn failed_finish_does_not_write_again_on_drop() {
+    let mut writes = 0;
+    let header = Header::for_cipher(Cipher::ChaCha20Poly1305);
+    let writer = FailingWriter {
+        capacity: header.size(),
+        written: 0,
+        writes: &mut writes,
+    };
+    let mut writer = Encryptor::new(&key()).header(header).writer(writer);
+    writer.write_all(b"data").unwrap();
+
+    // ヘッダーは書き込めるが、最後のチャンクの書き込みに失敗する
+    assert!(writer.finish().is_err());
+    assert_eq!(writes, 2);
+}
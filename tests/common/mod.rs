@@ -0,0 +1,32 @@
+//! 結合テストで共通に使う一時ディレクトリ
+
+use std::path::{Path, PathBuf};
+
+/// # 一時ディレクトリ
+/// テストごとに別の空のディレクトリを作成し、破棄すると中のファイルごと削除します。
+/// `Path`として使えます。
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("crypto_tool_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,142 @@
+//! 暗号化したファイルの形式が変わっていないことを、`tests/golden`のファイルと比べて確認する
+//! 乱数と時刻を固定して(`SeededRandom`、`FixedClock`)暗号化し、バイト単位で一致することを確かめます。
+//! 形式を変えた場合は`UPDATE_GOLDEN=1 cargo test --test golden_files`でファイルを作り直します。
+
+use crypto_tool::crypto::{
+    Cipher, Decryptor, Encryptor, FixedClock, Header, Key, SeededRandom, DEFAULT_CHUNK_SIZE,
+};
+use std::path::PathBuf;
+
+/// 2020-09-13T12:26:40Z
+const TIMESTAMP_NANOS: i64 = 1_600_000_000_000_000_000;
+const SEED: u64 = 20211001;
+
+fn key() -> Key {
+    Key::new([0x42; 32])
+}
+
+fn plain_text(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+/// # 確認するファイル
+struct Case {
+    name: &'static str,
+    cipher: Cipher,
+    deterministic: bool,
+    header: Option<Header>,
+    size: usize,
+}
+
+fn cases() -> Vec<Case> {
+    let case = |name, cipher, size| Case {
+        name,
+        cipher,
+        deterministic: false,
+        header: None,
+        size,
+    };
+    vec![
+        case("chacha20.c20", Cipher::ChaCha20, 1000),
+        // 複数のチャンクに分かれる
+        case(
+            "chacha20poly1305.c20",
+            Cipher::ChaCha20Poly1305,
+            DEFAULT_CHUNK_SIZE as usize + 1000,
+        ),
+        case("xchacha20poly1305.c20", Cipher::XChaCha20Poly1305, 1000),
+        case("aes256gcm.c20", Cipher::Aes256Gcm, 1000),
+        case("aes256gcmsiv.c20", Cipher::Aes256GcmSiv, 1000),
+        Case {
+            deterministic: true,
+            ..case("aes256gcmsiv_deterministic.c20", Cipher::Aes256GcmSiv, 1000)
+        },
+        Case {
+            header: Some(Header::legacy([9; 12])),
+            ..case("legacy_v1.c20", Cipher::ChaCha20, 1000)
+        },
+    ]
+}
+
+/// # 乱数と時刻を固定して暗号化
+fn encrypt(case: &Case) -> Vec<u8> {
+    let key = key();
+    let random = SeededRandom::new(SEED);
+    let encryptor = Encryptor::new(&key)
+        .cipher(case.cipher)
+        .deterministic(case.deterministic)
+        .random(&random)
+        .clock(&FixedClock(TIMESTAMP_NANOS));
+    let encryptor = match case.header {
+        Some(header) => encryptor.header(header),
+        None => encryptor,
+    };
+    let mut encrypted = Vec::new();
+    encryptor
+        .encrypt(&plain_text(case.size)[..], &mut encrypted)
+        .unwrap();
+    encrypted
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(name)
+}
+
+#[test]
+fn encryption_matches_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    for case in cases() {
+        let encrypted = encrypt(&case);
+        let path = golden_path(case.name);
+        if update {
+            std::fs::write(&path, &encrypted).unwrap();
+        }
+        let golden = std::fs::read(&path).unwrap();
+        assert!(encrypted == golden, "{}と一致しません", case.name);
+    }
+}
+
+#[test]
+fn golden_files_decrypt() {
+    for case in cases() {
+        let golden = std::fs::read(golden_path(case.name)).unwrap();
+        let mut decrypted = Vec::new();
+        Decryptor::new(&key())
+            .decrypt(&golden[..], &mut decrypted)
+            .unwrap();
+        assert!(
+            decrypted == plain_text(case.size),
+            "{}を復号できません",
+            case.name
+        );
+    }
+}
+
+#[test]
+fn seeded_random_repeats() {
+    let encrypt_with_seed = |seed| {
+        let random = SeededRandom::new(seed);
+        let key = key();
+        let mut encrypted = Vec::new();
+        Encryptor::new(&key)
+            .cipher(Cipher::XChaCha20Poly1305)
+            .random(&random)
+            .clock(&FixedClock(TIMESTAMP_NANOS))
+            .encrypt(&b"hello"[..], &mut encrypted)
+            .unwrap();
+        encrypted
+    };
+    assert_eq!(encrypt_with_seed(SEED), encrypt_with_seed(SEED));
+    assert_ne!(encrypt_with_seed(SEED), encrypt_with_seed(SEED + 1));
+
+    // 同じ乱数から続けて作るヘッダーは異なる
+    let random = SeededRandom::new(SEED);
+    let clock = FixedClock(TIMESTAMP_NANOS);
+    let first = Header::for_cipher_with(Cipher::ChaCha20, &random, &clock);
+    let second = Header::for_cipher_with(Cipher::ChaCha20, &random, &clock);
+    assert_ne!(first.salt(), second.salt());
+    assert_ne!(first.nonce(), second.nonce());
+}
@@ -0,0 +1,162 @@
+//! `--json`で表示する結果が、標準出力に書き出すデータと混ざらないことを確認する
+
+mod common;
+
+use common::TempDir;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// ディレクトリの中の鍵ファイルのパス
+fn key_path(dir: &Path) -> PathBuf {
+    let path = dir.join("key.bin");
+    std::fs::write(&path, [0x42; 32]).unwrap();
+    path
+}
+
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crypto_tool"))
+        .args(["--lang", "en"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn failure_goes_to_stderr_when_writing_to_stdout() {
+    let dir = TempDir::new("json_stdout");
+    let key = key_path(&dir);
+    let key = key.to_str().unwrap();
+    for args in [
+        vec!["decrypt", "--json", "-k", key, "-"],
+        vec!["decrypt", "--json", "-k", key, "-o", "-", "-"],
+    ] {
+        // ヘッダーより短いので復号できない
+        let output = run(&args, b"x");
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty(), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(r#""operation":"decrypt","status":"failed""#),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn failure_goes_to_stdout_when_writing_to_file() {
+    let dir = TempDir::new("json_file");
+    let key = key_path(&dir);
+    let missing = dir.join("missing.c20");
+    let output = run(
+        &[
+            "decrypt",
+            "--json",
+            "-k",
+            key.to_str().unwrap(),
+            missing.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(r#""status":"failed","error":{"code":"not_found""#),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn batch_failure_reports_each_file_only() {
+    let dir = TempDir::new("json_batch");
+    let key = key_path(&dir);
+    let plain = dir.join("plain.txt");
+    std::fs::write(&plain, b"hello").unwrap();
+
+    let output = run(
+        &[
+            "encrypt",
+            "--json",
+            "-k",
+            key.to_str().unwrap(),
+            plain.to_str().unwrap(),
+            dir.join("missing.txt").to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(!output.status.success());
+    // ファイルごとの結果だけを表示し、コマンド全体の失敗は表示しない
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let statuses = stdout
+        .lines()
+        .map(|line| {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(value["input"].is_string(), "{}", line);
+            value["status"].as_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(statuses, ["success", "failed"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("\"operation\""));
+}
+
+/// 失敗したJSONの`error.code`
+fn failure_code(output: &Output) -> String {
+    let json = String::from_utf8_lossy(&output.stdout);
+    let value: serde_json::Value = serde_json::from_str(json.trim()).unwrap();
+    value["error"]["code"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn crypto_errors_have_their_own_codes() {
+    let dir = TempDir::new("json_codes");
+    let key = key_path(&dir);
+    let other_key = dir.join("other.key");
+    std::fs::write(&other_key, [0x24; 32]).unwrap();
+    let short_key = dir.join("short.key");
+    std::fs::write(&short_key, [0x42; 16]).unwrap();
+    let plain = dir.join("codes.txt");
+    std::fs::write(&plain, b"hello").unwrap();
+    let encrypted = dir.join("codes.txt.c20");
+    let decrypted = dir.join("codes.out");
+
+    let path = |path: &PathBuf| path.to_str().unwrap().to_string();
+    let crypto = |mode: &str, key: &PathBuf, input: &PathBuf, output: &PathBuf| {
+        run(
+            &[
+                mode,
+                "--json",
+                "-k",
+                &path(key),
+                "-o",
+                &path(output),
+                &path(input),
+            ],
+            b"",
+        )
+    };
+    assert!(crypto("encrypt", &key, &plain, &encrypted).status.success());
+
+    let output = crypto("decrypt", &other_key, &encrypted, &decrypted);
+    assert!(!output.status.success());
+    assert_eq!(failure_code(&output), "authentication_failed");
+
+    let output = crypto("decrypt", &short_key, &encrypted, &decrypted);
+    assert!(!output.status.success());
+    assert_eq!(failure_code(&output), "invalid_key_length");
+
+    // ヘッダーのフラグ(予約)を0以外にする
+    let mut flagged = std::fs::read(&encrypted).unwrap();
+    flagged[10] = 1;
+    let flagged_path = dir.join("flagged.c20");
+    std::fs::write(&flagged_path, flagged).unwrap();
+    let output = crypto("decrypt", &key, &flagged_path, &decrypted);
+    assert!(!output.status.success());
+    assert_eq!(failure_code(&output), "unsupported_flags");
+}
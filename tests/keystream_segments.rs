@@ -0,0 +1,113 @@
+//! ChaCha20のブロックカウンタの上限(256GiB)をまたいで鍵ストリームを適用できることを確認する
+//! 256GiBのデータを書き込まずに、境界の手前にシークしてから確かめる
+
+use chacha20::cipher::{NewCipher, StreamCipher, StreamCipherSeek};
+use crypto_tool::crypto::{Backend, ChaCha20Cipher, KEYSTREAM_SEGMENT_SIZE};
+
+const KEY: [u8; 32] = [0x42; 32];
+const NONCE: [u8; 12] = [7; 12];
+
+/// # セグメントの先頭からの鍵ストリーム
+/// `chacha20`クレートに、セグメントの番号をXORしたナンスを渡して求める
+fn segment_keystream(segment: u64, offset: u64, size: usize) -> Vec<u8> {
+    let mut nonce = NONCE;
+    for (byte, segment_byte) in nonce.iter_mut().zip(segment.to_le_bytes()) {
+        *byte ^= segment_byte;
+    }
+    let mut cipher = chacha20::ChaCha20::new(
+        chacha20::Key::from_slice(&KEY),
+        chacha20::Nonce::from_slice(&nonce),
+    );
+    cipher.seek(offset);
+    let mut keystream = vec![0; size];
+    cipher.apply_keystream(&mut keystream);
+    keystream
+}
+
+/// # 境界の前後の鍵ストリーム
+fn expected_keystream(before: usize, after: usize) -> Vec<u8> {
+    let mut keystream = segment_keystream(0, KEYSTREAM_SEGMENT_SIZE - before as u64, before);
+    keystream.extend(segment_keystream(1, 0, after));
+    keystream
+}
+
+#[test]
+fn keystream_continues_across_counter_limit() {
+    // ブロックの途中から読み始めて境界をまたぐ
+    let expected = expected_keystream(1000 + 13, 2000);
+
+    for backend in [Backend::Auto, Backend::Soft] {
+        // 1回で境界をまたぐ
+        let mut cipher = ChaCha20Cipher::with_backend(backend, &KEY, &NONCE);
+        cipher.seek(KEYSTREAM_SEGMENT_SIZE - 1013);
+        let mut keystream = vec![0; expected.len()];
+        cipher.apply_keystream(&mut keystream);
+        assert_eq!(keystream, expected, "{}", backend.name());
+
+        // 少しずつ適用しても同じ鍵ストリームになる
+        let mut cipher = ChaCha20Cipher::with_backend(backend, &KEY, &NONCE);
+        cipher.seek(KEYSTREAM_SEGMENT_SIZE - 1013);
+        let mut keystream = vec![0; expected.len()];
+        for chunk in keystream.chunks_mut(100) {
+            cipher.apply_keystream(chunk);
+        }
+        assert_eq!(keystream, expected, "{}", backend.name());
+    }
+}
+
+#[test]
+fn seek_lands_in_later_segments() {
+    for backend in [Backend::Auto, Backend::Soft] {
+        let mut cipher = ChaCha20Cipher::with_backend(backend, &KEY, &NONCE);
+
+        // 境界ちょうど、その後のセグメント、最初のセグメントへ戻る場合
+        for (segment, offset) in [(1, 0), (3, 12345), (0, 64), (1, 1)] {
+            cipher.seek(segment * KEYSTREAM_SEGMENT_SIZE + offset);
+            let mut keystream = vec![0; 300];
+            cipher.apply_keystream(&mut keystream);
+            assert_eq!(
+                keystream,
+                segment_keystream(segment, offset, 300),
+                "{} {} {}",
+                backend.name(),
+                segment,
+                offset
+            );
+        }
+    }
+}
+
+#[test]
+fn first_segment_is_unchanged() {
+    // 256GiBより小さいデータは以前と同じ鍵ストリーム
+    let mut cipher = ChaCha20Cipher::new(&KEY, &NONCE);
+    let mut keystream = vec![0; 100_000];
+    cipher.apply_keystream(&mut keystream);
+    assert_eq!(keystream, segment_keystream(0, 0, 100_000));
+}
+
+#[test]
+fn seekable_decryptor_accepts_positions_past_counter_limit() {
+    use crypto_tool::crypto::{Cipher, Decryptor, Encryptor, Key};
+    use std::io::{Read, Seek, SeekFrom};
+
+    let key = Key::new(KEY);
+    for cipher in Cipher::ALL {
+        let mut encrypted = Vec::new();
+        Encryptor::new(&key)
+            .cipher(cipher)
+            .encrypt(&b"data"[..], &mut encrypted)
+            .unwrap();
+        let mut reader = Decryptor::new(&key)
+            .seekable(std::io::Cursor::new(encrypted))
+            .unwrap();
+
+        // データの終わりより後ろなので何も読み込まない
+        for position in [KEYSTREAM_SEGMENT_SIZE + 1, u64::MAX / 2] {
+            assert_eq!(reader.seek(SeekFrom::Start(position)).unwrap(), position);
+            let mut decrypted = Vec::new();
+            reader.read_to_end(&mut decrypted).unwrap();
+            assert!(decrypted.is_empty(), "{}", cipher.name());
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! `--lang`と環境変数`LC_ALL`、`LC_MESSAGES`、`LANG`から、表示するメッセージの言語を選べることを確認する
+
+use std::process::Command;
+
+/// 指定した`--lang`と環境変数で、存在しない鍵ファイルを指定して復号したエラーメッセージ
+fn error_message(lang: Option<&str>, env: &[(&str, &str)]) -> String {
+    let missing =
+        std::env::temp_dir().join(format!("crypto_tool_{}_missing.c20", std::process::id()));
+    let mut command = Command::new(env!("CARGO_BIN_EXE_crypto_tool"));
+    for name in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        command.env_remove(name);
+    }
+    command.envs(env.iter().copied());
+    if let Some(lang) = lang {
+        command.args(["--lang", lang]);
+    }
+    let output = command
+        .arg("decrypt")
+        .arg("-k")
+        .arg(&missing)
+        .arg(&missing)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+const JA: &str = "鍵ファイルにアクセスできませんでした。";
+const EN: &str = "Could not access the key file.";
+
+#[test]
+fn lang_option_selects_catalog() {
+    assert!(error_message(Some("en"), &[]).contains(EN));
+    assert!(error_message(Some("ja"), &[]).contains(JA));
+    // 環境変数より`--lang`を優先する
+    assert!(error_message(Some("en"), &[("LC_ALL", "ja_JP.UTF-8")]).contains(EN));
+}
+
+#[test]
+fn locale_selects_catalog() {
+    assert!(error_message(None, &[("LANG", "ja_JP.UTF-8")]).contains(JA));
+    assert!(error_message(None, &[("LANG", "en_US.UTF-8")]).contains(EN));
+    // 日本語以外のロケールは英語にする
+    assert!(error_message(None, &[("LANG", "C")]).contains(EN));
+    // 環境変数がなければ日本語にする
+    assert!(error_message(None, &[]).contains(JA));
+}
+
+#[test]
+fn lc_all_overrides_lang() {
+    let env = [("LC_ALL", "en_US.UTF-8"), ("LANG", "ja_JP.UTF-8")];
+    assert!(error_message(None, &env).contains(EN));
+    let env = [("LC_ALL", "ja_JP.UTF-8"), ("LANG", "en_US.UTF-8")];
+    assert!(error_message(None, &env).contains(JA));
+    let env = [("LC_MESSAGES", "ja_JP.UTF-8"), ("LANG", "en_US.UTF-8")];
+    assert!(error_message(None, &env).contains(JA));
+}
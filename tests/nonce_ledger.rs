@@ -0,0 +1,172 @@
+//! ナンスの台帳が使用済みのナンスを拒否し、鍵の使用量を記録できることを確認する
+
+use crypto_tool::crypto::{
+    Cipher, Encryptor, Error, Header, Key, KeyUsage, NonceLedger, RECOMMENDED_BYTES_PER_KEY,
+    RECOMMENDED_FILES_PER_KEY,
+};
+use std::path::PathBuf;
+
+fn key() -> Key {
+    Key::new([0x42; 32])
+}
+
+/// テストごとに別の空の台帳のパス
+fn ledger_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "crypto_tool_{}_{}.ledger",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn encrypt(
+    ledger: &NonceLedger,
+    header: Option<Header>,
+    plain_text: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let key = key();
+    let encryptor = Encryptor::new(&key).ledger(ledger);
+    let encryptor = match header {
+        Some(header) => encryptor.header(header),
+        None => encryptor,
+    };
+    let mut encrypted = Vec::new();
+    encryptor.encrypt(plain_text, &mut encrypted)?;
+    Ok(encrypted)
+}
+
+#[test]
+fn reused_nonce_is_refused() {
+    let path = ledger_path("reuse");
+    let ledger = NonceLedger::open(&path).unwrap();
+    let header = Header::for_cipher(Cipher::ChaCha20Poly1305);
+
+    encrypt(&ledger, Some(header), b"first").unwrap();
+    assert!(matches!(
+        encrypt(&ledger, Some(header), b"second"),
+        Err(Error::NonceReused)
+    ));
+
+    // 別の鍵なら同じナンスを使える
+    let other_key = Key::new([7; 32]);
+    let header = Header::for_cipher(Cipher::ChaCha20Poly1305);
+    assert!(ledger.record_nonce(&key(), &header).unwrap());
+    assert!(ledger.record_nonce(&other_key, &header).unwrap());
+    assert!(!ledger.record_nonce(&key(), &header).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn usage_survives_reopening() {
+    let path = ledger_path("reopen");
+    let ledger = NonceLedger::open(&path).unwrap();
+    encrypt(&ledger, None, &[1; 1000]).unwrap();
+    encrypt(&ledger, None, &[2; 234]).unwrap();
+    let header = Header::for_cipher(Cipher::Aes256Gcm);
+    encrypt(&ledger, Some(header), b"").unwrap();
+    let usage = KeyUsage {
+        files: 3,
+        bytes: 1234,
+    };
+    assert_eq!(ledger.usage(&key()).unwrap(), usage);
+    drop(ledger);
+
+    let ledger = NonceLedger::open(&path).unwrap();
+    assert_eq!(ledger.usage(&key()).unwrap(), usage);
+    assert_eq!(
+        ledger.usage(&Key::new([7; 32])).unwrap(),
+        KeyUsage::default()
+    );
+    assert!(!ledger.record_nonce(&key(), &header).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn invalid_ledger_line_is_reported() {
+    let path = ledger_path("invalid");
+    std::fs::write(&path, "bytes abc 10\n\nnonce abc chacha20 0g\n").unwrap();
+    assert!(matches!(
+        NonceLedger::open(&path),
+        Err(Error::InvalidLedger(3))
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn other_processes_records_are_seen() {
+    // 同じファイルを別々に開いた台帳は、別のプロセスの台帳と同じように振る舞う
+    let path = ledger_path("shared");
+    let first = NonceLedger::open(&path).unwrap();
+    let second = NonceLedger::open(&path).unwrap();
+    let header = Header::for_cipher(Cipher::ChaCha20);
+    assert!(first.record_nonce(&key(), &header).unwrap());
+    assert!(!second.record_nonce(&key(), &header).unwrap());
+    second.record_bytes(&key(), 100).unwrap();
+    assert_eq!(
+        first.usage(&key()).unwrap(),
+        KeyUsage {
+            files: 1,
+            bytes: 100
+        }
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn concurrent_ledgers_accept_a_nonce_once() {
+    let path = ledger_path("concurrent");
+    let header = Header::for_cipher(Cipher::ChaCha20Poly1305);
+    let ledgers = (0..8)
+        .map(|_| NonceLedger::open(&path).unwrap())
+        .collect::<Vec<_>>();
+    let accepted = std::thread::scope(|scope| {
+        let handles = ledgers
+            .iter()
+            .map(|ledger| scope.spawn(move || ledger.record_nonce(&key(), &header).unwrap()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|accepted| *accepted)
+            .count()
+    });
+    assert_eq!(accepted, 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn torn_last_line_is_truncated() {
+    // 書き込み中に異常終了して改行で終わっていない行
+    let path = ledger_path("torn");
+    let valid = "bytes abc 10\n";
+    std::fs::write(&path, format!("{}nonce abc chacha20 00ff", valid)).unwrap();
+    let ledger = NonceLedger::open(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), valid);
+
+    ledger.record_bytes(&key(), 5).unwrap();
+    drop(ledger);
+    let ledger = NonceLedger::open(&path).unwrap();
+    assert_eq!(ledger.usage(&key()).unwrap().bytes, 5);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn near_limit_at_three_quarters() {
+    assert!(!KeyUsage::default().near_limit());
+    let files = RECOMMENDED_FILES_PER_KEY / 4 * 3;
+    assert!(!KeyUsage {
+        files: files - 1,
+        bytes: 0
+    }
+    .near_limit());
+    assert!(KeyUsage { files, bytes: 0 }.near_limit());
+    let bytes = RECOMMENDED_BYTES_PER_KEY / 4 * 3;
+    assert!(!KeyUsage {
+        files: 0,
+        bytes: bytes - 1
+    }
+    .near_limit());
+    assert!(KeyUsage { files: 0, bytes }.near_limit());
+}
@@ -0,0 +1,150 @@
+//! 暗号化・復号に失敗した場合に、途中まで書き込んだファイルが残らないことを確認する
+
+mod common;
+
+use common::TempDir;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&std::ffi::OsStr], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crypto_tool"))
+        .args(["--lang", "en"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // 失敗したプロセスは入力を読み終わる前に終了することがある
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().unwrap()
+}
+
+/// 鍵ファイル、平文、暗号文を用意する
+fn prepare(dir: &std::path::Path) -> (PathBuf, PathBuf) {
+    let key = dir.join("key.bin");
+    std::fs::write(&key, [0x42; 32]).unwrap();
+    let plain = dir.join("data.bin");
+    // 並列に復号される大きさにして、最後のチャンクを改ざんする
+    let plain_text = (0..3_000_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    std::fs::write(&plain, plain_text).unwrap();
+    let encrypted = dir.join("data.bin.c20");
+    let output = run(
+        &[
+            "encrypt".as_ref(),
+            "--cipher".as_ref(),
+            "chacha20poly1305".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            plain.as_os_str(),
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    let mut tampered = std::fs::read(&encrypted).unwrap();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    std::fs::write(&encrypted, tampered).unwrap();
+    std::fs::remove_file(&plain).unwrap();
+    (key, encrypted)
+}
+
+fn file_names(dir: &std::path::Path) -> Vec<String> {
+    let mut names = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn failed_decryption_leaves_no_file() {
+    let dir = TempDir::new("partial_file");
+    let (key, encrypted) = prepare(&dir);
+    let output = run(
+        &[
+            "decrypt".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            encrypted.as_os_str(),
+        ],
+        b"",
+    );
+    assert!(!output.status.success());
+    assert_eq!(file_names(&dir), ["data.bin.c20", "key.bin"]);
+}
+
+#[test]
+fn failed_decryption_keeps_existing_file() {
+    let dir = TempDir::new("partial_existing");
+    let (key, encrypted) = prepare(&dir);
+    let existing = dir.join("data.bin");
+    std::fs::write(&existing, b"keep").unwrap();
+    let output = run(
+        &[
+            "decrypt".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            encrypted.as_os_str(),
+        ],
+        b"",
+    );
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(&existing).unwrap(), b"keep");
+    assert_eq!(file_names(&dir), ["data.bin", "data.bin.c20", "key.bin"]);
+}
+
+#[test]
+fn failed_stream_decryption_leaves_no_file() {
+    let dir = TempDir::new("partial_stream");
+    let (key, encrypted) = prepare(&dir);
+    let output_path = dir.join("data.bin");
+    let output = run(
+        &[
+            "decrypt".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            "-o".as_ref(),
+            output_path.as_os_str(),
+            "-".as_ref(),
+        ],
+        &std::fs::read(&encrypted).unwrap(),
+    );
+    assert!(!output.status.success());
+    assert_eq!(file_names(&dir), ["data.bin.c20", "key.bin"]);
+}
+
+#[test]
+fn successful_decryption_leaves_only_the_output() {
+    let dir = TempDir::new("partial_success");
+    let key = dir.join("key.bin");
+    std::fs::write(&key, [0x42; 32]).unwrap();
+    let plain = dir.join("data.bin");
+    std::fs::write(&plain, b"hello").unwrap();
+    let encrypt = run(
+        &[
+            "encrypt".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            plain.as_os_str(),
+        ],
+        b"",
+    );
+    assert!(encrypt.status.success());
+    std::fs::write(&plain, b"old").unwrap();
+    let encrypted = dir.join("data.bin.c20");
+    let decrypt = run(
+        &[
+            "decrypt".as_ref(),
+            "-k".as_ref(),
+            key.as_os_str(),
+            encrypted.as_os_str(),
+        ],
+        b"",
+    );
+    assert!(decrypt.status.success());
+    assert_eq!(std::fs::read(&plain).unwrap(), b"hello");
+    assert_eq!(file_names(&dir), ["data.bin", "data.bin.c20", "key.bin"]);
+}
@@ -0,0 +1,72 @@
+//! 暗号化の後に表示する処理結果の各項目と、サイズと処理速度を10^6byteを1MBとして計算していることを確認する
+
+mod common;
+
+use common::TempDir;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// 1MB(10^6byte)と1MiBで小数第2位まで異なるサイズ
+const SIZE: usize = 2_500_000;
+
+fn encrypt(dir: &Path, json: bool) -> Output {
+    let key = dir.join("key.bin");
+    std::fs::write(&key, [0x42; 32]).unwrap();
+    let plain = dir.join("data.bin");
+    std::fs::write(&plain, vec![0x5a; SIZE]).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_crypto_tool"));
+    command.args(["--lang", "en", "encrypt", "--cipher", "chacha20poly1305"]);
+    if json {
+        command.arg("--json");
+    }
+    command.arg("-k").arg(&key).arg(&plain).output().unwrap()
+}
+
+/// 人が読める形式の`名前: 値`の値
+fn field<'a>(text: &'a str, name: &str) -> &'a str {
+    text.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+        .unwrap_or_else(|| panic!("{} is missing: {}", name, text))
+}
+
+#[test]
+fn summary_goes_to_stderr() {
+    let dir = TempDir::new("report_text");
+    let output = encrypt(&dir, false);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // 2500000 / 1024 / 1024 = 2.38 ではなく 2500000 / 10^6 = 2.50
+    assert_eq!(field(&stderr, "Processed"), "2.50 MB (2500000 bytes)");
+    let seconds = field(&stderr, "Time").strip_suffix(" s").unwrap();
+    assert!(seconds.parse::<f64>().unwrap() >= 0.0);
+    let throughput = field(&stderr, "Throughput").strip_suffix(" MB/s").unwrap();
+    assert!(throughput.parse::<f64>().unwrap() > 0.0);
+    assert!(field(&stderr, "Cipher").starts_with("chacha20poly1305 ("));
+    let fingerprint = field(&stderr, "Key fingerprint");
+    assert!(fingerprint.len() == 16 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(
+        Path::new(field(&stderr, "Output")),
+        dir.join("data.bin.c20")
+    );
+}
+
+#[test]
+fn throughput_uses_decimal_megabytes() {
+    let dir = TempDir::new("report_json");
+    let output = encrypt(&dir, true);
+    assert!(output.status.success());
+    let json = String::from_utf8_lossy(&output.stdout);
+    let value: serde_json::Value = serde_json::from_str(json.trim()).unwrap();
+
+    assert_eq!(value["bytes"], SIZE);
+    let seconds = value["seconds"].as_f64().unwrap();
+    let megabytes_per_second = value["megabytes_per_second"].as_f64().unwrap();
+    let megabytes = megabytes_per_second * seconds;
+    assert!(
+        (megabytes - SIZE as f64 / 1_000_000.0).abs() < 1e-6,
+        "{}",
+        megabytes
+    );
+}
@@ -0,0 +1,28 @@
+//! ビルドしたCLIの`selftest`がすべての確認に成功することを確認する
+
+use std::process::Command;
+
+#[test]
+fn selftest_passes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_crypto_tool"))
+        .args(["--lang", "en", "selftest", "--json"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains(r#""operation":"selftest","status":"success""#));
+    assert!(stdout.contains("RFC 8439 2.8.2 ChaCha20-Poly1305"));
+    assert!(!stdout.contains(r#""passed":false"#));
+}
+
+#[test]
+fn selftest_checks_soft_backend() {
+    let output = Command::new(env!("CARGO_BIN_EXE_crypto_tool"))
+        .args(["--lang", "en", "--backend", "soft", "selftest"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("ok: RFC 8439 2.4.2 ChaCha20 (soft)"));
+    assert!(stdout.ends_with("All checks passed.\n"));
+}
@@ -0,0 +1,47 @@
+//! ポータブル実装(`--backend soft`)のChaCha20が、`chacha20`クレートと同じ鍵ストリームになることを確認する
+//! 乱数で選んだ位置と長さで、ブロックの境界と32bitのブロックカウンタの上限の手前を確かめる
+
+use chacha20::cipher::{NewCipher, StreamCipher, StreamCipherSeek};
+use crypto_tool::crypto::{Backend, ChaCha20Cipher, KEYSTREAM_SEGMENT_SIZE};
+use rand::{Rng, SeedableRng};
+
+/// `chacha20`クレートで求めた`offset`からの鍵ストリーム
+fn expected_keystream(key: &[u8; 32], nonce: &[u8; 12], offset: u64, size: usize) -> Vec<u8> {
+    let mut cipher = chacha20::ChaCha20::new(
+        chacha20::Key::from_slice(key),
+        chacha20::Nonce::from_slice(nonce),
+    );
+    cipher.seek(offset);
+    let mut keystream = vec![0; size];
+    cipher.apply_keystream(&mut keystream);
+    keystream
+}
+
+#[test]
+fn soft_matches_chacha20_crate() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(8439);
+    for _ in 0..200 {
+        let key = rng.gen::<[u8; 32]>();
+        let nonce = rng.gen::<[u8; 12]>();
+        let size = rng.gen_range(0..1000);
+        // 半分はブロックカウンタの上限の直前(最後の数ブロック)から読む
+        let offset = match rng.gen::<bool>() {
+            true => rng.gen_range(0..KEYSTREAM_SEGMENT_SIZE - size as u64),
+            false => KEYSTREAM_SEGMENT_SIZE - size as u64 - rng.gen_range(0..256),
+        };
+        let expected = expected_keystream(&key, &nonce, offset, size);
+
+        let mut cipher = ChaCha20Cipher::with_backend(Backend::Soft, &key, &nonce);
+        cipher.seek(offset);
+        let mut keystream = vec![0; size];
+        // ブロックの途中で区切りながら適用する
+        let mut rest = &mut keystream[..];
+        while !rest.is_empty() {
+            let part = rng.gen_range(1..=rest.len().min(150));
+            let (head, tail) = rest.split_at_mut(part);
+            cipher.apply_keystream(head);
+            rest = tail;
+        }
+        assert_eq!(keystream, expected, "offset {} size {}", offset, size);
+    }
+}